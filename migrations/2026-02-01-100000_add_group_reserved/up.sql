@@ -0,0 +1 @@
+ALTER TABLE groups ADD COLUMN reserved BOOLEAN NOT NULL DEFAULT 0;
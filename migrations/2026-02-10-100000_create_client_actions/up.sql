@@ -0,0 +1,12 @@
+CREATE TABLE client_actions (
+    client_id TEXT PRIMARY KEY NOT NULL,
+    group_id TEXT NOT NULL,
+    action TEXT NOT NULL,
+    status TEXT NOT NULL,
+    message TEXT,
+    performed_at TIMESTAMP NOT NULL,
+    received_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    -- Who performed the action, a reused client ID from another post, or from a post for an
+    -- action of an admin, is refused
+    actor TEXT NOT NULL DEFAULT ''
+);
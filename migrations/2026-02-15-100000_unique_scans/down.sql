@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS scans_group_id_post_id;
+
+INSERT INTO scans (id, group_id, post_id, arrival_time, departure_time)
+SELECT id, group_id, post_id, arrival_time, departure_time FROM duplicate_scans;
+
+DROP TABLE IF EXISTS duplicate_scans;
@@ -0,0 +1,28 @@
+CREATE TABLE duplicate_scans (
+    id TEXT PRIMARY KEY NOT NULL,
+    group_id TEXT NOT NULL,
+    post_id TEXT NOT NULL,
+    arrival_time TIMESTAMP NOT NULL,
+    departure_time TIMESTAMP,
+    -- Scan that was kept for the same group and post
+    kept_scan_id TEXT NOT NULL,
+    detected_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Keep the earliest scan per group and post, move the others aside for review
+INSERT INTO duplicate_scans (id, group_id, post_id, arrival_time, departure_time, kept_scan_id)
+SELECT id, group_id, post_id, arrival_time, departure_time, kept_scan_id
+FROM (
+    SELECT s.*, (
+        SELECT k.id FROM scans k
+        WHERE k.group_id = s.group_id AND k.post_id = s.post_id
+        ORDER BY k.arrival_time, k.rowid
+        LIMIT 1
+    ) AS kept_scan_id
+    FROM scans s
+)
+WHERE id <> kept_scan_id;
+
+DELETE FROM scans WHERE id IN (SELECT id FROM duplicate_scans);
+
+CREATE UNIQUE INDEX scans_group_id_post_id ON scans (group_id, post_id);
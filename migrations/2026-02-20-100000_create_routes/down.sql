@@ -0,0 +1,3 @@
+ALTER TABLE groups DROP COLUMN route_id;
+DROP TABLE IF EXISTS route_posts;
+DROP TABLE IF EXISTS hike_routes;
@@ -0,0 +1,24 @@
+CREATE TABLE hike_routes (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE route_posts (
+    route_id TEXT NOT NULL REFERENCES hike_routes(id),
+    post_id TEXT NOT NULL REFERENCES posts(id),
+    position INTEGER NOT NULL,
+    PRIMARY KEY (route_id, post_id)
+);
+
+-- Routes already used by groups visit every post, as before
+INSERT INTO hike_routes (id, name)
+SELECT lower(hex(randomblob(4))), route FROM (SELECT DISTINCT trim(route) AS route FROM groups WHERE trim(route) <> '');
+
+INSERT INTO route_posts (route_id, post_id, position)
+SELECT r.id, p.id, p.post_order FROM hike_routes r CROSS JOIN posts p;
+
+-- Groups refer to their route by ID, `route` keeps the name of that route for display
+ALTER TABLE groups ADD COLUMN route_id TEXT;
+UPDATE groups SET route_id = (SELECT id FROM hike_routes WHERE name = trim(groups.route));
+UPDATE groups SET route = coalesce((SELECT name FROM hike_routes WHERE id = groups.route_id), '');
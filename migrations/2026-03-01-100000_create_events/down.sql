@@ -0,0 +1,31 @@
+DROP INDEX IF EXISTS groups_event_id;
+DROP INDEX IF EXISTS posts_event_id;
+
+CREATE TABLE route_posts_backup AS SELECT * FROM route_posts;
+DROP TABLE route_posts;
+
+CREATE TABLE hike_routes_old (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+INSERT OR IGNORE INTO hike_routes_old (id, name, created_at)
+SELECT id, name, created_at FROM hike_routes;
+DROP TABLE hike_routes;
+ALTER TABLE hike_routes_old RENAME TO hike_routes;
+
+CREATE TABLE route_posts (
+    route_id TEXT NOT NULL REFERENCES hike_routes(id),
+    post_id TEXT NOT NULL REFERENCES posts(id),
+    position INTEGER NOT NULL,
+    PRIMARY KEY (route_id, post_id)
+);
+INSERT INTO route_posts
+SELECT route_id, post_id, position FROM route_posts_backup
+WHERE route_id IN (SELECT id FROM hike_routes);
+DROP TABLE route_posts_backup;
+
+ALTER TABLE posts DROP COLUMN event_id;
+ALTER TABLE groups DROP COLUMN event_id;
+
+DROP TABLE IF EXISTS events;
@@ -0,0 +1,44 @@
+CREATE TABLE events (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'draft',
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Everything recorded so far belongs to the first event, which is the active one
+INSERT INTO events (id, name, status)
+VALUES (lower(hex(randomblob(4))), 'Hike ' || strftime('%Y', 'now'), 'active');
+
+ALTER TABLE groups ADD COLUMN event_id TEXT NOT NULL DEFAULT '';
+UPDATE groups SET event_id = (SELECT id FROM events);
+
+ALTER TABLE posts ADD COLUMN event_id TEXT NOT NULL DEFAULT '';
+UPDATE posts SET event_id = (SELECT id FROM events);
+
+-- Route names are unique per event, rebuild the routes table with route_posts out of the way
+CREATE TABLE route_posts_backup AS SELECT * FROM route_posts;
+DROP TABLE route_posts;
+
+CREATE TABLE hike_routes_new (
+    id TEXT PRIMARY KEY NOT NULL,
+    event_id TEXT NOT NULL REFERENCES events(id),
+    name TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (event_id, name)
+);
+INSERT INTO hike_routes_new (id, event_id, name, created_at)
+SELECT id, (SELECT id FROM events), name, created_at FROM hike_routes;
+DROP TABLE hike_routes;
+ALTER TABLE hike_routes_new RENAME TO hike_routes;
+
+CREATE TABLE route_posts (
+    route_id TEXT NOT NULL REFERENCES hike_routes(id),
+    post_id TEXT NOT NULL REFERENCES posts(id),
+    position INTEGER NOT NULL,
+    PRIMARY KEY (route_id, post_id)
+);
+INSERT INTO route_posts SELECT route_id, post_id, position FROM route_posts_backup;
+DROP TABLE route_posts_backup;
+
+CREATE INDEX groups_event_id ON groups (event_id);
+CREATE INDEX posts_event_id ON posts (event_id);
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS scores;
+DROP TABLE IF EXISTS post_criteria;
+ALTER TABLE posts DROP COLUMN max_points;
@@ -0,0 +1,24 @@
+-- Single score up to this many points, for posts without criteria
+ALTER TABLE posts ADD COLUMN max_points INTEGER;
+
+CREATE TABLE post_criteria (
+    id TEXT PRIMARY KEY NOT NULL,
+    post_id TEXT NOT NULL REFERENCES posts(id),
+    name TEXT NOT NULL,
+    max_points INTEGER NOT NULL,
+    position INTEGER NOT NULL
+);
+
+CREATE INDEX post_criteria_post_id ON post_criteria (post_id);
+
+-- Points a group scored at a post, per criterion or 'total' for posts without criteria
+CREATE TABLE scores (
+    group_id TEXT NOT NULL REFERENCES groups(id),
+    post_id TEXT NOT NULL REFERENCES posts(id),
+    criterion_id TEXT NOT NULL,
+    points INTEGER NOT NULL,
+    -- Post of the post holder that entered the score, NULL for an admin
+    judged_by TEXT,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (group_id, post_id, criterion_id)
+);
@@ -0,0 +1,15 @@
+-- Penalties and bonuses per group. Time in seconds, positive adds time. Points, positive adds
+-- points.
+CREATE TABLE adjustments (
+    id TEXT PRIMARY KEY NOT NULL,
+    group_id TEXT NOT NULL REFERENCES groups(id),
+    kind TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    reason TEXT NOT NULL,
+    author TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    -- Scan an automatic penalty was given for, the penalty goes together with the scan
+    scan_id TEXT
+);
+
+CREATE INDEX adjustments_group_id ON adjustments (group_id);
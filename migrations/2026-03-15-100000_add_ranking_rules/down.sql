@@ -0,0 +1,3 @@
+ALTER TABLE events DROP COLUMN max_post_time;
+ALTER TABLE events DROP COLUMN min_idle_credit;
+ALTER TABLE events DROP COLUMN ranking_criteria;
@@ -0,0 +1,6 @@
+-- Ordered ranking criteria, comma separated, the first that differs decides
+ALTER TABLE events ADD COLUMN ranking_criteria TEXT NOT NULL DEFAULT 'complete,total_time';
+-- Seconds of idle time credited per visited post, also when a group left sooner
+ALTER TABLE events ADD COLUMN min_idle_credit INTEGER NOT NULL DEFAULT 0;
+-- Seconds of idle time per post at most, longer stays count as walking time
+ALTER TABLE events ADD COLUMN max_post_time INTEGER;
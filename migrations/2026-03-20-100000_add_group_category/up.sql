@@ -0,0 +1,2 @@
+-- Free form category to rank groups in, such as an age class
+ALTER TABLE groups ADD COLUMN category TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,5 @@
+ALTER TABLE events DROP COLUMN public_snapshot;
+ALTER TABLE events DROP COLUMN public_revealed;
+ALTER TABLE events DROP COLUMN public_freeze_at;
+ALTER TABLE events DROP COLUMN public_fields;
+ALTER TABLE events DROP COLUMN public_ranking;
@@ -0,0 +1,8 @@
+-- Public leaderboard without login, showing only the chosen columns
+ALTER TABLE events ADD COLUMN public_ranking BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE events ADD COLUMN public_fields TEXT NOT NULL DEFAULT 'name,category';
+-- From this time on the public leaderboard shows the standings as they were, until revealed
+ALTER TABLE events ADD COLUMN public_freeze_at TIMESTAMP;
+ALTER TABLE events ADD COLUMN public_revealed BOOLEAN NOT NULL DEFAULT 0;
+-- Public standings as they were when the leaderboard froze, as JSON
+ALTER TABLE events ADD COLUMN public_snapshot TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE scans DROP COLUMN out_of_order;
+ALTER TABLE events DROP COLUMN scan_order;
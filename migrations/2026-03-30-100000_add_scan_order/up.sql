@@ -0,0 +1,4 @@
+-- Whether scans have to follow the route order: `off`, `warn` or `block`
+ALTER TABLE events ADD COLUMN scan_order TEXT NOT NULL DEFAULT 'off';
+-- Scans recorded while an earlier post on the route was not visited yet
+ALTER TABLE scans ADD COLUMN out_of_order BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,4 @@
+ALTER TABLE posts DROP COLUMN hours_penalty;
+ALTER TABLE posts DROP COLUMN cutoff_at;
+ALTER TABLE posts DROP COLUMN closes_at;
+ALTER TABLE posts DROP COLUMN opens_at;
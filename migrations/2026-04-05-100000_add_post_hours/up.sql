@@ -0,0 +1,6 @@
+-- Times a post is staffed, groups arriving after the cut-off are too late
+ALTER TABLE posts ADD COLUMN opens_at TIMESTAMP;
+ALTER TABLE posts ADD COLUMN closes_at TIMESTAMP;
+ALTER TABLE posts ADD COLUMN cutoff_at TIMESTAMP;
+-- Seconds of penalty given automatically for arriving before opening or after closing
+ALTER TABLE posts ADD COLUMN hours_penalty INTEGER;
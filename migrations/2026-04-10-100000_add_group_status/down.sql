@@ -0,0 +1,4 @@
+ALTER TABLE events DROP COLUMN max_course_time;
+ALTER TABLE groups DROP COLUMN status_override;
+ALTER TABLE groups DROP COLUMN status_reason;
+ALTER TABLE groups DROP COLUMN status;
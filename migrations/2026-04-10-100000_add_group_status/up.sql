@@ -0,0 +1,8 @@
+-- `dnf`, `dsq` or `withdrawn` for groups out of the race, empty while the status follows from
+-- the start and finish time
+ALTER TABLE groups ADD COLUMN status TEXT NOT NULL DEFAULT '';
+ALTER TABLE groups ADD COLUMN status_reason TEXT NOT NULL DEFAULT '';
+-- Status set by an admin, the max course time check leaves these groups alone
+ALTER TABLE groups ADD COLUMN status_override BOOLEAN NOT NULL DEFAULT 0;
+-- Seconds a group may take from start to finish before it did not finish
+ALTER TABLE events ADD COLUMN max_course_time INTEGER;
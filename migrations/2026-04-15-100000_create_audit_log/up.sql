@@ -0,0 +1,17 @@
+-- Append-only log of changes to scans and group timers. Actor is `admin` or the ID of the post
+-- of the post holder. Before and after hold the times as JSON, empty when the scan did not exist.
+-- No foreign keys, entries outlive the groups and posts they are about.
+CREATE TABLE audit_log (
+    id TEXT PRIMARY KEY NOT NULL,
+    event_id TEXT NOT NULL,
+    group_id TEXT NOT NULL,
+    post_id TEXT,
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    before TEXT,
+    after TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX audit_log_group_id ON audit_log (group_id);
+CREATE INDEX audit_log_post_id ON audit_log (post_id);
@@ -0,0 +1,7 @@
+DELETE FROM scans WHERE deleted_at IS NOT NULL;
+DROP INDEX scans_group_id_post_id;
+CREATE UNIQUE INDEX scans_group_id_post_id ON scans (group_id, post_id);
+
+ALTER TABLE scans DROP COLUMN deleted_at;
+ALTER TABLE posts DROP COLUMN deleted_at;
+ALTER TABLE groups DROP COLUMN deleted_at;
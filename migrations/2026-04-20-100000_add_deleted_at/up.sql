@@ -0,0 +1,8 @@
+-- Deleted groups, posts and scans go to the trash first, and can be restored until purged
+ALTER TABLE groups ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE posts ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE scans ADD COLUMN deleted_at TIMESTAMP;
+
+-- A group can arrive at a post again after its scan there was deleted
+DROP INDEX scans_group_id_post_id;
+CREATE UNIQUE INDEX scans_group_id_post_id ON scans (group_id, post_id) WHERE deleted_at IS NULL;
@@ -0,0 +1,821 @@
+use std::fmt;
+
+use chrono::{NaiveDateTime, TimeDelta};
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use diesel::sqlite::SqliteConnection;
+use serde::Serialize;
+
+use crate::models::{
+    admin_actor, Adjustment, AuditEntry, AuditTimes, ClientAction, Event, Group, NewScan, Post,
+    RouteMap, Scan, ADJUSTMENT_TIME, AUDIT_ADMIN, AUDIT_ARRIVE, AUDIT_FINISH, AUDIT_LEAVE,
+    AUDIT_START, AUDIT_UNDO, SCAN_ORDER_BLOCK, SCAN_ORDER_OFF,
+};
+use crate::sync::SyncStatus;
+
+/// Author of adjustments given without an admin, e.g. for arriving outside opening hours
+const AUTOMATIC_AUTHOR: &str = "Automatisch";
+
+/// Something that can happen to a group while it is on the hike.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanAction {
+    StartTimer,
+    StopTimer,
+    Arrive(String),
+    Leave(String),
+}
+
+impl ScanAction {
+    /// Parse an action as submitted by the scan page form.
+    pub fn parse(action: &str) -> Option<Self> {
+        match action {
+            "__START_TIMER__" => Some(ScanAction::StartTimer),
+            "__STOP_TIMER__" => Some(ScanAction::StopTimer),
+            _ => {
+                if let Some(post_id) = action.strip_prefix("ARRIVE_") {
+                    Some(ScanAction::Arrive(post_id.to_string()))
+                } else {
+                    action
+                        .strip_prefix("LEAVE_")
+                        .map(|post_id| ScanAction::Leave(post_id.to_string()))
+                }
+            }
+        }
+    }
+
+    /// The action an audit log entry records, if it records a scan action
+    fn from_audit(entry: &AuditEntry) -> Option<Self> {
+        match (entry.action.as_str(), &entry.post_id) {
+            (AUDIT_START, None) => Some(ScanAction::StartTimer),
+            (AUDIT_FINISH, None) => Some(ScanAction::StopTimer),
+            (AUDIT_ARRIVE, Some(post_id)) => Some(ScanAction::Arrive(post_id.clone())),
+            (AUDIT_LEAVE, Some(post_id)) => Some(ScanAction::Leave(post_id.clone())),
+            _ => None,
+        }
+    }
+
+    /// Post the action applies to, if any
+    pub fn post_id(&self) -> Option<&str> {
+        match self {
+            ScanAction::Arrive(post_id) | ScanAction::Leave(post_id) => Some(post_id),
+            ScanAction::StartTimer | ScanAction::StopTimer => None,
+        }
+    }
+}
+
+impl fmt::Display for ScanAction {
+    /// Formats the action as submitted by the scan page form
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanAction::StartTimer => write!(f, "__START_TIMER__"),
+            ScanAction::StopTimer => write!(f, "__STOP_TIMER__"),
+            ScanAction::Arrive(post_id) => write!(f, "ARRIVE_{post_id}"),
+            ScanAction::Leave(post_id) => write!(f, "LEAVE_{post_id}"),
+        }
+    }
+}
+
+/// Who performs an action: an admin, or a post holder limited to their own post.
+#[derive(Debug, Clone)]
+pub struct Actor {
+    pub is_admin: bool,
+    pub post_id: Option<String>,
+    /// Login session or API token of an admin
+    pub admin_session: Option<String>,
+}
+
+impl Actor {
+    pub fn may_perform(&self, action: &ScanAction) -> bool {
+        if self.is_admin {
+            return true;
+        }
+        match action.post_id() {
+            // Post holders can only scan for their assigned post
+            Some(post_id) => self.post_id.as_deref() == Some(post_id),
+            // Starting and stopping the timer is admin only
+            None => false,
+        }
+    }
+
+    /// Admins score every post, post holders only their own post
+    pub fn may_score(&self, post_id: &str) -> bool {
+        self.is_admin || self.post_id.as_deref() == Some(post_id)
+    }
+
+    /// How the actor is recorded in the audit log
+    pub fn audit_name(&self) -> String {
+        if self.is_admin {
+            self.admin_session
+                .as_deref()
+                .map_or_else(|| AUDIT_ADMIN.to_string(), admin_actor)
+        } else {
+            self.post_id.clone().unwrap_or_default()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ActionError {
+    GroupNotFound,
+    PostNotFound,
+    Forbidden,
+    /// The group belongs to an event that is not active anymore
+    EventClosed,
+    /// The action contradicts what is already recorded
+    Conflict(String),
+    /// The group skipped a post on its route and the event blocks that
+    OutOfOrder(String),
+    Database(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for ActionError {
+    fn from(err: diesel::result::Error) -> Self {
+        ActionError::Database(err)
+    }
+}
+
+/// State of the group after performing an action, and the scan it touched.
+#[derive(Debug, Serialize)]
+pub struct ActionOutcome {
+    pub group: Group,
+    pub scan: Option<Scan>,
+    /// The request was performed before, nothing changed now
+    pub replayed: bool,
+}
+
+/// Perform a scan action for a group at time `at`, with the same rules for every client.
+pub fn perform(
+    conn: &mut SqliteConnection,
+    actor: &Actor,
+    group_id: &str,
+    action: &ScanAction,
+    at: NaiveDateTime,
+) -> Result<ActionOutcome, ActionError> {
+    let group = authorize(conn, actor, group_id, action)?;
+    perform_unchecked(conn, actor, group, action, at)
+}
+
+/// Perform an action once per `request_id`. Repeating a request, e.g. by a double submit or a
+/// browser retry, returns the current state without performing the action again. Reusing a
+/// `request_id` for another action, group or actor is a conflict.
+pub fn perform_once(
+    conn: &mut SqliteConnection,
+    actor: &Actor,
+    request_id: Option<&str>,
+    group_id: &str,
+    action: &ScanAction,
+    at: NaiveDateTime,
+) -> Result<ActionOutcome, ActionError> {
+    let Some(request_id) = request_id else {
+        return perform(conn, actor, group_id, action, at);
+    };
+
+    conn.transaction(|conn| {
+        if let Some(existing) = ClientAction::get_by_client_id(conn, request_id)? {
+            if !existing.matches(group_id, &action.to_string(), &actor.audit_name()) {
+                return Err(ActionError::Conflict(
+                    "Deze aanvraag is al voor een andere actie gebruikt".to_string(),
+                ));
+            }
+            let group = authorize(conn, actor, group_id, action)?;
+            let scan = match action.post_id() {
+                Some(post_id) => Scan::get_by_group_and_post(conn, &group.id, post_id)?,
+                None => None,
+            };
+            return Ok(ActionOutcome {
+                group,
+                scan,
+                replayed: true,
+            });
+        }
+
+        let outcome = perform(conn, actor, group_id, action, at)?;
+        ClientAction::insert(
+            conn,
+            &ClientAction {
+                client_id: request_id.to_string(),
+                group_id: group_id.to_string(),
+                action: action.to_string(),
+                status: SyncStatus::Applied.as_str().to_string(),
+                message: None,
+                performed_at: at,
+                received_at: at,
+                actor: actor.audit_name(),
+            },
+        )?;
+        Ok(outcome)
+    })
+}
+
+/// Like [`perform`], but refuses actions that contradict what is already recorded instead of
+/// applying them anyway. Used for actions replayed later, which may have been overtaken by
+/// another device.
+pub fn replay(
+    conn: &mut SqliteConnection,
+    actor: &Actor,
+    group_id: &str,
+    action: &ScanAction,
+    at: NaiveDateTime,
+) -> Result<ActionOutcome, ActionError> {
+    let group = authorize(conn, actor, group_id, action)?;
+
+    let conflict = match action {
+        ScanAction::StartTimer => group
+            .start_time
+            .map(|t| format!("Groep is al gestart om {}", t.format("%H:%M:%S"))),
+        ScanAction::StopTimer => group
+            .finish_time
+            .map(|t| format!("Groep is al gefinisht om {}", t.format("%H:%M:%S"))),
+        ScanAction::Arrive(post_id) => {
+            Scan::get_by_group_and_post(conn, &group.id, post_id)?.map(|s| {
+                format!(
+                    "Groep is al aangekomen om {}",
+                    s.arrival_time.format("%H:%M:%S")
+                )
+            })
+        }
+        ScanAction::Leave(post_id) => {
+            match Scan::get_by_group_and_post(conn, &group.id, post_id)? {
+                None => Some("Groep is nog niet aangekomen bij deze post".to_string()),
+                Some(scan) => match scan.departure_time {
+                    Some(t) => Some(format!(
+                        "Groep is al vertrokken om {}",
+                        t.format("%H:%M:%S")
+                    )),
+                    None if at < scan.arrival_time => {
+                        Some("Vertrektijd ligt voor de aankomsttijd".to_string())
+                    }
+                    None => None,
+                },
+            }
+        }
+    };
+    if let Some(message) = conflict {
+        return Err(ActionError::Conflict(message));
+    }
+
+    perform_unchecked(conn, actor, group, action, at)
+}
+
+/// Posts before `post_id` on the route of the group that it did not arrive at before `at`
+pub fn skipped_posts(
+    conn: &mut SqliteConnection,
+    group: &Group,
+    post_id: &str,
+    at: NaiveDateTime,
+) -> QueryResult<Vec<Post>> {
+    let posts = RouteMap::load(conn, &group.event_id)?.posts_for(group.route_id.as_deref());
+    let Some(position) = posts.iter().position(|p| p.id == post_id) else {
+        return Ok(Vec::new());
+    };
+    let scans = Scan::get_by_group(conn, &group.id)?;
+    Ok(posts
+        .into_iter()
+        .take(position)
+        .filter(|post| {
+            !scans
+                .iter()
+                .any(|s| s.post_id == post.id && s.arrival_time <= at)
+        })
+        .collect())
+}
+
+/// Whether arriving at `post_id` at `at` skips a post on the route of the group, as far as the
+/// event checks the order. Refused when the event blocks this, unless an admin overrides it.
+pub fn check_order(
+    conn: &mut SqliteConnection,
+    actor: &Actor,
+    group: &Group,
+    post_id: &str,
+    at: NaiveDateTime,
+) -> Result<bool, ActionError> {
+    let scan_order = Event::get_by_id(conn, &group.event_id)?
+        .map(|e| e.scan_order)
+        .unwrap_or_default();
+    if scan_order.is_empty() || scan_order == SCAN_ORDER_OFF {
+        return Ok(false);
+    }
+
+    let skipped = skipped_posts(conn, group, post_id, at)?;
+    if skipped.is_empty() {
+        return Ok(false);
+    }
+    if scan_order == SCAN_ORDER_BLOCK && !actor.is_admin {
+        let posts: Vec<String> = skipped
+            .iter()
+            .map(|p| format!("{}. {}", p.post_order, p.name))
+            .collect();
+        return Err(ActionError::OutOfOrder(format!(
+            "Groep is nog niet langs {} geweest",
+            posts.join(", ")
+        )));
+    }
+    Ok(true)
+}
+
+/// The most recent action of the actor, if it is a scan action on this group performed less than
+/// `window` before `now`
+pub fn undoable(
+    conn: &mut SqliteConnection,
+    actor: &Actor,
+    group: &Group,
+    now: NaiveDateTime,
+    window: TimeDelta,
+) -> QueryResult<Option<AuditEntry>> {
+    let entry = AuditEntry::last_by_actor(conn, &group.event_id, &actor.audit_name())?;
+    Ok(entry.filter(|entry| {
+        entry.group_id == group.id
+            && now - entry.created_at <= window
+            && ScanAction::from_audit(entry).is_some()
+    }))
+}
+
+/// Revert the scan action recorded in the audit log as `entry_id`, which must still be
+/// [`undoable`], and return the reverted action. Refused when the scan or timer changed since.
+pub fn undo(
+    conn: &mut SqliteConnection,
+    actor: &Actor,
+    group_id: &str,
+    entry_id: &str,
+    now: NaiveDateTime,
+    window: TimeDelta,
+) -> Result<ScanAction, ActionError> {
+    conn.transaction(|conn| {
+        let group = Group::get_by_id(conn, group_id)?.ok_or(ActionError::GroupNotFound)?;
+        let (entry, action) = undoable(conn, actor, &group, now, window)?
+            .filter(|entry| entry.id == entry_id)
+            .and_then(|entry| ScanAction::from_audit(&entry).map(|action| (entry, action)))
+            .ok_or_else(|| {
+                ActionError::Conflict(
+                    "Deze actie kan niet meer ongedaan worden gemaakt".to_string(),
+                )
+            })?;
+        let group = authorize(conn, actor, group_id, &action)?;
+
+        let scan = match action.post_id() {
+            Some(post_id) => Scan::get_by_group_and_post(conn, &group.id, post_id)?,
+            None => None,
+        };
+        let current = match &scan {
+            Some(scan) => Some(AuditTimes::of_scan(scan)),
+            None if action.post_id().is_some() => None,
+            None => Some(AuditTimes::of_group(&group)),
+        };
+        if current != entry.after_times() {
+            return Err(ActionError::Conflict(
+                "De groep is na deze actie al gewijzigd".to_string(),
+            ));
+        }
+
+        let before = entry.before_times();
+        match (&action, &scan, before) {
+            (ScanAction::Arrive(_), Some(scan), None) => {
+                Scan::purge(conn, &scan.id)?;
+            }
+            (_, Some(scan), Some(times)) => {
+                if let Some(arrival) = times.from {
+                    Scan::set_arrival_time(conn, &scan.id, arrival)?;
+                }
+                match times.until {
+                    Some(departure) => Scan::set_departure_time(conn, &scan.id, departure)?,
+                    None => Scan::clear_departure_time(conn, &scan.id)?,
+                };
+            }
+            (ScanAction::StartTimer | ScanAction::StopTimer, None, Some(times)) => {
+                match times.from {
+                    Some(start) => Group::set_start_time(conn, &group.id, start)?,
+                    None => Group::clear_start_time(conn, &group.id)?,
+                };
+                match times.until {
+                    Some(finish) => Group::set_finish_time(conn, &group.id, finish)?,
+                    None => Group::clear_finish_time(conn, &group.id)?,
+                };
+            }
+            _ => {
+                return Err(ActionError::Conflict(
+                    "Deze actie kan niet ongedaan worden gemaakt".to_string(),
+                ))
+            }
+        }
+        AuditEntry::record(
+            conn,
+            &group,
+            action.post_id(),
+            &actor.audit_name(),
+            AUDIT_UNDO,
+            current,
+            before,
+        )?;
+        Ok(action)
+    })
+}
+
+/// Give the penalty of a post to a group arriving outside its opening hours, before the opening
+/// or after the last arrival time. The penalty is linked to the scan of the arrival.
+pub fn penalize_outside_hours(
+    conn: &mut SqliteConnection,
+    group: &Group,
+    post_id: &str,
+    scan_id: &str,
+    at: NaiveDateTime,
+) -> QueryResult<()> {
+    let Some(post) = Post::get_by_id(conn, post_id)? else {
+        return Ok(());
+    };
+    let (Some(penalty), Some(warning)) = (post.hours_penalty, post.hours_warning(at)) else {
+        return Ok(());
+    };
+    let mut adjustment = Adjustment::new(
+        group.id.clone(),
+        ADJUSTMENT_TIME,
+        penalty,
+        format!("{}: {warning}", post.name),
+        AUTOMATIC_AUTHOR.to_string(),
+    );
+    adjustment.scan_id = Some(scan_id.to_string());
+    Adjustment::insert(conn, &adjustment)?;
+    Ok(())
+}
+
+/// Check permissions, and that the group and post exist in the active event
+fn authorize(
+    conn: &mut SqliteConnection,
+    actor: &Actor,
+    group_id: &str,
+    action: &ScanAction,
+) -> Result<Group, ActionError> {
+    let group = Group::get_by_id(conn, group_id)?
+        .filter(|g| !g.reserved)
+        .ok_or(ActionError::GroupNotFound)?;
+    if group.event_id != Event::get_active(conn)?.id {
+        return Err(ActionError::EventClosed);
+    }
+    if !actor.may_perform(action) {
+        return Err(ActionError::Forbidden);
+    }
+    if let Some(post_id) = action.post_id() {
+        Post::get_by_id(conn, post_id)?
+            .filter(|p| p.event_id == group.event_id)
+            .ok_or(ActionError::PostNotFound)?;
+    }
+    Ok(group)
+}
+
+fn perform_unchecked(
+    conn: &mut SqliteConnection,
+    actor: &Actor,
+    group: Group,
+    action: &ScanAction,
+    at: NaiveDateTime,
+) -> Result<ActionOutcome, ActionError> {
+    let previous = match action.post_id() {
+        Some(post_id) => Scan::get_by_group_and_post(conn, &group.id, post_id)?,
+        None => None,
+    };
+    let scan = match action {
+        ScanAction::StartTimer => {
+            Group::set_start_time(conn, &group.id, at)?;
+            None
+        }
+        ScanAction::StopTimer => {
+            Group::set_finish_time(conn, &group.id, at)?;
+            None
+        }
+        // A group arrives at a post once, arriving again returns the existing scan
+        ScanAction::Arrive(post_id) => match previous.clone() {
+            Some(scan) => Some(scan),
+            None => {
+                let mut scan = NewScan::new(group.id.clone(), post_id.clone());
+                scan.arrival_time = at;
+                scan.out_of_order = check_order(conn, actor, &group, post_id, at)?;
+                let scan_id = scan.id.clone();
+                match Scan::insert(conn, scan) {
+                    Ok(_) => penalize_outside_hours(conn, &group, post_id, &scan_id, at)?,
+                    // Inserted by a concurrent request
+                    Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {}
+                    Err(err) => return Err(err.into()),
+                }
+                Scan::get_by_group_and_post(conn, &group.id, post_id)?
+            }
+        },
+        ScanAction::Leave(_) => match previous.clone() {
+            Some(scan) if scan.departure_time.is_none() => {
+                Scan::set_departure_time(conn, &scan.id, at)?;
+                Scan::get_by_id(conn, &scan.id)?
+            }
+            Some(scan) => Some(scan),
+            // Leaving needs an arrival to record the departure on
+            None => {
+                return Err(ActionError::Conflict(
+                    "Groep is nog niet aangekomen bij deze post".to_string(),
+                ))
+            }
+        },
+    };
+
+    let updated = Group::get_by_id(conn, &group.id)?.ok_or(ActionError::GroupNotFound)?;
+    let audit_action = match action {
+        ScanAction::StartTimer => AUDIT_START,
+        ScanAction::StopTimer => AUDIT_FINISH,
+        ScanAction::Arrive(_) => AUDIT_ARRIVE,
+        ScanAction::Leave(_) => AUDIT_LEAVE,
+    };
+    let (before, after) = if action.post_id().is_some() {
+        (
+            previous.as_ref().map(AuditTimes::of_scan),
+            scan.as_ref().map(AuditTimes::of_scan),
+        )
+    } else {
+        (
+            Some(AuditTimes::of_group(&group)),
+            Some(AuditTimes::of_group(&updated)),
+        )
+    };
+    AuditEntry::record(
+        conn,
+        &group,
+        action.post_id(),
+        &actor.audit_name(),
+        audit_action,
+        before,
+        after,
+    )?;
+
+    Ok(ActionOutcome {
+        group: updated,
+        scan,
+        replayed: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::db::{test_conn, test_group, test_post};
+    use crate::models::SCAN_ORDER_WARN;
+
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 5, 1)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap()
+    }
+
+    fn admin() -> Actor {
+        Actor {
+            is_admin: true,
+            post_id: None,
+            admin_session: Some("sessie".to_string()),
+        }
+    }
+
+    fn post_holder(post_id: &str) -> Actor {
+        Actor {
+            is_admin: false,
+            post_id: Some(post_id.to_string()),
+            admin_session: None,
+        }
+    }
+
+    fn audit_entries(conn: &mut SqliteConnection, group: &Group) -> Vec<AuditEntry> {
+        AuditEntry::get_by_event(conn, &group.event_id, Some(&group.id), None).unwrap()
+    }
+
+    /// Group `g1` on a route visiting three posts in another order than their post order
+    fn group_on_route(conn: &mut SqliteConnection) -> (Group, Vec<Post>) {
+        use crate::models::HikeRoute;
+        use crate::schema::groups;
+
+        let group = test_group(conn, "g1");
+        let posts = vec![
+            test_post(conn, "P3", 3),
+            test_post(conn, "P1", 1),
+            test_post(conn, "P2", 2),
+        ];
+        let route_id = HikeRoute::insert(conn, &group.event_id, "Lang").unwrap();
+        let positions = posts.iter().zip(1..).map(|(p, i)| (p.id.clone(), i));
+        HikeRoute::set_posts(conn, &route_id, positions.collect()).unwrap();
+        diesel::update(groups::table.find(&group.id))
+            .set(groups::route_id.eq(&route_id))
+            .execute(conn)
+            .unwrap();
+        (Group::get_by_id(conn, &group.id).unwrap().unwrap(), posts)
+    }
+
+    fn names(posts: Vec<Post>) -> Vec<String> {
+        posts.into_iter().map(|p| p.name).collect()
+    }
+
+    #[test]
+    fn skipped_posts_follow_the_route_order() {
+        let mut conn = test_conn();
+        let (group, posts) = group_on_route(&mut conn);
+        let last = &posts[2].id;
+        let skipped = skipped_posts(&mut conn, &group, last, now()).unwrap();
+        assert_eq!(names(skipped), ["P3", "P1"]);
+
+        let arrive = ScanAction::Arrive(posts[0].id.clone());
+        perform(&mut conn, &admin(), &group.id, &arrive, now()).unwrap();
+        let skipped = skipped_posts(&mut conn, &group, last, now()).unwrap();
+        assert_eq!(names(skipped), ["P1"]);
+        // Arriving at the first post later does not count for an earlier arrival
+        let earlier = now() - TimeDelta::minutes(1);
+        let skipped = skipped_posts(&mut conn, &group, last, earlier).unwrap();
+        assert_eq!(names(skipped), ["P3", "P1"]);
+        assert!(skipped_posts(&mut conn, &group, &posts[0].id, now())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn check_order_follows_the_event_setting() {
+        let mut conn = test_conn();
+        let (group, posts) = group_on_route(&mut conn);
+        let holder = post_holder(&posts[1].id);
+        let second = &posts[1].id;
+
+        let mut check = |scan_order: &str, actor: &Actor| {
+            Event::set_scan_order(&mut conn, &group.event_id, scan_order).unwrap();
+            check_order(&mut conn, actor, &group, second, now())
+        };
+        assert!(!check(SCAN_ORDER_OFF, &holder).unwrap());
+        assert!(check(SCAN_ORDER_WARN, &holder).unwrap());
+        assert!(check(SCAN_ORDER_BLOCK, &admin()).unwrap());
+        match check(SCAN_ORDER_BLOCK, &holder) {
+            Err(ActionError::OutOfOrder(message)) => {
+                assert_eq!(message, "Groep is nog niet langs 3. P3 geweest")
+            }
+            result => panic!("Expected the scan to be blocked, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn out_of_order_arrivals_are_marked() {
+        let mut conn = test_conn();
+        let (group, posts) = group_on_route(&mut conn);
+        Event::set_scan_order(&mut conn, &group.event_id, SCAN_ORDER_WARN).unwrap();
+
+        let arrive = ScanAction::Arrive(posts[1].id.clone());
+        let outcome = perform(&mut conn, &admin(), &group.id, &arrive, now()).unwrap();
+        assert!(outcome.scan.unwrap().out_of_order);
+        let arrive = ScanAction::Arrive(posts[0].id.clone());
+        let outcome = perform(&mut conn, &admin(), &group.id, &arrive, now()).unwrap();
+        assert!(!outcome.scan.unwrap().out_of_order);
+    }
+
+    #[test]
+    fn repeated_request_is_performed_once() {
+        let mut conn = test_conn();
+        let group = test_group(&mut conn, "g1");
+        let post = test_post(&mut conn, "P1", 1);
+        let arrive = ScanAction::Arrive(post.id.clone());
+        let actor = post_holder(&post.id);
+
+        let first = perform_once(&mut conn, &actor, Some("r1"), "g1", &arrive, now()).unwrap();
+        assert!(!first.replayed);
+        let later = now() + TimeDelta::minutes(1);
+        let again = perform_once(&mut conn, &actor, Some("r1"), "g1", &arrive, later).unwrap();
+        assert!(again.replayed);
+        assert_eq!(again.scan.unwrap().arrival_time, now());
+        assert_eq!(audit_entries(&mut conn, &group).len(), 1);
+    }
+
+    #[test]
+    fn request_id_reused_for_another_action_or_actor_is_a_conflict() {
+        let mut conn = test_conn();
+        test_group(&mut conn, "g1");
+        test_group(&mut conn, "g2");
+        let post = test_post(&mut conn, "P1", 1);
+        let other = test_post(&mut conn, "P2", 2);
+        let arrive = ScanAction::Arrive(post.id.clone());
+        let actor = post_holder(&post.id);
+        perform_once(&mut conn, &actor, Some("r1"), "g1", &arrive, now()).unwrap();
+
+        let leave = ScanAction::Leave(post.id.clone());
+        let other_arrive = ScanAction::Arrive(other.id.clone());
+        let reused = [
+            (&actor, "g1", &leave),
+            (&actor, "g2", &arrive),
+            (&admin(), "g1", &arrive),
+            (&post_holder(&other.id), "g1", &other_arrive),
+        ];
+        for (actor, group_id, action) in reused {
+            let result = perform_once(&mut conn, actor, Some("r1"), group_id, action, now());
+            assert!(
+                matches!(result, Err(ActionError::Conflict(_))),
+                "{result:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn admin_may_replay_a_request_from_another_session() {
+        let mut conn = test_conn();
+        test_group(&mut conn, "g1");
+        let start = ScanAction::StartTimer;
+        perform_once(&mut conn, &admin(), Some("r1"), "g1", &start, now()).unwrap();
+
+        let other_session = Actor {
+            admin_session: Some("andere".to_string()),
+            ..admin()
+        };
+        let again = perform_once(&mut conn, &other_session, Some("r1"), "g1", &start, now());
+        assert!(again.unwrap().replayed);
+    }
+
+    #[test]
+    fn requests_without_id_are_performed_every_time() {
+        let mut conn = test_conn();
+        let group = test_group(&mut conn, "g1");
+        let start = ScanAction::StartTimer;
+        perform_once(&mut conn, &admin(), None, "g1", &start, now()).unwrap();
+        let later = now() + TimeDelta::minutes(1);
+        let again = perform_once(&mut conn, &admin(), None, "g1", &start, later).unwrap();
+        assert!(!again.replayed);
+        assert_eq!(again.group.start_time, Some(later));
+        assert_eq!(audit_entries(&mut conn, &group).len(), 2);
+    }
+
+    fn undo_window() -> TimeDelta {
+        TimeDelta::minutes(5)
+    }
+
+    /// Arrival of `g1` at a post that opens an hour after [`now`], with a penalty for arriving early
+    fn early_arrival(conn: &mut SqliteConnection) -> (Group, Post, AuditEntry) {
+        let group = test_group(conn, "g1");
+        let post = test_post(conn, "P1", 1);
+        let opens_at = now() + TimeDelta::hours(1);
+        Post::set_hours(conn, &post.id, Some(opens_at), None, None, Some(300)).unwrap();
+        let arrive = ScanAction::Arrive(post.id.clone());
+        perform(conn, &admin(), &group.id, &arrive, now()).unwrap();
+        let entry = undoable(conn, &admin(), &group, now(), undo_window())
+            .unwrap()
+            .unwrap();
+        (group, post, entry)
+    }
+
+    #[test]
+    fn undoing_an_arrival_removes_the_scan_and_its_penalty() {
+        let mut conn = test_conn();
+        let (group, post, entry) = early_arrival(&mut conn);
+        assert_eq!(
+            Adjustment::get_by_group(&mut conn, &group.id)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let undone = undo(&mut conn, &admin(), "g1", &entry.id, now(), undo_window()).unwrap();
+        assert!(matches!(undone, ScanAction::Arrive(post_id) if post_id == post.id));
+        let scan = Scan::get_by_group_and_post(&mut conn, &group.id, &post.id).unwrap();
+        assert!(scan.is_none());
+        assert!(Adjustment::get_by_group(&mut conn, &group.id)
+            .unwrap()
+            .is_empty());
+        assert_eq!(audit_entries(&mut conn, &group)[0].action, AUDIT_UNDO);
+    }
+
+    #[test]
+    fn only_the_same_admin_session_can_undo() {
+        let mut conn = test_conn();
+        let (group, _, entry) = early_arrival(&mut conn);
+        let other_session = Actor {
+            admin_session: Some("andere".to_string()),
+            ..admin()
+        };
+        let undoable_by_other = undoable(&mut conn, &other_session, &group, now(), undo_window());
+        assert!(undoable_by_other.unwrap().is_none());
+
+        let result = undo(
+            &mut conn,
+            &other_session,
+            "g1",
+            &entry.id,
+            now(),
+            undo_window(),
+        );
+        assert!(
+            matches!(result, Err(ActionError::Conflict(_))),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn undo_is_refused_after_a_later_change() {
+        let mut conn = test_conn();
+        let (group, post, entry) = early_arrival(&mut conn);
+        let leave = ScanAction::Leave(post.id.clone());
+        let later = now() + TimeDelta::minutes(1);
+        perform(&mut conn, &post_holder(&post.id), &group.id, &leave, later).unwrap();
+
+        let result = undo(&mut conn, &admin(), "g1", &entry.id, later, undo_window());
+        assert!(
+            matches!(result, Err(ActionError::Conflict(_))),
+            "{result:?}"
+        );
+        let scan = Scan::get_by_group_and_post(&mut conn, &group.id, &post.id).unwrap();
+        assert_eq!(scan.unwrap().departure_time, Some(later));
+    }
+}
@@ -3,15 +3,33 @@ use rocket::request::{FromRequest, Outcome, Request};
 use rocket::time::Duration;
 use serde::{Deserialize, Serialize};
 
+use crate::db::DbConn;
+use crate::models::{admin_actor, new_short_id, ApiToken};
+
 const AUTH_COOKIE: &str = "auth_session";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum AuthSession {
-    Admin,
-    PostHolder { post_id: String },
+    /// Admins share a password, every login gets its own session ID to tell them apart. Empty
+    /// for logins from before admins had a session ID.
+    Admin {
+        session_id: String,
+    },
+    PostHolder {
+        post_id: String,
+    },
 }
 
-pub struct Admin;
+pub struct Admin {
+    pub session_id: String,
+}
+
+impl Admin {
+    /// How this admin is recorded in the audit log
+    pub fn audit_name(&self) -> String {
+        admin_actor(&self.session_id)
+    }
+}
 
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for Admin {
@@ -19,7 +37,7 @@ impl<'r> FromRequest<'r> for Admin {
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
         match get_auth_session(request.cookies()) {
-            Some(AuthSession::Admin) => Outcome::Success(Admin),
+            Some(AuthSession::Admin { session_id }) => Outcome::Success(Admin { session_id }),
             _ => Outcome::Forward(Status::Unauthorized),
         }
     }
@@ -28,6 +46,8 @@ impl<'r> FromRequest<'r> for Admin {
 pub struct AnyAuth {
     pub is_admin: bool,
     pub post_id: Option<String>,
+    /// Login session of an admin
+    pub admin_session: Option<String>,
 }
 
 #[rocket::async_trait]
@@ -36,19 +56,67 @@ impl<'r> FromRequest<'r> for AnyAuth {
 
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
         match get_auth_session(request.cookies()) {
-            Some(AuthSession::Admin) => Outcome::Success(AnyAuth {
+            Some(AuthSession::Admin { session_id }) => Outcome::Success(AnyAuth {
                 is_admin: true,
                 post_id: None,
+                admin_session: Some(session_id),
             }),
             Some(AuthSession::PostHolder { post_id }) => Outcome::Success(AnyAuth {
                 is_admin: false,
                 post_id: Some(post_id),
+                admin_session: None,
             }),
             None => Outcome::Forward(Status::Unauthorized),
         }
     }
 }
 
+/// Auth for API clients: a `Bearer` API token, or the session cookie of the web interface.
+pub struct ApiAuth {
+    pub is_admin: bool,
+    pub post_id: Option<String>,
+    /// Login session of an admin, or the ID of an admin API token
+    pub admin_session: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ApiAuth {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let token = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| token.trim().to_string());
+
+        let Some(token) = token else {
+            return AnyAuth::from_request(request).await.map(|auth| ApiAuth {
+                is_admin: auth.is_admin,
+                post_id: auth.post_id,
+                admin_session: auth.admin_session,
+            });
+        };
+
+        let conn = match request.guard::<DbConn>().await {
+            Outcome::Success(conn) => conn,
+            _ => return Outcome::Error((Status::ServiceUnavailable, ())),
+        };
+        match conn.run(move |c| ApiToken::find_valid(c, &token)).await {
+            Ok(Some(api_token)) => Outcome::Success(ApiAuth {
+                is_admin: api_token.post_id.is_none(),
+                admin_session: Some(api_token.id).filter(|_| api_token.post_id.is_none()),
+                post_id: api_token.post_id,
+            }),
+            Ok(None) => Outcome::Error((Status::Unauthorized, ())),
+            Err(err) => {
+                eprintln!("Failed to verify API token: {err}");
+                Outcome::Error((Status::InternalServerError, ()))
+            }
+        }
+    }
+}
+
 pub struct CurrentPath(pub String);
 
 #[rocket::async_trait]
@@ -59,14 +127,38 @@ impl<'r> FromRequest<'r> for CurrentPath {
     }
 }
 
+/// Session cookie as stored, also from before admins had a session ID
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredSession {
+    Current(AuthSession),
+    Old(OldSession),
+}
+
+#[derive(Deserialize)]
+enum OldSession {
+    Admin,
+}
+
+fn parse_auth_session(value: &str) -> Option<AuthSession> {
+    match serde_json::from_str(value).ok()? {
+        StoredSession::Current(session) => Some(session),
+        StoredSession::Old(OldSession::Admin) => Some(AuthSession::Admin {
+            session_id: String::new(),
+        }),
+    }
+}
+
 fn get_auth_session(cookies: &CookieJar<'_>) -> Option<AuthSession> {
     cookies
         .get_private(AUTH_COOKIE)
-        .and_then(|cookie| serde_json::from_str(cookie.value()).ok())
+        .and_then(|cookie| parse_auth_session(cookie.value()))
 }
 
 pub fn login_admin(cookies: &CookieJar<'_>) {
-    let session = AuthSession::Admin;
+    let session = AuthSession::Admin {
+        session_id: new_short_id(),
+    };
     let value = serde_json::to_string(&session).expect("Failed to serialize session");
     let mut cookie = Cookie::new(AUTH_COOKIE, value);
     cookie.set_max_age(Duration::hours(24));
@@ -91,7 +183,7 @@ pub fn check_admin_password(password: &str) -> bool {
 }
 
 pub fn is_admin(cookies: &CookieJar<'_>) -> bool {
-    matches!(get_auth_session(cookies), Some(AuthSession::Admin))
+    matches!(get_auth_session(cookies), Some(AuthSession::Admin { .. }))
 }
 
 pub fn get_current_auth(cookies: &CookieJar<'_>) -> Option<AuthSession> {
@@ -102,11 +194,16 @@ pub struct AuthContext {
     pub is_admin: bool,
     pub is_post_holder: bool,
     pub holder_post_id: Option<String>,
+    pub admin_session: Option<String>,
 }
 
 pub fn get_auth_context(cookies: &CookieJar<'_>) -> AuthContext {
     let current_auth = get_auth_session(cookies);
-    let is_admin = matches!(&current_auth, Some(AuthSession::Admin));
+    let admin_session = match &current_auth {
+        Some(AuthSession::Admin { session_id }) => Some(session_id.clone()),
+        _ => None,
+    };
+    let is_admin = admin_session.is_some();
     let holder_post_id = match &current_auth {
         Some(AuthSession::PostHolder { post_id }) => Some(post_id.clone()),
         _ => None,
@@ -117,5 +214,30 @@ pub fn get_auth_context(cookies: &CookieJar<'_>) -> AuthContext {
         is_admin,
         is_post_holder,
         holder_post_id,
+        admin_session,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn admin_cookies_from_before_sessions_are_accepted() {
+        let session = parse_auth_session(r#""Admin""#);
+        assert!(
+            matches!(session, Some(AuthSession::Admin { session_id }) if session_id.is_empty())
+        );
+        assert_eq!(admin_actor(""), crate::models::AUDIT_ADMIN);
+
+        let value = serde_json::to_string(&AuthSession::Admin {
+            session_id: "sessie".to_string(),
+        })
+        .unwrap();
+        let session = parse_auth_session(&value);
+        assert!(
+            matches!(session, Some(AuthSession::Admin { session_id }) if session_id == "sessie")
+        );
+        assert!(parse_auth_session(r#""PostHolder""#).is_none());
     }
 }
@@ -0,0 +1,99 @@
+use rocket::fairing::AdHoc;
+use rocket::{Build, Rocket};
+
+/// A point makes up for a minute of hiking unless configured otherwise
+const DEFAULT_SECONDS_PER_POINT: i64 = 60;
+
+/// Minutes a group may be later than expected before it is flagged as overdue
+const DEFAULT_OVERDUE_MARGIN: i64 = 15;
+
+/// Minutes during which the last scan action can be undone from the scan page
+const DEFAULT_UNDO_WINDOW: i64 = 2;
+
+/// Application settings, read from `Rocket.toml` or `ROCKET_*` environment variables.
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    /// Public base URL of this installation without trailing slash, e.g. `https://hike.example.com`
+    pub public_url: String,
+    /// Seconds one point is worth in the combined time and points ranking
+    pub seconds_per_point: i64,
+    /// Minutes past its expected arrival after which a group is overdue
+    pub overdue_margin: i64,
+    /// Minutes after a scan action during which it can be undone
+    pub undo_window: i64,
+}
+
+impl AppConfig {
+    /// Absolute URL of the scan page for a group, as printed in QR codes and written to NFC tags.
+    pub fn scan_url(&self, group_id: &str) -> String {
+        format!("{}/scan/{group_id}", self.public_url)
+    }
+
+    pub fn fairing() -> AdHoc {
+        AdHoc::try_on_ignite("App Config", |rocket| async {
+            match Self::from_rocket(&rocket) {
+                Ok(config) => Ok(rocket.manage(config)),
+                Err(err) => {
+                    error!("Invalid configuration: {err}");
+                    Err(rocket)
+                }
+            }
+        })
+    }
+
+    fn from_rocket(rocket: &Rocket<Build>) -> Result<Self, String> {
+        let figment = rocket.figment();
+
+        let public_url = match figment.extract_inner::<String>("public_url") {
+            Ok(url) => url.trim().trim_end_matches('/').to_string(),
+            // Fall back to the local server in debug builds, so development needs no setup
+            Err(_) if cfg!(debug_assertions) => {
+                let port = figment.extract_inner::<u16>("port").unwrap_or(8000);
+                format!("http://localhost:{port}")
+            }
+            Err(_) => return Err(
+                "`public_url` is not set, configure it in Rocket.toml or through ROCKET_PUBLIC_URL"
+                    .to_string(),
+            ),
+        };
+        if !public_url.starts_with("http://") && !public_url.starts_with("https://") {
+            return Err(format!(
+                "`public_url` must start with http:// or https://, got `{public_url}`"
+            ));
+        }
+
+        let seconds_per_point = figment
+            .extract_inner::<i64>("seconds_per_point")
+            .unwrap_or(DEFAULT_SECONDS_PER_POINT);
+        if seconds_per_point < 0 {
+            return Err(format!(
+                "`seconds_per_point` cannot be negative, got `{seconds_per_point}`"
+            ));
+        }
+
+        let overdue_margin = figment
+            .extract_inner::<i64>("overdue_margin")
+            .unwrap_or(DEFAULT_OVERDUE_MARGIN);
+        if overdue_margin < 0 {
+            return Err(format!(
+                "`overdue_margin` cannot be negative, got `{overdue_margin}`"
+            ));
+        }
+
+        let undo_window = figment
+            .extract_inner::<i64>("undo_window")
+            .unwrap_or(DEFAULT_UNDO_WINDOW);
+        if undo_window < 0 {
+            return Err(format!(
+                "`undo_window` cannot be negative, got `{undo_window}`"
+            ));
+        }
+
+        Ok(AppConfig {
+            public_url,
+            seconds_per_point,
+            overdue_margin,
+            undo_window,
+        })
+    }
+}
@@ -11,3 +11,45 @@ pub fn run_migrations(conn: &mut SqliteConnection) {
     conn.run_pending_migrations(MIGRATIONS)
         .expect("Failed to run database migrations");
 }
+
+/// An empty in-memory database with all migrations applied, for tests
+#[cfg(test)]
+pub fn test_conn() -> SqliteConnection {
+    use diesel::Connection;
+
+    let mut conn =
+        SqliteConnection::establish(":memory:").expect("Failed to open in-memory database");
+    run_migrations(&mut conn);
+    conn
+}
+
+/// A group of the active event, for tests
+#[cfg(test)]
+pub fn test_group(conn: &mut SqliteConnection, id: &str) -> crate::models::Group {
+    use crate::models::{Event, Group, NewGroup};
+
+    let event_id = Event::get_active(conn).expect("No active event").id;
+    let group = NewGroup::new_with_id(
+        event_id,
+        id.to_string(),
+        format!("Groep {id}"),
+        "Verkenners".to_string(),
+        String::new(),
+        String::new(),
+        1,
+    );
+    Group::insert(conn, group).expect("Failed to insert group");
+    Group::get_by_id(conn, id).unwrap().unwrap()
+}
+
+/// A post of the active event, for tests
+#[cfg(test)]
+pub fn test_post(conn: &mut SqliteConnection, name: &str, order: i32) -> crate::models::Post {
+    use crate::models::{Event, NewPost, Post};
+
+    let event_id = Event::get_active(conn).expect("No active event").id;
+    let post = NewPost::new(event_id, name.to_string(), order);
+    let id = post.id.clone();
+    Post::insert(conn, post).expect("Failed to insert post");
+    Post::get_by_id(conn, &id).unwrap().unwrap()
+}
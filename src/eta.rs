@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use chrono::{NaiveDateTime, TimeDelta};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::Serialize;
+
+use crate::models::{Group, Post, RouteMap, Scan, GROUP_WITHDRAWN};
+use crate::stats::{calculate_group_stats, compare_legs, now_naive, secs, GroupStats};
+
+/// Expected arrival of a walking group at the next post of its route
+#[derive(Serialize, Clone)]
+pub struct Eta {
+    pub group: Group,
+    /// Empty when the group is on its way to the finish
+    pub next_post: Option<Post>,
+    pub expected_at: NaiveDateTime,
+    /// Whether the group is later than expected by more than the margin
+    pub overdue: bool,
+    /// How much later than expected the group is, zero while on time
+    #[serde(with = "secs")]
+    pub late_by: TimeDelta,
+}
+
+/// Post IDs at both ends of a leg, empty for the start and the finish
+type LegKey = (Option<String>, Option<String>);
+
+/// Median walking time per leg over all groups of an event
+struct LegHistory {
+    medians: HashMap<LegKey, TimeDelta>,
+}
+
+impl LegHistory {
+    fn new(groups: &[(Group, GroupStats)]) -> Self {
+        let legs = groups
+            .iter()
+            .map(|(group, stats)| (group.clone(), stats.legs.clone()))
+            .collect();
+        let medians = compare_legs(legs)
+            .into_iter()
+            .map(|leg| ((leg.from.map(|p| p.id), leg.to.map(|p| p.id)), leg.median))
+            .collect();
+        LegHistory { medians }
+    }
+
+    /// Expected arrival at the first post the group did not visit yet, or at the finish after
+    /// the last post. Groups that did not start, are at a post, finished or withdrew have none,
+    /// and neither do groups on a leg no other group walked yet.
+    fn predict(
+        &self,
+        group: &Group,
+        stats: &GroupStats,
+        now: NaiveDateTime,
+        margin: TimeDelta,
+    ) -> Option<Eta> {
+        let start_time = group.start_time?;
+        // Groups out of the race for other reasons may still be out there
+        if group.finish_time.is_some() || group.status == GROUP_WITHDRAWN {
+            return None;
+        }
+
+        let last_visit = stats
+            .post_scans
+            .iter()
+            .filter_map(|ps| ps.scan.as_ref().map(|scan| (&ps.post, scan)))
+            .max_by_key(|(_, scan)| scan.arrival_time);
+        let (from, left_at) = match last_visit {
+            Some((post, scan)) => (Some(post.id.clone()), scan.departure_time?),
+            None => (None, start_time),
+        };
+        let next_post = stats
+            .post_scans
+            .iter()
+            .find(|ps| ps.scan.is_none())
+            .map(|ps| ps.post.clone());
+
+        let leg = (from, next_post.as_ref().map(|p| p.id.clone()));
+        let expected_at = left_at + *self.medians.get(&leg)?;
+        let late_by = (now - expected_at).max(TimeDelta::zero());
+        Some(Eta {
+            group: group.clone(),
+            next_post,
+            expected_at,
+            overdue: late_by > margin,
+            late_by,
+        })
+    }
+}
+
+/// Expected arrivals of all walking groups of an event, by the median time other groups took on
+/// the same leg. Ordered by how late groups are, most overdue first.
+pub fn load_etas(
+    conn: &mut SqliteConnection,
+    event_id: &str,
+    margin_minutes: i64,
+) -> QueryResult<Vec<Eta>> {
+    let route_map = RouteMap::load(conn, event_id)?;
+    let groups = Group::get_all(conn, event_id)?
+        .into_iter()
+        .map(|group| {
+            let scans = Scan::get_by_group(conn, &group.id)?;
+            let posts = route_map.posts_for(group.route_id.as_deref());
+            let stats = calculate_group_stats(&group, &scans, posts, &[]);
+            Ok((group, stats))
+        })
+        .collect::<QueryResult<Vec<_>>>()?;
+
+    let history = LegHistory::new(&groups);
+    let now = now_naive();
+    let margin = TimeDelta::minutes(margin_minutes);
+    let mut etas: Vec<Eta> = groups
+        .iter()
+        .filter_map(|(group, stats)| history.predict(group, stats, now, margin))
+        .collect();
+    etas.sort_by_key(|eta| (std::cmp::Reverse(eta.late_by), eta.expected_at));
+    Ok(etas)
+}
@@ -0,0 +1,51 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+
+use crate::db::DbConn;
+use crate::models::Event;
+
+/// The active event, which pages and API calls work on unless an archived event is asked for.
+pub struct ActiveEvent(pub Event);
+
+impl ActiveEvent {
+    pub fn id(&self) -> String {
+        self.0.id.clone()
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ActiveEvent {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let conn = match request.guard::<DbConn>().await {
+            Outcome::Success(conn) => conn,
+            _ => return Outcome::Error((Status::ServiceUnavailable, ())),
+        };
+        match conn.run(Event::get_active).await {
+            Ok(event) => Outcome::Success(ActiveEvent(event)),
+            Err(err) => {
+                eprintln!("Failed to load active event: {err}");
+                Outcome::Error((Status::InternalServerError, ()))
+            }
+        }
+    }
+}
+
+/// Event to show on overview pages: the one asked for by `?event=`, or the active event.
+///
+/// Returns `None` if the asked for event does not exist.
+pub async fn viewed_event(
+    conn: &DbConn,
+    active: ActiveEvent,
+    event_id: Option<String>,
+) -> Option<Event> {
+    match event_id {
+        Some(event_id) if event_id != active.0.id => conn
+            .run(move |c| Event::get_by_id(c, &event_id))
+            .await
+            .ok()
+            .flatten(),
+        _ => Some(active.0),
+    }
+}
@@ -0,0 +1,298 @@
+use chrono::{NaiveDateTime, TimeDelta};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+
+use crate::models::{group_status_label, Adjustment, Group, Post, RouteMap, Scan, Score};
+use crate::scoring::ScoreSchemes;
+use crate::stats::{calculate_group_stats, format_duration, GroupStats};
+
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Sheet name for groups without a route
+const NO_ROUTE: &str = "Zonder route";
+
+/// Longest sheet name Excel accepts
+const MAX_SHEET_NAME: usize = 31;
+
+/// Sheet name Excel keeps for itself, in any case
+const RESERVED_SHEET_NAME: &str = "history";
+
+pub struct GroupResult {
+    pub group: Group,
+    pub stats: GroupStats,
+    pub points: i32,
+}
+
+enum Cell {
+    Text(String),
+    Number(i32),
+    DateTime(Option<NaiveDateTime>),
+    Duration(Option<TimeDelta>),
+}
+
+/// Results of all groups of an event ordered by group number, with the posts of their route.
+pub fn load_results(
+    conn: &mut SqliteConnection,
+    event_id: &str,
+) -> QueryResult<(RouteMap, Vec<GroupResult>)> {
+    let route_map = RouteMap::load(conn, event_id)?;
+    let schemes = ScoreSchemes::load(conn, event_id)?;
+    let mut groups = Group::get_all(conn, event_id)?;
+    groups.sort_by_key(|g| g.group_number);
+
+    let results = groups
+        .into_iter()
+        .map(|group| {
+            let scans = Scan::get_by_group(conn, &group.id)?;
+            let adjustments = Adjustment::get_by_group(conn, &group.id)?;
+            let stats = calculate_group_stats(
+                &group,
+                &scans,
+                route_map.posts_for(group.route_id.as_deref()),
+                &adjustments,
+            );
+            let points = schemes.points(&Score::get_by_group(conn, &group.id)?);
+            Ok(GroupResult {
+                group,
+                stats,
+                points,
+            })
+        })
+        .collect::<QueryResult<_>>()?;
+
+    Ok((route_map, results))
+}
+
+fn headers(posts: &[Post]) -> Vec<String> {
+    let mut headers: Vec<String> = [
+        "Nr",
+        "Groep",
+        "Speltak",
+        "Route",
+        "Categorie",
+        "Status",
+        "Reden status",
+        "Start",
+        "Finish",
+        "Totale tijd",
+        "Looptijd",
+        "Wachttijd",
+        "Punten",
+        "Correctie tijd",
+        "Correctie punten",
+        "Gecorrigeerde tijd",
+        "Gecorrigeerde punten",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect();
+
+    for post in posts {
+        let label = format!("{}. {}", post.post_order, post.name);
+        headers.push(format!("{label} aankomst"));
+        headers.push(format!("{label} vertrek"));
+        headers.push(format!("{label} wachttijd"));
+    }
+    headers
+}
+
+/// Cells for a group, with post columns for `posts`. Posts not on the route of the group are
+/// left empty.
+fn cells(result: &GroupResult, posts: &[Post]) -> Vec<Cell> {
+    let group = &result.group;
+    let stats = &result.stats;
+    let mut cells = vec![
+        Cell::Number(group.group_number),
+        Cell::Text(group.name.clone()),
+        Cell::Text(group.scout_group.clone()),
+        Cell::Text(group.route.clone()),
+        Cell::Text(group.category.clone()),
+        Cell::Text(group_status_label(group.status()).to_string()),
+        Cell::Text(group.status_reason.clone()),
+        Cell::DateTime(group.start_time),
+        Cell::DateTime(group.finish_time),
+        Cell::Duration(stats.total_time),
+        Cell::Duration(stats.walking_time),
+        Cell::Duration(Some(stats.idle_time)),
+        Cell::Number(result.points),
+        Cell::Duration(Some(stats.time_adjustment)),
+        Cell::Number(stats.points_adjustment),
+        Cell::Duration(stats.adjusted_total_time),
+        Cell::Number(result.points + stats.points_adjustment),
+    ];
+
+    for post in posts {
+        let post_scan = stats.post_scans.iter().find(|ps| ps.post.id == post.id);
+        let scan = post_scan.and_then(|ps| ps.scan.as_ref());
+        cells.push(Cell::DateTime(scan.map(|s| s.arrival_time)));
+        cells.push(Cell::DateTime(scan.and_then(|s| s.departure_time)));
+        cells.push(Cell::Duration(post_scan.and_then(|ps| ps.idle_time)));
+    }
+    cells
+}
+
+pub fn to_csv(posts: &[Post], results: &[GroupResult]) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(headers(posts))?;
+
+    for result in results {
+        let record: Vec<String> = cells(result, posts)
+            .into_iter()
+            .map(|cell| match cell {
+                Cell::Text(text) => text,
+                Cell::Number(number) => number.to_string(),
+                Cell::DateTime(dt) => dt
+                    .map(|dt| dt.format(DATETIME_FORMAT).to_string())
+                    .unwrap_or_default(),
+                Cell::Duration(delta) => delta.map(format_duration).unwrap_or_default(),
+            })
+            .collect();
+        writer.write_record(record)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|err| csv::Error::from(err.into_error()))
+}
+
+/// Excel sheet names are limited to 31 characters and may not contain some symbols
+fn sheet_name(route: &str) -> String {
+    let name: String = route
+        .chars()
+        .filter(|c| !matches!(c, '[' | ']' | ':' | '*' | '?' | '/' | '\\'))
+        .take(MAX_SHEET_NAME)
+        .collect();
+    let name = name.trim().trim_matches('\'').to_string();
+    if name.is_empty() {
+        NO_ROUTE.to_string()
+    } else {
+        name
+    }
+}
+
+/// A sheet name for every route name, in order. Names that are taken, regardless of case, or
+/// reserved by Excel get a number.
+fn sheet_names<'a>(routes: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for route in routes {
+        let base = sheet_name(route);
+        let mut name = base.clone();
+        let mut number = 1;
+        while name.to_lowercase() == RESERVED_SHEET_NAME
+            || names
+                .iter()
+                .any(|n| n.to_lowercase() == name.to_lowercase())
+        {
+            number += 1;
+            let suffix = format!(" ({number})");
+            let kept: String = base.chars().take(MAX_SHEET_NAME - suffix.len()).collect();
+            name = format!("{}{suffix}", kept.trim_end());
+        }
+        names.push(name);
+    }
+    names
+}
+
+/// Workbook with a sheet per route, with the posts of that route
+pub fn to_xlsx(route_map: &RouteMap, results: &[GroupResult]) -> Result<Vec<u8>, XlsxError> {
+    // Routes by ID with their name, groups without a route share a sheet
+    let mut routes: Vec<(Option<String>, String)> = Vec::new();
+    for result in results {
+        let group = &result.group;
+        if !routes.iter().any(|(id, _)| *id == group.route_id) {
+            let name = group.route_id.as_ref().map(|_| group.route.clone());
+            routes.push((group.route_id.clone(), name.unwrap_or_default()));
+        }
+    }
+    routes.sort_by_key(|(_, name)| sheet_name(name).to_lowercase());
+    if routes.is_empty() {
+        routes.push((None, String::new()));
+    }
+    let names = sheet_names(routes.iter().map(|(_, name)| name.as_str()));
+
+    let bold = Format::new().set_bold();
+    let datetime_format = Format::new().set_num_format("yyyy-mm-dd hh:mm:ss");
+    let duration_format = Format::new().set_num_format("[h]:mm:ss");
+
+    let mut workbook = Workbook::new();
+    for ((route_id, _), name) in routes.into_iter().zip(names) {
+        let posts = route_map.posts_for(route_id.as_deref());
+        let headers = headers(&posts);
+        let sheet = workbook.add_worksheet();
+        sheet.set_name(&name)?;
+        for (col, header) in headers.iter().enumerate() {
+            sheet.write_string_with_format(0, col as u16, header, &bold)?;
+        }
+        sheet.set_freeze_panes(1, 2)?;
+
+        let route_results = results.iter().filter(|r| r.group.route_id == route_id);
+        for (row, result) in route_results.enumerate() {
+            let row = row as u32 + 1;
+            for (col, cell) in cells(result, &posts).into_iter().enumerate() {
+                let col = col as u16;
+                match cell {
+                    Cell::Text(text) => {
+                        sheet.write_string(row, col, text)?;
+                    }
+                    Cell::Number(number) => {
+                        sheet.write_number(row, col, number)?;
+                    }
+                    Cell::DateTime(Some(dt)) => {
+                        sheet.write_datetime_with_format(row, col, dt, &datetime_format)?;
+                    }
+                    // Excel stores durations as fractions of a day
+                    Cell::Duration(Some(delta)) => {
+                        let days = delta.num_seconds() as f64 / 86400.0;
+                        sheet.write_number_with_format(row, col, days, &duration_format)?;
+                    }
+                    Cell::DateTime(None) | Cell::Duration(None) => {}
+                }
+            }
+        }
+        sheet.autofit();
+    }
+
+    workbook.save_to_buffer()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sheet_names_drop_symbols_and_fit_excel() {
+        assert_eq!(sheet_name("Lang: 25/km?"), "Lang 25km");
+        assert_eq!(sheet_name("'Kort'"), "Kort");
+        assert_eq!(sheet_name(" "), NO_ROUTE);
+        assert_eq!(sheet_name(&"a".repeat(40)).len(), MAX_SHEET_NAME);
+    }
+
+    #[test]
+    fn sheet_names_are_unique_regardless_of_case() {
+        let long = "Route langs de rivier en het bos, noord";
+        let other = "Route langs de rivier en het bos, zuid";
+        assert_eq!(
+            sheet_names(["Kort", "KORT", long, other]),
+            [
+                "Kort",
+                "KORT (2)",
+                "Route langs de rivier en het bo",
+                "Route langs de rivier en he (2)",
+            ]
+        );
+    }
+
+    #[test]
+    fn sheet_names_avoid_the_no_route_sheet_and_reserved_names() {
+        assert_eq!(
+            sheet_names(["", "Zonder route", "History", "history"]),
+            [
+                "Zonder route",
+                "Zonder route (2)",
+                "History (2)",
+                "history (3)"
+            ]
+        );
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::HashSet;
+
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::Serialize;
+
+use crate::models::{new_short_id, Group, HikeRoute, NewGroup};
+
+/// Accepted column headers per field, matching the `NewGroup` field names and the labels of the
+/// group form.
+const NAME_HEADERS: &[&str] = &["name", "naam", "groepsnaam"];
+const SCOUT_GROUP_HEADERS: &[&str] = &["scout_group", "speltak"];
+const MEMBERS_HEADERS: &[&str] = &["members", "leden"];
+const PHONE_HEADERS: &[&str] = &["phone_number", "phone", "telefoonnummer", "telefoon"];
+const NUMBER_HEADERS: &[&str] = &["group_number", "groepsnummer", "nummer"];
+const ROUTE_HEADERS: &[&str] = &["route"];
+const CATEGORY_HEADERS: &[&str] = &["category", "categorie"];
+const ID_HEADERS: &[&str] = &["id", "scan_id"];
+
+/// Longest ID accepted from a file, IDs end up in scan URLs and QR codes
+const MAX_ID_LENGTH: usize = 32;
+
+#[derive(Debug, Serialize)]
+pub struct ImportRow {
+    /// Line number in the uploaded file, for error reporting
+    pub line: usize,
+    pub id: Option<String>,
+    /// Whether the row fills in a reserved ID instead of creating a new group
+    pub claims_reserved: bool,
+    pub name: String,
+    pub scout_group: String,
+    pub members: String,
+    pub phone_number: String,
+    pub group_number: i32,
+    pub route: String,
+    pub category: String,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportPreview {
+    pub rows: Vec<ImportRow>,
+    /// Errors that are not tied to a single row
+    pub errors: Vec<String>,
+}
+
+impl ImportPreview {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+            && !self.rows.is_empty()
+            && self.rows.iter().all(|r| r.errors.is_empty())
+    }
+}
+
+/// Guess the delimiter from the header line, spreadsheets in Dutch locales export semicolons.
+fn detect_delimiter(data: &str) -> u8 {
+    let header = data.lines().next().unwrap_or_default();
+    [b';', b'\t', b',']
+        .into_iter()
+        .max_by_key(|d| header.matches(*d as char).count())
+        .unwrap_or(b',')
+}
+
+/// Whether an ID from a file can be used in a scan URL as is
+fn is_valid_id(id: &str) -> bool {
+    id.len() <= MAX_ID_LENGTH
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn find_column(headers: &csv::StringRecord, names: &[&str]) -> Option<usize> {
+    headers
+        .iter()
+        .position(|h| names.contains(&h.trim().to_lowercase().as_str()))
+}
+
+/// Parse and validate a CSV file with one group per row.
+pub fn parse_groups_csv(data: &str, scout_groups: &[String]) -> ImportPreview {
+    let mut preview = ImportPreview::default();
+    let data = data.trim_start_matches('\u{feff}');
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(detect_delimiter(data))
+        .flexible(true)
+        .from_reader(data.as_bytes());
+
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(err) => {
+            preview.errors.push(format!("Kan CSV niet lezen: {err}"));
+            return preview;
+        }
+    };
+
+    let name_col = find_column(&headers, NAME_HEADERS);
+    let scout_group_col = find_column(&headers, SCOUT_GROUP_HEADERS);
+    let number_col = find_column(&headers, NUMBER_HEADERS);
+    let members_col = find_column(&headers, MEMBERS_HEADERS);
+    let phone_col = find_column(&headers, PHONE_HEADERS);
+    let route_col = find_column(&headers, ROUTE_HEADERS);
+    let category_col = find_column(&headers, CATEGORY_HEADERS);
+    let id_col = find_column(&headers, ID_HEADERS);
+
+    for (col, label) in [
+        (name_col, "name"),
+        (scout_group_col, "scout_group"),
+        (number_col, "group_number"),
+    ] {
+        if col.is_none() {
+            preview.errors.push(format!("Kolom '{label}' ontbreekt"));
+        }
+    }
+    if !preview.errors.is_empty() {
+        return preview;
+    }
+
+    let mut seen_ids = HashSet::new();
+    for (index, record) in reader.records().enumerate() {
+        // Header is line 1
+        let line = index + 2;
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                preview.errors.push(format!("Regel {line}: {err}"));
+                continue;
+            }
+        };
+        if record.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+
+        let field = |col: Option<usize>| {
+            col.and_then(|c| record.get(c))
+                .unwrap_or_default()
+                .trim()
+                .to_string()
+        };
+        let mut errors = Vec::new();
+
+        let name = field(name_col);
+        if name.is_empty() {
+            errors.push("Groepsnaam is verplicht".to_string());
+        }
+
+        let scout_group = field(scout_group_col);
+        if scout_group.is_empty() {
+            errors.push("Speltak is verplicht".to_string());
+        } else if !scout_groups.is_empty() && !scout_groups.contains(&scout_group) {
+            errors.push(format!("Onbekende speltak '{scout_group}'"));
+        }
+
+        let number = field(number_col);
+        let group_number = match number.parse::<i32>() {
+            Ok(n) if n >= 0 => n,
+            _ => {
+                errors.push(format!("Ongeldig groepsnummer '{number}'"));
+                0
+            }
+        };
+
+        let id = Some(field(id_col)).filter(|id| !id.is_empty());
+        if let Some(id) = &id {
+            if !is_valid_id(id) {
+                errors.push(format!(
+                    "Ongeldig ID '{id}', gebruik alleen letters, cijfers, - en _ (max {MAX_ID_LENGTH} tekens)"
+                ));
+            }
+            if !seen_ids.insert(id.clone()) {
+                errors.push(format!("ID '{id}' komt meerdere keren voor"));
+            }
+        }
+
+        preview.rows.push(ImportRow {
+            line,
+            id,
+            claims_reserved: false,
+            name,
+            scout_group,
+            members: field(members_col),
+            phone_number: field(phone_col),
+            group_number,
+            route: field(route_col),
+            category: field(category_col),
+            errors,
+        });
+    }
+
+    if preview.rows.is_empty() && preview.errors.is_empty() {
+        preview
+            .errors
+            .push("Het bestand bevat geen groepen".to_string());
+    }
+
+    preview
+}
+
+/// Check IDs against the database, including groups in the trash, and hand out reserved IDs to
+/// rows without one when `bind_reserved` is set.
+pub fn resolve_ids(
+    conn: &mut SqliteConnection,
+    event_id: &str,
+    preview: &mut ImportPreview,
+    bind_reserved: bool,
+) -> QueryResult<()> {
+    let reserved: Vec<String> = Group::get_reserved(conn, event_id)?
+        .into_iter()
+        .map(|g| g.id)
+        .collect();
+
+    for row in preview.rows.iter_mut() {
+        let Some(id) = &row.id else {
+            continue;
+        };
+        match Group::get_by_id(conn, id)? {
+            Some(group) if group.reserved => row.claims_reserved = true,
+            Some(_) => row.errors.push(format!("ID '{id}' is al in gebruik")),
+            // A group in the trash keeps its ID until it is purged
+            None if Group::get_deleted_by_id(conn, id)?.is_some() => row
+                .errors
+                .push(format!("ID '{id}' hoort bij een verwijderde groep")),
+            None => {}
+        }
+    }
+
+    if bind_reserved {
+        let available: Vec<String> = reserved
+            .into_iter()
+            .filter(|id| !preview.rows.iter().any(|r| r.id.as_ref() == Some(id)))
+            .collect();
+        let mut available = available.into_iter();
+        let mut missing = 0;
+        for row in preview.rows.iter_mut().filter(|r| r.id.is_none()) {
+            match available.next() {
+                Some(id) => {
+                    row.id = Some(id);
+                    row.claims_reserved = true;
+                }
+                None => missing += 1,
+            }
+        }
+        if missing > 0 {
+            preview.errors.push(format!(
+                "Niet genoeg gereserveerde ID's, er ontbreken er {missing}"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Insert all rows of a valid preview into an event in a single transaction.
+pub fn apply(
+    conn: &mut SqliteConnection,
+    event_id: &str,
+    preview: ImportPreview,
+) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        let mut count = 0;
+        for row in preview.rows {
+            let claims_reserved = row.claims_reserved;
+            let mut group = NewGroup::new_with_id(
+                event_id.to_string(),
+                row.id.unwrap_or_else(new_short_id),
+                row.name,
+                row.scout_group,
+                row.members,
+                row.phone_number,
+                row.group_number,
+            );
+            group.set_route(HikeRoute::find_or_create(conn, event_id, &row.route)?.as_ref());
+            group.category = row.category;
+            if claims_reserved {
+                // The reserved ID was claimed by a scan in the meantime, roll everything back
+                if Group::claim(conn, group)? == 0 {
+                    return Err(diesel::result::Error::NotFound);
+                }
+            } else {
+                Group::insert(conn, group)?;
+            }
+            count += 1;
+        }
+        Ok(count)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_conn, test_group};
+
+    fn scout_groups() -> Vec<String> {
+        vec!["Verkenners".to_string(), "Explorers".to_string()]
+    }
+
+    #[test]
+    fn detects_semicolons_tabs_and_commas() {
+        assert_eq!(detect_delimiter("naam;speltak;nummer\n"), b';');
+        assert_eq!(detect_delimiter("naam\tspeltak\tnummer\n"), b'\t');
+        assert_eq!(detect_delimiter("naam,speltak,nummer\n"), b',');
+        assert_eq!(detect_delimiter(""), b',');
+    }
+
+    #[test]
+    fn parses_rows_with_dutch_headers() {
+        let csv =
+            "\u{feff}Naam;Speltak;Nummer;Route;ID\nDe Wolven;Verkenners;3;Lang;abc-123\n;;;;\n";
+        let preview = parse_groups_csv(csv, &scout_groups());
+        assert!(preview.is_valid(), "{preview:?}");
+        assert_eq!(preview.rows.len(), 1);
+        let row = &preview.rows[0];
+        assert_eq!(row.line, 2);
+        assert_eq!(row.name, "De Wolven");
+        assert_eq!(row.group_number, 3);
+        assert_eq!(row.route, "Lang");
+        assert_eq!(row.id.as_deref(), Some("abc-123"));
+    }
+
+    #[test]
+    fn missing_columns_are_file_errors() {
+        let preview = parse_groups_csv("naam,route\nDe Wolven,Lang\n", &scout_groups());
+        assert!(preview.rows.is_empty());
+        assert_eq!(
+            preview.errors,
+            [
+                "Kolom 'scout_group' ontbreekt",
+                "Kolom 'group_number' ontbreekt"
+            ]
+        );
+        assert!(!preview.is_valid());
+    }
+
+    #[test]
+    fn row_errors_are_reported_per_line() {
+        let csv = "naam,speltak,nummer,id\n\
+                   ,Verkenners,1,a\n\
+                   De Vossen,Welpen,-1,a\n\
+                   De Uilen,Explorers,2,../admin\n";
+        let preview = parse_groups_csv(csv, &scout_groups());
+        assert!(!preview.is_valid());
+        let errors: Vec<(usize, usize)> = preview
+            .rows
+            .iter()
+            .map(|r| (r.line, r.errors.len()))
+            .collect();
+        // Missing name; unknown scout group, negative number and reused ID; unsafe ID
+        assert_eq!(errors, [(2, 1), (3, 3), (4, 1)]);
+    }
+
+    #[test]
+    fn file_without_groups_is_invalid() {
+        let preview = parse_groups_csv("naam,speltak,nummer\n", &scout_groups());
+        assert_eq!(preview.errors, ["Het bestand bevat geen groepen"]);
+    }
+
+    #[test]
+    fn ids_of_groups_in_the_trash_are_refused() {
+        let mut conn = test_conn();
+        let event_id = test_group(&mut conn, "weg").event_id;
+        Group::delete(&mut conn, "weg").unwrap();
+
+        let csv = "naam,speltak,nummer,id\nDe Wolven,Verkenners,1,weg\n";
+        let mut preview = parse_groups_csv(csv, &scout_groups());
+        resolve_ids(&mut conn, &event_id, &mut preview, false).unwrap();
+        assert_eq!(
+            preview.rows[0].errors,
+            ["ID 'weg' hoort bij een verwijderde groep"]
+        );
+    }
+}
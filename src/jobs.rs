@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use diesel::prelude::*;
+use rocket::fairing::AdHoc;
+use rocket::tokio::time::interval;
+
+use crate::config::AppConfig;
+use crate::db::DbConn;
+use crate::live::LiveUpdates;
+use crate::models::Event;
+use crate::routes::leaderboard::frozen_standings;
+
+/// Seconds between runs of the scheduled jobs
+const JOB_INTERVAL: u64 = 30;
+
+/// Runs work that depends on the clock rather than on a request, for the active event: taking
+/// groups over the max course time out of the race, and keeping the public standings once the
+/// leaderboard freezes.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_liftoff("Scheduled Jobs", |rocket| {
+        Box::pin(async move {
+            let (Some(pool), Some(config), Some(live)) = (
+                DbConn::pool(rocket).cloned(),
+                rocket.state::<AppConfig>().cloned(),
+                rocket.state::<LiveUpdates>().cloned(),
+            ) else {
+                eprintln!("Failed to start scheduled jobs: no database or configuration");
+                return;
+            };
+            rocket::tokio::spawn(async move {
+                let mut ticks = interval(Duration::from_secs(JOB_INTERVAL));
+                loop {
+                    ticks.tick().await;
+                    let Some(conn) = pool.get().await else {
+                        continue;
+                    };
+                    let seconds_per_point = config.seconds_per_point;
+                    let result = conn
+                        .run(move |c| {
+                            // Nothing to do without an active event
+                            let Some(event) = Event::get_active(c).optional()? else {
+                                return Ok(Vec::new());
+                            };
+                            let marked = event.apply_max_course_time(c)?;
+                            frozen_standings(c, &event, seconds_per_point)?;
+                            Ok::<_, diesel::result::Error>(marked)
+                        })
+                        .await;
+                    match result {
+                        Ok(marked) => marked.iter().for_each(|group_id| live.group(group_id)),
+                        Err(err) => eprintln!("Failed to run scheduled jobs: {err}"),
+                    }
+                }
+            });
+        })
+    })
+}
@@ -0,0 +1,68 @@
+use rocket::tokio::sync::broadcast;
+use serde::Serialize;
+
+use crate::actions::ScanAction;
+
+/// Number of events kept for slow subscribers, they resync when they fall further behind
+const CHANNEL_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LiveEventKind {
+    /// A scan was recorded, changed or removed
+    Scan,
+    /// The timer or details of a group changed
+    Group,
+}
+
+/// Change pushed to open dashboard, post overview and ranking pages.
+#[derive(Debug, Clone, Serialize)]
+pub struct LiveEvent {
+    pub kind: LiveEventKind,
+    pub group_id: String,
+    pub post_id: Option<String>,
+}
+
+/// Broadcast channel for live updates, kept in managed state.
+#[derive(Clone)]
+pub struct LiveUpdates {
+    sender: broadcast::Sender<LiveEvent>,
+}
+
+impl Default for LiveUpdates {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        LiveUpdates { sender }
+    }
+}
+
+impl LiveUpdates {
+    pub fn subscribe(&self) -> broadcast::Receiver<LiveEvent> {
+        self.sender.subscribe()
+    }
+
+    pub fn scan(&self, group_id: &str, post_id: Option<&str>) {
+        self.send(LiveEventKind::Scan, group_id, post_id);
+    }
+
+    pub fn group(&self, group_id: &str) {
+        self.send(LiveEventKind::Group, group_id, None);
+    }
+
+    /// Announce a performed scan action
+    pub fn action(&self, group_id: &str, action: &ScanAction) {
+        match action.post_id() {
+            Some(post_id) => self.scan(group_id, Some(post_id)),
+            None => self.group(group_id),
+        }
+    }
+
+    fn send(&self, kind: LiveEventKind, group_id: &str, post_id: Option<&str>) {
+        // Fails only when nobody is listening
+        let _ = self.sender.send(LiveEvent {
+            kind,
+            group_id: group_id.to_string(),
+            post_id: post_id.map(String::from),
+        });
+    }
+}
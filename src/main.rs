@@ -1,13 +1,25 @@
 #[macro_use]
 extern crate rocket;
 
+mod actions;
 mod auth;
 mod cache;
+mod config;
 mod db;
+mod eta;
+mod event;
+mod export;
+mod import;
+mod jobs;
+mod live;
 mod models;
+mod qr_sheet;
+mod ranking_rules;
 mod routes;
 mod schema;
+mod scoring;
 mod stats;
+mod sync;
 
 use db::DbConn;
 use rocket::fs::FileServer;
@@ -22,6 +34,8 @@ fn index() -> rocket::response::Redirect {
 fn rocket() -> _ {
     dotenvy::dotenv().ok();
     rocket::build()
+        .attach(config::AppConfig::fairing())
+        .manage(live::LiveUpdates::default())
         .attach(DbConn::fairing())
         .attach(rocket::fairing::AdHoc::on_ignite(
             "Run Migrations",
@@ -37,16 +51,29 @@ fn rocket() -> _ {
                 rocket
             },
         ))
+        .attach(jobs::fairing())
         .attach(Template::fairing())
         .attach(cache::StaticCache)
         .mount("/", routes![index])
         .mount("/", routes::auth::routes())
+        .mount("/admin/events", routes::admin::events::routes())
         .mount("/admin/posts", routes::admin::posts::routes())
         .mount("/admin/groups", routes::admin::groups::routes())
+        .mount("/admin/routes", routes::admin::hike_routes::routes())
+        .mount("/admin/tokens", routes::admin::tokens::routes())
+        .mount("/admin/scans", routes::admin::scans::routes())
+        .mount("/admin/audit", routes::admin::audit::routes())
+        .mount("/admin/trash", routes::admin::trash::routes())
         .mount("/scan", routes::scan::routes())
         .mount("/scan", routes::scan::edit::routes())
+        .mount("/scan", routes::scan::offline::routes())
+        .mount("/", routes::pwa::routes())
+        .mount("/live", routes::live::routes())
         .mount("/dashboard", routes::dashboard::routes())
         .mount("/post", routes::post::routes())
         .mount("/ranking", routes::ranking::routes())
+        .mount("/leaderboard", routes::leaderboard::routes())
+        .mount("/api/v1", routes::api::v1::routes())
+        .register("/api", routes::api::catchers())
         .mount("/static", FileServer::from("static"))
 }
@@ -1,12 +1,262 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, TimeDelta};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
-use crate::schema::{groups, posts, scans};
+use std::collections::HashMap;
+
+use crate::schema::{
+    adjustments, api_tokens, audit_log, client_actions, duplicate_scans, events, groups,
+    hike_routes, post_criteria, posts, route_posts, scans, scores,
+};
+
+// ============ EVENT MODELS ============
+
+pub const EVENT_DRAFT: &str = "draft";
+pub const EVENT_ACTIVE: &str = "active";
+pub const EVENT_ARCHIVED: &str = "archived";
+
+/// Scans may be recorded at posts in any order
+pub const SCAN_ORDER_OFF: &str = "off";
+/// Scans skipping a post on the route are recorded but marked
+pub const SCAN_ORDER_WARN: &str = "warn";
+/// Post holders cannot record scans skipping a post on the route, admins can
+pub const SCAN_ORDER_BLOCK: &str = "block";
+
+/// One edition of the hike. Groups, posts and routes belong to an event.
+///
+/// Exactly one event is active at a time, it is the one being hiked. Draft events are being
+/// prepared and can be changed too, archived events are kept to look back on.
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = events)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Event {
+    pub id: String,
+    pub name: String,
+    /// `draft`, `active` or `archived`
+    pub status: String,
+    pub created_at: NaiveDateTime,
+    /// Comma separated ranking criteria, see [`crate::ranking_rules::Criterion`]
+    pub ranking_criteria: String,
+    /// Seconds of idle time credited per visited post at least
+    pub min_idle_credit: i32,
+    /// Seconds of idle time per post at most
+    pub max_post_time: Option<i32>,
+    /// Whether the leaderboard is shown without login
+    pub public_ranking: bool,
+    /// Comma separated group columns shown on the public leaderboard, besides the rank
+    pub public_fields: String,
+    /// From this time on the public leaderboard no longer changes, until revealed
+    pub public_freeze_at: Option<NaiveDateTime>,
+    /// Show the final standings on the public leaderboard, also when frozen
+    pub public_revealed: bool,
+    /// Public standings as JSON, as they were when the leaderboard froze
+    #[serde(skip_serializing)]
+    pub public_snapshot: Option<String>,
+    /// Route order check for scans, `off`, `warn` or `block`
+    pub scan_order: String,
+    /// Seconds a group may take from start to finish, groups taking longer did not finish
+    pub max_course_time: Option<i32>,
+}
+
+impl Event {
+    /// New events start as draft
+    pub fn insert(conn: &mut SqliteConnection, name: &str) -> QueryResult<String> {
+        let id = new_short_id();
+        diesel::insert_into(events::table)
+            .values((
+                events::id.eq(&id),
+                events::name.eq(name),
+                events::status.eq(EVENT_DRAFT),
+                events::created_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(conn)?;
+        Ok(id)
+    }
+
+    pub fn get_all(conn: &mut SqliteConnection) -> QueryResult<Vec<Event>> {
+        events::table
+            .order(events::created_at.desc())
+            .load::<Event>(conn)
+    }
+
+    pub fn get_by_id(conn: &mut SqliteConnection, event_id: &str) -> QueryResult<Option<Event>> {
+        events::table
+            .filter(events::id.eq(event_id))
+            .first::<Event>(conn)
+            .optional()
+    }
+
+    pub fn get_active(conn: &mut SqliteConnection) -> QueryResult<Event> {
+        events::table
+            .filter(events::status.eq(EVENT_ACTIVE))
+            .first::<Event>(conn)
+    }
+
+    /// Whether groups and scans of the event can be changed, archived events are read-only
+    pub fn is_editable(&self) -> bool {
+        self.status != EVENT_ARCHIVED
+    }
+
+    pub fn rename(conn: &mut SqliteConnection, event_id: &str, name: &str) -> QueryResult<usize> {
+        diesel::update(events::table.filter(events::id.eq(event_id)))
+            .set(events::name.eq(name))
+            .execute(conn)
+    }
+
+    pub fn set_ranking_rules(
+        conn: &mut SqliteConnection,
+        event_id: &str,
+        criteria: &str,
+        min_idle_credit: i32,
+        max_post_time: Option<i32>,
+        max_course_time: Option<i32>,
+    ) -> QueryResult<usize> {
+        diesel::update(events::table.filter(events::id.eq(event_id)))
+            .set((
+                events::ranking_criteria.eq(criteria),
+                events::min_idle_credit.eq(min_idle_credit),
+                events::max_post_time.eq(max_post_time),
+                events::max_course_time.eq(max_course_time),
+            ))
+            .execute(conn)
+    }
+
+    /// Mark groups that exceeded the max course time as not finished, while the event is active.
+    /// Returns the IDs of the groups marked.
+    pub fn apply_max_course_time(&self, conn: &mut SqliteConnection) -> QueryResult<Vec<String>> {
+        match self.max_course_time {
+            Some(max) if self.status == EVENT_ACTIVE => Group::mark_over_time(
+                conn,
+                &self.id,
+                TimeDelta::seconds(max.into()),
+                chrono::Utc::now().naive_utc(),
+            ),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    pub fn set_public_ranking(
+        conn: &mut SqliteConnection,
+        event_id: &str,
+        enabled: bool,
+        fields: &str,
+        freeze_at: Option<NaiveDateTime>,
+    ) -> QueryResult<usize> {
+        conn.transaction(|conn| {
+            // Standings frozen at another time no longer hold
+            diesel::update(
+                events::table
+                    .filter(events::id.eq(event_id))
+                    .filter(events::public_freeze_at.is_not(freeze_at)),
+            )
+            .set(events::public_snapshot.eq(None::<String>))
+            .execute(conn)?;
+            diesel::update(events::table.filter(events::id.eq(event_id)))
+                .set((
+                    events::public_ranking.eq(enabled),
+                    events::public_fields.eq(fields),
+                    events::public_freeze_at.eq(freeze_at),
+                ))
+                .execute(conn)
+        })
+    }
+
+    /// Keep the public standings of the moment the leaderboard froze
+    pub fn set_public_snapshot(
+        conn: &mut SqliteConnection,
+        event_id: &str,
+        snapshot: &str,
+    ) -> QueryResult<usize> {
+        diesel::update(events::table.filter(events::id.eq(event_id)))
+            .set(events::public_snapshot.eq(snapshot))
+            .execute(conn)
+    }
+
+    pub fn set_revealed(
+        conn: &mut SqliteConnection,
+        event_id: &str,
+        revealed: bool,
+    ) -> QueryResult<usize> {
+        diesel::update(events::table.filter(events::id.eq(event_id)))
+            .set(events::public_revealed.eq(revealed))
+            .execute(conn)
+    }
+
+    pub fn set_scan_order(
+        conn: &mut SqliteConnection,
+        event_id: &str,
+        scan_order: &str,
+    ) -> QueryResult<usize> {
+        diesel::update(events::table.filter(events::id.eq(event_id)))
+            .set(events::scan_order.eq(scan_order))
+            .execute(conn)
+    }
+
+    /// Make this the active event, archiving the event that was active before. An archived event
+    /// is only reopened with `reopen_archived`, its results can change again once active.
+    pub fn activate(
+        conn: &mut SqliteConnection,
+        event_id: &str,
+        reopen_archived: bool,
+    ) -> QueryResult<usize> {
+        conn.transaction(|conn| {
+            match Self::get_by_id(conn, event_id)? {
+                Some(event) if event.is_editable() || reopen_archived => {}
+                _ => return Ok(0),
+            }
+            diesel::update(events::table.filter(events::status.eq(EVENT_ACTIVE)))
+                .set(events::status.eq(EVENT_ARCHIVED))
+                .execute(conn)?;
+            diesel::update(events::table.filter(events::id.eq(event_id)))
+                .set(events::status.eq(EVENT_ACTIVE))
+                .execute(conn)
+        })
+    }
+
+    /// Archive an event that is not active. The active event is archived by activating another.
+    pub fn archive(conn: &mut SqliteConnection, event_id: &str) -> QueryResult<usize> {
+        diesel::update(
+            events::table
+                .filter(events::id.eq(event_id))
+                .filter(events::status.ne(EVENT_ACTIVE)),
+        )
+        .set(events::status.eq(EVENT_ARCHIVED))
+        .execute(conn)
+    }
+}
 
 // ============ GROUP MODELS ============
 
+pub const GROUP_REGISTERED: &str = "registered";
+pub const GROUP_STARTED: &str = "started";
+pub const GROUP_FINISHED: &str = "finished";
+/// Did not finish, given by the organisation or for exceeding the max course time
+pub const GROUP_DNF: &str = "dnf";
+/// Disqualified
+pub const GROUP_DSQ: &str = "dsq";
+pub const GROUP_WITHDRAWN: &str = "withdrawn";
+
+/// Statuses that take a group out of the race, with their label
+pub const GROUP_OUT_OF_RACE: [(&str, &str); 3] = [
+    (GROUP_DNF, "Niet gefinisht"),
+    (GROUP_DSQ, "Gediskwalificeerd"),
+    (GROUP_WITHDRAWN, "Teruggetrokken"),
+];
+
+pub fn group_status_label(status: &str) -> &'static str {
+    match status {
+        GROUP_REGISTERED => "Niet gestart",
+        GROUP_STARTED => "Onderweg",
+        GROUP_FINISHED => "Voltooid",
+        _ => GROUP_OUT_OF_RACE
+            .iter()
+            .find(|(key, _)| *key == status)
+            .map_or("Onbekend", |(_, label)| label),
+    }
+}
+
 #[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
 #[diesel(table_name = groups)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
@@ -20,7 +270,23 @@ pub struct Group {
     pub finish_time: Option<NaiveDateTime>,
     pub created_at: NaiveDateTime,
     pub group_number: i32,
+    /// Name of the route with `route_id`, empty without a route
     pub route: String,
+    pub reserved: bool,
+    pub route_id: Option<String>,
+    pub event_id: String,
+    /// Category to rank the group in besides route and scout group, such as an age class
+    pub category: String,
+    /// One of [`GROUP_OUT_OF_RACE`], empty while the status follows from the start and finish
+    /// time. Use [`Group::status`] for the status of the group.
+    pub status: String,
+    /// Why the group is out of the race
+    pub status_reason: String,
+    /// Status set by an admin, also when put back in the race. The max course time no longer
+    /// changes the status of the group.
+    pub status_override: bool,
+    /// Moved to the trash at this time
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Insertable)]
@@ -36,16 +302,25 @@ pub struct NewGroup {
     pub created_at: NaiveDateTime,
     pub group_number: i32,
     pub route: String,
+    pub reserved: bool,
+    pub route_id: Option<String>,
+    pub event_id: String,
+    pub category: String,
+}
+
+/// Short random group id, as printed on group cards and written to NFC tags.
+pub fn new_short_id() -> String {
+    Uuid::new_v4().to_string()[..8].to_string()
 }
 
 impl NewGroup {
     pub fn new(
+        event_id: String,
         name: String,
         scout_group: String,
         members: String,
         phone_number: String,
         group_number: i32,
-        route: String,
     ) -> Self {
         NewGroup {
             id: Uuid::new_v4().to_string(),
@@ -57,18 +332,22 @@ impl NewGroup {
             finish_time: None,
             created_at: chrono::Utc::now().naive_utc(),
             group_number,
-            route,
+            route: String::new(),
+            reserved: false,
+            route_id: None,
+            event_id,
+            category: String::new(),
         }
     }
 
     pub fn new_with_id(
+        event_id: String,
         id: String,
         name: String,
         scout_group: String,
         members: String,
         phone_number: String,
         group_number: i32,
-        route: String,
     ) -> Self {
         NewGroup {
             id,
@@ -80,36 +359,193 @@ impl NewGroup {
             finish_time: None,
             created_at: chrono::Utc::now().naive_utc(),
             group_number,
-            route,
+            route: String::new(),
+            reserved: false,
+            route_id: None,
+            event_id,
+            category: String::new(),
+        }
+    }
+
+    /// Placeholder for an ID that is printed or written to a tag before the group registers.
+    pub fn reserved(event_id: String, id: String) -> Self {
+        NewGroup {
+            id,
+            name: String::new(),
+            scout_group: String::new(),
+            members: String::new(),
+            phone_number: String::new(),
+            start_time: None,
+            finish_time: None,
+            created_at: chrono::Utc::now().naive_utc(),
+            group_number: 0,
+            route: String::new(),
+            reserved: true,
+            route_id: None,
+            event_id,
+            category: String::new(),
         }
     }
+
+    /// Put the group on a route, or on no route for `None`
+    pub fn set_route(&mut self, route: Option<&HikeRoute>) {
+        self.route_id = route.map(|r| r.id.clone());
+        self.route = route.map(|r| r.name.clone()).unwrap_or_default();
+    }
 }
 
 impl Group {
+    /// Registered, started, finished, or the status that took the group out of the race
+    pub fn status(&self) -> &str {
+        if !self.status.is_empty() {
+            &self.status
+        } else if self.finish_time.is_some() {
+            GROUP_FINISHED
+        } else if self.start_time.is_some() {
+            GROUP_STARTED
+        } else {
+            GROUP_REGISTERED
+        }
+    }
+
+    /// Whether the group is out of the race, and left out of the ranking
+    pub fn is_out_of_race(&self) -> bool {
+        !self.status.is_empty()
+    }
+
     pub fn insert(conn: &mut SqliteConnection, new_group: NewGroup) -> QueryResult<usize> {
         diesel::insert_into(groups::table)
             .values(&new_group)
             .execute(conn)
     }
 
-    pub fn get_all(conn: &mut SqliteConnection) -> QueryResult<Vec<Group>> {
+    pub fn get_all(conn: &mut SqliteConnection, event_id: &str) -> QueryResult<Vec<Group>> {
         groups::table
+            .filter(groups::event_id.eq(event_id))
+            .filter(groups::reserved.eq(false))
+            .filter(groups::deleted_at.is_null())
             .order((groups::group_number.desc(), groups::created_at.asc()))
             .load::<Group>(conn)
     }
 
+    /// Categories in use by the groups of an event, sorted
+    pub fn get_categories(conn: &mut SqliteConnection, event_id: &str) -> QueryResult<Vec<String>> {
+        groups::table
+            .filter(groups::event_id.eq(event_id))
+            .filter(groups::category.ne(""))
+            .filter(groups::deleted_at.is_null())
+            .select(groups::category)
+            .distinct()
+            .order(groups::category.asc())
+            .load::<String>(conn)
+    }
+
+    /// Reserved IDs that have not been claimed by a group yet, oldest first.
+    pub fn get_reserved(conn: &mut SqliteConnection, event_id: &str) -> QueryResult<Vec<Group>> {
+        groups::table
+            .filter(groups::event_id.eq(event_id))
+            .filter(groups::reserved.eq(true))
+            .filter(groups::deleted_at.is_null())
+            .order(groups::created_at.asc())
+            .load::<Group>(conn)
+    }
+
+    /// Reserve `count` fresh short IDs and return them.
+    pub fn reserve(
+        conn: &mut SqliteConnection,
+        event_id: &str,
+        count: usize,
+    ) -> QueryResult<Vec<String>> {
+        let new_groups: Vec<NewGroup> = (0..count)
+            .map(|_| NewGroup::reserved(event_id.to_string(), new_short_id()))
+            .collect();
+        diesel::insert_into(groups::table)
+            .values(&new_groups)
+            .execute(conn)?;
+        Ok(new_groups.into_iter().map(|group| group.id).collect())
+    }
+
+    /// Fill in the details of a reserved ID, turning it into a regular group. IDs reserved for
+    /// an earlier event move to the event of the group.
+    pub fn claim(conn: &mut SqliteConnection, group: NewGroup) -> QueryResult<usize> {
+        diesel::update(
+            groups::table
+                .filter(groups::id.eq(&group.id))
+                .filter(groups::reserved.eq(true)),
+        )
+        .set((
+            groups::name.eq(group.name),
+            groups::scout_group.eq(group.scout_group),
+            groups::members.eq(group.members),
+            groups::phone_number.eq(group.phone_number),
+            groups::group_number.eq(group.group_number),
+            groups::route.eq(group.route),
+            groups::route_id.eq(group.route_id),
+            groups::created_at.eq(group.created_at),
+            groups::reserved.eq(false),
+            groups::event_id.eq(group.event_id),
+            groups::category.eq(group.category),
+        ))
+        .execute(conn)
+    }
+
     pub fn get_by_id(conn: &mut SqliteConnection, group_id: &str) -> QueryResult<Option<Group>> {
         groups::table
             .filter(groups::id.eq(group_id))
+            .filter(groups::deleted_at.is_null())
             .first::<Group>(conn)
             .optional()
     }
 
+    /// Groups of an event in the trash, most recently deleted first
+    pub fn get_deleted(conn: &mut SqliteConnection, event_id: &str) -> QueryResult<Vec<Group>> {
+        groups::table
+            .filter(groups::event_id.eq(event_id))
+            .filter(groups::deleted_at.is_not_null())
+            .order(groups::deleted_at.desc())
+            .load::<Group>(conn)
+    }
+
+    pub fn get_deleted_by_id(
+        conn: &mut SqliteConnection,
+        group_id: &str,
+    ) -> QueryResult<Option<Group>> {
+        groups::table
+            .filter(groups::id.eq(group_id))
+            .filter(groups::deleted_at.is_not_null())
+            .first::<Group>(conn)
+            .optional()
+    }
+
+    /// Move the group to the trash, its scans, scores and adjustments are kept for a restore
     pub fn delete(conn: &mut SqliteConnection, group_id: &str) -> QueryResult<usize> {
-        // Delete associated scans first (cascade)
-        diesel::delete(scans::table.filter(scans::group_id.eq(group_id))).execute(conn)?;
-        // Delete the group
-        diesel::delete(groups::table.filter(groups::id.eq(group_id))).execute(conn)
+        diesel::update(groups::table.filter(groups::id.eq(group_id)))
+            .set(groups::deleted_at.eq(Some(chrono::Utc::now().naive_utc())))
+            .execute(conn)
+    }
+
+    pub fn restore(conn: &mut SqliteConnection, group_id: &str) -> QueryResult<usize> {
+        diesel::update(groups::table.filter(groups::id.eq(group_id)))
+            .set(groups::deleted_at.eq(None::<NaiveDateTime>))
+            .execute(conn)
+    }
+
+    /// Permanently delete a group in the trash, with its scans, scores and adjustments
+    pub fn purge(conn: &mut SqliteConnection, group_id: &str) -> QueryResult<usize> {
+        conn.transaction(|conn| {
+            if Self::get_deleted_by_id(conn, group_id)?.is_none() {
+                return Ok(0);
+            }
+            // Delete associated scans first (cascade)
+            diesel::delete(scans::table.filter(scans::group_id.eq(group_id))).execute(conn)?;
+            diesel::delete(duplicate_scans::table.filter(duplicate_scans::group_id.eq(group_id)))
+                .execute(conn)?;
+            diesel::delete(scores::table.filter(scores::group_id.eq(group_id))).execute(conn)?;
+            diesel::delete(adjustments::table.filter(adjustments::group_id.eq(group_id)))
+                .execute(conn)?;
+            // Delete the group
+            diesel::delete(groups::table.filter(groups::id.eq(group_id))).execute(conn)
+        })
     }
 
     pub fn set_start_time(
@@ -144,6 +580,64 @@ impl Group {
             .execute(conn)
     }
 
+    /// Take a group out of the race with one of [`GROUP_OUT_OF_RACE`], or put it back with an
+    /// empty status. The status is set by an admin, so it overrides the max course time.
+    pub fn set_status(
+        conn: &mut SqliteConnection,
+        group_id: &str,
+        status: &str,
+        reason: &str,
+    ) -> QueryResult<usize> {
+        diesel::update(groups::table.filter(groups::id.eq(group_id)))
+            .set((
+                groups::status.eq(status),
+                groups::status_reason.eq(reason),
+                groups::status_override.eq(true),
+            ))
+            .execute(conn)
+    }
+
+    /// Mark groups of the event that took longer than `max_course_time` as not finished, up to
+    /// now for groups still on their way, and record it in the audit log. Groups with a status
+    /// set by an admin are left alone. Returns the IDs of the groups marked.
+    pub fn mark_over_time(
+        conn: &mut SqliteConnection,
+        event_id: &str,
+        max_course_time: TimeDelta,
+        now: NaiveDateTime,
+    ) -> QueryResult<Vec<String>> {
+        let reason = format!(
+            "Maximale tijd van {}:{:02} overschreden",
+            max_course_time.num_hours(),
+            max_course_time.num_minutes() % 60
+        );
+        conn.transaction(|conn| {
+            let over_time: Vec<Group> = Self::get_all(conn, event_id)?
+                .into_iter()
+                .filter(|g| !g.is_out_of_race() && !g.status_override)
+                .filter(|g| {
+                    g.start_time
+                        .is_some_and(|start| g.finish_time.unwrap_or(now) - start > max_course_time)
+                })
+                .collect();
+
+            for group in &over_time {
+                diesel::update(groups::table.filter(groups::id.eq(&group.id)))
+                    .set((
+                        groups::status.eq(GROUP_DNF),
+                        groups::status_reason.eq(&reason),
+                    ))
+                    .execute(conn)?;
+                let after = AuditStatus {
+                    status: GROUP_DNF.to_string(),
+                    reason: reason.clone(),
+                };
+                AuditEntry::record_status(conn, group, AUDIT_AUTOMATIC, AUDIT_OVER_TIME, after)?;
+            }
+            Ok(over_time.into_iter().map(|g| g.id).collect())
+        })
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn update_details(
         conn: &mut SqliteConnection,
@@ -153,7 +647,8 @@ impl Group {
         members: &str,
         phone_number: &str,
         group_number: i32,
-        route: &str,
+        route: Option<&HikeRoute>,
+        category: &str,
     ) -> QueryResult<usize> {
         diesel::update(groups::table.filter(groups::id.eq(group_id)))
             .set((
@@ -162,7 +657,9 @@ impl Group {
                 groups::members.eq(members),
                 groups::phone_number.eq(phone_number),
                 groups::group_number.eq(group_number),
-                groups::route.eq(route),
+                groups::route.eq(route.map(|r| r.name.as_str()).unwrap_or_default()),
+                groups::route_id.eq(route.map(|r| &r.id)),
+                groups::category.eq(category),
             ))
             .execute(conn)
     }
@@ -178,7 +675,22 @@ pub struct Post {
     pub name: String,
     pub post_order: i32,
     pub created_at: NaiveDateTime,
+    #[serde(skip_serializing)]
     pub password_hash: Option<String>,
+    pub event_id: String,
+    /// Single score up to this many points, used when the post has no criteria
+    pub max_points: Option<i32>,
+    /// Groups arriving earlier are outside the opening hours
+    pub opens_at: Option<NaiveDateTime>,
+    /// The post is no longer staffed from this time on
+    pub closes_at: Option<NaiveDateTime>,
+    /// Groups arriving later are too late, the closing time if not set
+    pub cutoff_at: Option<NaiveDateTime>,
+    /// Seconds of penalty for arriving outside the opening hours, early or late, only a warning
+    /// if not set
+    pub hours_penalty: Option<i32>,
+    /// Moved to the trash at this time
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Insertable)]
@@ -189,16 +701,20 @@ pub struct NewPost {
     pub post_order: i32,
     pub created_at: NaiveDateTime,
     pub password_hash: Option<String>,
+    pub event_id: String,
+    pub max_points: Option<i32>,
 }
 
 impl NewPost {
-    pub fn new(name: String, order: i32) -> Self {
+    pub fn new(event_id: String, name: String, order: i32) -> Self {
         NewPost {
             id: Uuid::new_v4().to_string(),
             name,
             post_order: order,
             created_at: chrono::Utc::now().naive_utc(),
             password_hash: None,
+            event_id,
+            max_points: None,
         }
     }
 }
@@ -210,8 +726,10 @@ impl Post {
             .execute(conn)
     }
 
-    pub fn get_all(conn: &mut SqliteConnection) -> QueryResult<Vec<Post>> {
+    pub fn get_all(conn: &mut SqliteConnection, event_id: &str) -> QueryResult<Vec<Post>> {
         posts::table
+            .filter(posts::event_id.eq(event_id))
+            .filter(posts::deleted_at.is_null())
             .order(posts::post_order.asc())
             .load::<Post>(conn)
     }
@@ -219,15 +737,69 @@ impl Post {
     pub fn get_by_id(conn: &mut SqliteConnection, post_id: &str) -> QueryResult<Option<Post>> {
         posts::table
             .filter(posts::id.eq(post_id))
+            .filter(posts::deleted_at.is_null())
+            .first::<Post>(conn)
+            .optional()
+    }
+
+    /// Posts of an event in the trash, most recently deleted first
+    pub fn get_deleted(conn: &mut SqliteConnection, event_id: &str) -> QueryResult<Vec<Post>> {
+        posts::table
+            .filter(posts::event_id.eq(event_id))
+            .filter(posts::deleted_at.is_not_null())
+            .order(posts::deleted_at.desc())
+            .load::<Post>(conn)
+    }
+
+    pub fn get_deleted_by_id(
+        conn: &mut SqliteConnection,
+        post_id: &str,
+    ) -> QueryResult<Option<Post>> {
+        posts::table
+            .filter(posts::id.eq(post_id))
+            .filter(posts::deleted_at.is_not_null())
             .first::<Post>(conn)
             .optional()
     }
 
+    /// Move the post to the trash. Its scans, scores, criteria and place on routes are kept for a
+    /// restore.
     pub fn delete(conn: &mut SqliteConnection, post_id: &str) -> QueryResult<usize> {
-        // Delete associated scans first (cascade)
-        diesel::delete(scans::table.filter(scans::post_id.eq(post_id))).execute(conn)?;
-        // Delete the post
-        diesel::delete(posts::table.filter(posts::id.eq(post_id))).execute(conn)
+        diesel::update(posts::table.filter(posts::id.eq(post_id)))
+            .set(posts::deleted_at.eq(Some(chrono::Utc::now().naive_utc())))
+            .execute(conn)
+    }
+
+    pub fn restore(conn: &mut SqliteConnection, post_id: &str) -> QueryResult<usize> {
+        diesel::update(posts::table.filter(posts::id.eq(post_id)))
+            .set(posts::deleted_at.eq(None::<NaiveDateTime>))
+            .execute(conn)
+    }
+
+    /// Permanently delete a post in the trash, with the scans, their automatic penalties and the
+    /// scores of every group there
+    pub fn purge(conn: &mut SqliteConnection, post_id: &str) -> QueryResult<usize> {
+        conn.transaction(|conn| {
+            if Self::get_deleted_by_id(conn, post_id)?.is_none() {
+                return Ok(0);
+            }
+            // Delete associated scans first (cascade)
+            let post_scans = scans::table
+                .filter(scans::post_id.eq(post_id))
+                .select(scans::id.nullable());
+            diesel::delete(adjustments::table.filter(adjustments::scan_id.eq_any(post_scans)))
+                .execute(conn)?;
+            diesel::delete(scans::table.filter(scans::post_id.eq(post_id))).execute(conn)?;
+            diesel::delete(duplicate_scans::table.filter(duplicate_scans::post_id.eq(post_id)))
+                .execute(conn)?;
+            diesel::delete(route_posts::table.filter(route_posts::post_id.eq(post_id)))
+                .execute(conn)?;
+            diesel::delete(scores::table.filter(scores::post_id.eq(post_id))).execute(conn)?;
+            diesel::delete(post_criteria::table.filter(post_criteria::post_id.eq(post_id)))
+                .execute(conn)?;
+            // Delete the post
+            diesel::delete(posts::table.filter(posts::id.eq(post_id))).execute(conn)
+        })
     }
 
     pub fn set_password(
@@ -241,6 +813,55 @@ impl Post {
             .execute(conn)
     }
 
+    pub fn set_max_points(
+        conn: &mut SqliteConnection,
+        post_id: &str,
+        max_points: Option<i32>,
+    ) -> QueryResult<usize> {
+        diesel::update(posts::table.filter(posts::id.eq(post_id)))
+            .set(posts::max_points.eq(max_points))
+            .execute(conn)
+    }
+
+    pub fn set_hours(
+        conn: &mut SqliteConnection,
+        post_id: &str,
+        opens_at: Option<NaiveDateTime>,
+        closes_at: Option<NaiveDateTime>,
+        cutoff_at: Option<NaiveDateTime>,
+        hours_penalty: Option<i32>,
+    ) -> QueryResult<usize> {
+        diesel::update(posts::table.filter(posts::id.eq(post_id)))
+            .set((
+                posts::opens_at.eq(opens_at),
+                posts::closes_at.eq(closes_at),
+                posts::cutoff_at.eq(cutoff_at),
+                posts::hours_penalty.eq(hours_penalty),
+            ))
+            .execute(conn)
+    }
+
+    /// Last time a group can arrive in time
+    pub fn last_arrival(&self) -> Option<NaiveDateTime> {
+        self.cutoff_at.or(self.closes_at)
+    }
+
+    /// Why arriving at `at` is outside the opening hours, if it is
+    pub fn hours_warning(&self, at: NaiveDateTime) -> Option<String> {
+        if let Some(opens_at) = self.opens_at.filter(|opens_at| at < *opens_at) {
+            return Some(format!(
+                "Aangekomen voor de opening om {}",
+                opens_at.format("%H:%M")
+            ));
+        }
+        self.last_arrival().filter(|last| at > *last).map(|last| {
+            format!(
+                "Aangekomen na de sluitingstijd van {}",
+                last.format("%H:%M")
+            )
+        })
+    }
+
     pub fn clear_password(conn: &mut SqliteConnection, post_id: &str) -> QueryResult<usize> {
         diesel::update(posts::table.filter(posts::id.eq(post_id)))
             .set(posts::password_hash.eq(None::<String>))
@@ -254,18 +875,219 @@ impl Post {
         }
     }
 
+    /// Post of the given event with this password
     pub fn find_by_password(
         conn: &mut SqliteConnection,
+        event_id: &str,
         password: &str,
     ) -> QueryResult<Option<Post>> {
         let posts = posts::table
+            .filter(posts::event_id.eq(event_id))
             .filter(posts::password_hash.is_not_null())
+            .filter(posts::deleted_at.is_null())
             .load::<Post>(conn)?;
 
         Ok(posts.into_iter().find(|p| p.verify_password(password)))
     }
 }
 
+// ============ ROUTE MODELS ============
+
+/// A route of the hike, visiting some of the posts in its own order. Groups refer to a route
+/// by its ID through `Group::route_id`.
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = hike_routes)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct HikeRoute {
+    pub id: String,
+    pub event_id: String,
+    pub name: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = route_posts)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RoutePost {
+    pub route_id: String,
+    pub post_id: String,
+    pub position: i32,
+}
+
+impl HikeRoute {
+    pub fn insert(conn: &mut SqliteConnection, event_id: &str, name: &str) -> QueryResult<String> {
+        let id = new_short_id();
+        diesel::insert_into(hike_routes::table)
+            .values((
+                hike_routes::id.eq(&id),
+                hike_routes::event_id.eq(event_id),
+                hike_routes::name.eq(name),
+                hike_routes::created_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(conn)?;
+        Ok(id)
+    }
+
+    pub fn get_all(conn: &mut SqliteConnection, event_id: &str) -> QueryResult<Vec<HikeRoute>> {
+        hike_routes::table
+            .filter(hike_routes::event_id.eq(event_id))
+            .order(hike_routes::name)
+            .load::<HikeRoute>(conn)
+    }
+
+    pub fn get_by_id(
+        conn: &mut SqliteConnection,
+        route_id: &str,
+    ) -> QueryResult<Option<HikeRoute>> {
+        hike_routes::table
+            .filter(hike_routes::id.eq(route_id))
+            .first::<HikeRoute>(conn)
+            .optional()
+    }
+
+    /// The route with this ID if it belongs to the event, so `None` for an empty ID
+    pub fn get_in_event(
+        conn: &mut SqliteConnection,
+        event_id: &str,
+        route_id: &str,
+    ) -> QueryResult<Option<HikeRoute>> {
+        hike_routes::table
+            .filter(hike_routes::id.eq(route_id))
+            .filter(hike_routes::event_id.eq(event_id))
+            .first::<HikeRoute>(conn)
+            .optional()
+    }
+
+    /// The route of an event with this name ignoring case, created without posts when there is
+    /// none yet. `None` for an empty name.
+    pub fn find_or_create(
+        conn: &mut SqliteConnection,
+        event_id: &str,
+        name: &str,
+    ) -> QueryResult<Option<HikeRoute>> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Ok(None);
+        }
+        let existing = Self::get_all(conn, event_id)?
+            .into_iter()
+            .find(|r| r.name.to_lowercase() == name.to_lowercase());
+        if existing.is_some() {
+            return Ok(existing);
+        }
+        let id = Self::insert(conn, event_id, name)?;
+        Self::get_by_id(conn, &id)
+    }
+
+    /// Rename a route, and the route name shown for its groups
+    pub fn rename(conn: &mut SqliteConnection, route_id: &str, name: &str) -> QueryResult<usize> {
+        conn.transaction(|conn| {
+            diesel::update(groups::table.filter(groups::route_id.eq(route_id)))
+                .set(groups::route.eq(name))
+                .execute(conn)?;
+            diesel::update(hike_routes::table.filter(hike_routes::id.eq(route_id)))
+                .set(hike_routes::name.eq(name))
+                .execute(conn)
+        })
+    }
+
+    /// Groups on a deleted route are left without a route, and visit all posts
+    pub fn delete(conn: &mut SqliteConnection, route_id: &str) -> QueryResult<usize> {
+        conn.transaction(|conn| {
+            diesel::update(groups::table.filter(groups::route_id.eq(route_id)))
+                .set((groups::route.eq(""), groups::route_id.eq(None::<String>)))
+                .execute(conn)?;
+            diesel::delete(route_posts::table.filter(route_posts::route_id.eq(route_id)))
+                .execute(conn)?;
+            diesel::delete(hike_routes::table.filter(hike_routes::id.eq(route_id))).execute(conn)
+        })
+    }
+
+    pub fn get_route_posts(conn: &mut SqliteConnection) -> QueryResult<Vec<RoutePost>> {
+        route_posts::table
+            .order((route_posts::route_id, route_posts::position))
+            .load::<RoutePost>(conn)
+    }
+
+    /// Replace the posts on a route by `(post_id, position)` pairs
+    pub fn set_posts(
+        conn: &mut SqliteConnection,
+        route_id: &str,
+        posts: Vec<(String, i32)>,
+    ) -> QueryResult<usize> {
+        conn.transaction(|conn| {
+            diesel::delete(route_posts::table.filter(route_posts::route_id.eq(route_id)))
+                .execute(conn)?;
+            let route_posts: Vec<RoutePost> = posts
+                .into_iter()
+                .map(|(post_id, position)| RoutePost {
+                    route_id: route_id.to_string(),
+                    post_id,
+                    position,
+                })
+                .collect();
+            diesel::insert_into(route_posts::table)
+                .values(&route_posts)
+                .execute(conn)
+        })
+    }
+}
+
+/// Posts per route, to look up which posts a group has to visit.
+///
+/// Groups without a route, or with a route that has no posts yet, visit all posts.
+#[derive(Debug, Clone)]
+pub struct RouteMap {
+    pub routes: Vec<HikeRoute>,
+    all_posts: Vec<Post>,
+    /// Posts in route order, by route ID
+    posts_by_route: HashMap<String, Vec<Post>>,
+}
+
+impl RouteMap {
+    pub fn load(conn: &mut SqliteConnection, event_id: &str) -> QueryResult<RouteMap> {
+        let routes = HikeRoute::get_all(conn, event_id)?;
+        let all_posts = Post::get_all(conn, event_id)?;
+        let route_posts = HikeRoute::get_route_posts(conn)?;
+
+        let posts_by_route = routes
+            .iter()
+            .map(|route| {
+                let posts: Vec<Post> = route_posts
+                    .iter()
+                    .filter(|rp| rp.route_id == route.id)
+                    .filter_map(|rp| all_posts.iter().find(|p| p.id == rp.post_id).cloned())
+                    .collect();
+                (route.id.clone(), posts)
+            })
+            .filter(|(_, posts)| !posts.is_empty())
+            .collect();
+
+        Ok(RouteMap {
+            routes,
+            all_posts,
+            posts_by_route,
+        })
+    }
+
+    /// The route with this ID, if any
+    pub fn route(&self, route_id: Option<&str>) -> Option<&HikeRoute> {
+        self.routes.iter().find(|r| Some(r.id.as_str()) == route_id)
+    }
+
+    /// Posts a group on the route with `route_id` has to visit, in order
+    pub fn posts_for(&self, route_id: Option<&str>) -> Vec<Post> {
+        route_id
+            .and_then(|id| self.posts_by_route.get(id))
+            .cloned()
+            .unwrap_or_else(|| self.all_posts.clone())
+    }
+
+    pub fn all_posts(&self) -> &[Post] {
+        &self.all_posts
+    }
+}
+
 // ============ SCAN MODELS ============
 
 #[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
@@ -277,6 +1099,10 @@ pub struct Scan {
     pub post_id: String,
     pub arrival_time: NaiveDateTime,
     pub departure_time: Option<NaiveDateTime>,
+    /// Recorded while an earlier post on the route was not visited yet
+    pub out_of_order: bool,
+    /// Moved to the trash at this time
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Insertable)]
@@ -287,6 +1113,7 @@ pub struct NewScan {
     pub post_id: String,
     pub arrival_time: NaiveDateTime,
     pub departure_time: Option<NaiveDateTime>,
+    pub out_of_order: bool,
 }
 
 impl NewScan {
@@ -297,6 +1124,7 @@ impl NewScan {
             post_id,
             arrival_time: chrono::Utc::now().naive_utc(),
             departure_time: None,
+            out_of_order: false,
         }
     }
 }
@@ -311,6 +1139,7 @@ impl Scan {
     pub fn get_by_id(conn: &mut SqliteConnection, scan_id: &str) -> QueryResult<Option<Scan>> {
         scans::table
             .filter(scans::id.eq(scan_id))
+            .filter(scans::deleted_at.is_null())
             .first::<Scan>(conn)
             .optional()
     }
@@ -323,6 +1152,7 @@ impl Scan {
         scans::table
             .filter(scans::group_id.eq(group_id))
             .filter(scans::post_id.eq(post_id))
+            .filter(scans::deleted_at.is_null())
             .first::<Scan>(conn)
             .optional()
     }
@@ -332,6 +1162,8 @@ impl Scan {
         scans::table
             .inner_join(posts::table)
             .filter(scans::group_id.eq(group_id))
+            .filter(scans::deleted_at.is_null())
+            .filter(posts::deleted_at.is_null())
             .order(posts::post_order.asc())
             .select(Scan::as_select())
             .load::<Scan>(conn)
@@ -363,13 +1195,760 @@ impl Scan {
             .execute(conn)
     }
 
+    /// Accept a scan that skipped a post, e.g. because the group was sent a different way
+    pub fn approve_order(conn: &mut SqliteConnection, scan_id: &str) -> QueryResult<usize> {
+        diesel::update(scans::table.filter(scans::id.eq(scan_id)))
+            .set(scans::out_of_order.eq(false))
+            .execute(conn)
+    }
+
+    /// Move the scan to the trash
     pub fn delete(conn: &mut SqliteConnection, scan_id: &str) -> QueryResult<usize> {
-        diesel::delete(scans::table.filter(scans::id.eq(scan_id))).execute(conn)
+        diesel::update(scans::table.filter(scans::id.eq(scan_id)))
+            .set(scans::deleted_at.eq(Some(chrono::Utc::now().naive_utc())))
+            .execute(conn)
+    }
+
+    /// Fails with a unique violation when the group has a new scan at the post by now
+    pub fn restore(conn: &mut SqliteConnection, scan_id: &str) -> QueryResult<usize> {
+        diesel::update(scans::table.filter(scans::id.eq(scan_id)))
+            .set(scans::deleted_at.eq(None::<NaiveDateTime>))
+            .execute(conn)
+    }
+
+    /// Permanently delete a scan, whether it is in the trash or not, with its automatic penalty
+    pub fn purge(conn: &mut SqliteConnection, scan_id: &str) -> QueryResult<usize> {
+        conn.transaction(|conn| {
+            Adjustment::delete_by_scan(conn, scan_id)?;
+            diesel::delete(scans::table.filter(scans::id.eq(scan_id))).execute(conn)
+        })
+    }
+
+    /// Scans in the trash of groups of an event, most recently deleted first
+    pub fn get_deleted(conn: &mut SqliteConnection, event_id: &str) -> QueryResult<Vec<Scan>> {
+        scans::table
+            .inner_join(groups::table)
+            .filter(groups::event_id.eq(event_id))
+            .filter(scans::deleted_at.is_not_null())
+            .order(scans::deleted_at.desc())
+            .select(Scan::as_select())
+            .load::<Scan>(conn)
+    }
+
+    pub fn get_deleted_by_id(
+        conn: &mut SqliteConnection,
+        scan_id: &str,
+    ) -> QueryResult<Option<Scan>> {
+        scans::table
+            .filter(scans::id.eq(scan_id))
+            .filter(scans::deleted_at.is_not_null())
+            .first::<Scan>(conn)
+            .optional()
     }
 
     pub fn get_by_post(conn: &mut SqliteConnection, post_id: &str) -> QueryResult<Vec<Scan>> {
         scans::table
             .filter(scans::post_id.eq(post_id))
+            .filter(scans::deleted_at.is_null())
             .load::<Scan>(conn)
     }
 }
+
+/// Scan moved aside because another scan exists for the same group and post, to be reviewed
+/// by an admin.
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = duplicate_scans)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct DuplicateScan {
+    pub id: String,
+    pub group_id: String,
+    pub post_id: String,
+    pub arrival_time: NaiveDateTime,
+    pub departure_time: Option<NaiveDateTime>,
+    pub kept_scan_id: String,
+    pub detected_at: NaiveDateTime,
+}
+
+impl DuplicateScan {
+    pub fn get_all(conn: &mut SqliteConnection) -> QueryResult<Vec<DuplicateScan>> {
+        duplicate_scans::table
+            .order((duplicate_scans::group_id, duplicate_scans::arrival_time))
+            .load::<DuplicateScan>(conn)
+    }
+
+    pub fn count(conn: &mut SqliteConnection) -> QueryResult<i64> {
+        duplicate_scans::table.count().get_result(conn)
+    }
+
+    pub fn get_by_id(
+        conn: &mut SqliteConnection,
+        duplicate_id: &str,
+    ) -> QueryResult<Option<DuplicateScan>> {
+        duplicate_scans::table
+            .filter(duplicate_scans::id.eq(duplicate_id))
+            .first::<DuplicateScan>(conn)
+            .optional()
+    }
+
+    /// Replace the times of the kept scan with those of the duplicate, record the change in the
+    /// audit log as made by `actor`, and remove the duplicate
+    pub fn keep(
+        conn: &mut SqliteConnection,
+        duplicate_id: &str,
+        actor: &str,
+    ) -> QueryResult<usize> {
+        conn.transaction(|conn| {
+            let Some(duplicate) = Self::get_by_id(conn, duplicate_id)? else {
+                return Ok(0);
+            };
+            let scan = Scan::get_by_id(conn, &duplicate.kept_scan_id)?;
+            let group = Group::get_by_id(conn, &duplicate.group_id)?;
+            diesel::update(scans::table.filter(scans::id.eq(&duplicate.kept_scan_id)))
+                .set((
+                    scans::arrival_time.eq(duplicate.arrival_time),
+                    scans::departure_time.eq(duplicate.departure_time),
+                ))
+                .execute(conn)?;
+            if let (Some(scan), Some(group)) = (scan, group) {
+                let after = AuditTimes {
+                    from: Some(duplicate.arrival_time),
+                    until: duplicate.departure_time,
+                };
+                AuditEntry::record(
+                    conn,
+                    &group,
+                    Some(&scan.post_id),
+                    actor,
+                    AUDIT_EDIT_SCAN,
+                    Some(AuditTimes::of_scan(&scan)),
+                    Some(after),
+                )?;
+            }
+            Self::delete(conn, duplicate_id)
+        })
+    }
+
+    pub fn delete(conn: &mut SqliteConnection, duplicate_id: &str) -> QueryResult<usize> {
+        diesel::delete(duplicate_scans::table.filter(duplicate_scans::id.eq(duplicate_id)))
+            .execute(conn)
+    }
+}
+
+// ============ SCORE MODELS ============
+
+/// Criterion ID of the single score of a post without criteria
+pub const TOTAL_SCORE: &str = "total";
+
+/// Named part of the activity at a post, scored separately
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = post_criteria)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct PostCriterion {
+    pub id: String,
+    pub post_id: String,
+    pub name: String,
+    pub max_points: i32,
+    pub position: i32,
+}
+
+impl PostCriterion {
+    /// Add a criterion after the existing criteria of the post
+    pub fn insert(
+        conn: &mut SqliteConnection,
+        post_id: &str,
+        name: &str,
+        max_points: i32,
+    ) -> QueryResult<usize> {
+        conn.transaction(|conn| {
+            let position = Self::get_by_post(conn, post_id)?.len() as i32 + 1;
+            diesel::insert_into(post_criteria::table)
+                .values((
+                    post_criteria::id.eq(new_short_id()),
+                    post_criteria::post_id.eq(post_id),
+                    post_criteria::name.eq(name),
+                    post_criteria::max_points.eq(max_points),
+                    post_criteria::position.eq(position),
+                ))
+                .execute(conn)
+        })
+    }
+
+    pub fn get_by_post(
+        conn: &mut SqliteConnection,
+        post_id: &str,
+    ) -> QueryResult<Vec<PostCriterion>> {
+        post_criteria::table
+            .filter(post_criteria::post_id.eq(post_id))
+            .order(post_criteria::position)
+            .load::<PostCriterion>(conn)
+    }
+
+    /// Criteria of all posts of an event
+    pub fn get_by_event(
+        conn: &mut SqliteConnection,
+        event_id: &str,
+    ) -> QueryResult<Vec<PostCriterion>> {
+        post_criteria::table
+            .inner_join(posts::table)
+            .filter(posts::event_id.eq(event_id))
+            .filter(posts::deleted_at.is_null())
+            .order((post_criteria::post_id, post_criteria::position))
+            .select(PostCriterion::as_select())
+            .load::<PostCriterion>(conn)
+    }
+
+    /// Scores already given for the criterion are removed with it
+    pub fn delete(conn: &mut SqliteConnection, criterion_id: &str) -> QueryResult<usize> {
+        diesel::delete(scores::table.filter(scores::criterion_id.eq(criterion_id)))
+            .execute(conn)?;
+        diesel::delete(post_criteria::table.filter(post_criteria::id.eq(criterion_id)))
+            .execute(conn)
+    }
+}
+
+/// Points a group scored at a post for one criterion, or for the post as a whole
+#[derive(Debug, Clone, Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = scores)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Score {
+    pub group_id: String,
+    pub post_id: String,
+    /// Criterion ID, or [`TOTAL_SCORE`]
+    pub criterion_id: String,
+    pub points: i32,
+    /// Post of the post holder that entered the score, `None` for an admin
+    pub judged_by: Option<String>,
+    pub updated_at: NaiveDateTime,
+}
+
+impl Score {
+    /// Insert or replace a score
+    pub fn set(conn: &mut SqliteConnection, score: &Score) -> QueryResult<usize> {
+        diesel::replace_into(scores::table)
+            .values(score)
+            .execute(conn)
+    }
+
+    pub fn clear(
+        conn: &mut SqliteConnection,
+        group_id: &str,
+        post_id: &str,
+        criterion_id: &str,
+    ) -> QueryResult<usize> {
+        diesel::delete(
+            scores::table
+                .filter(scores::group_id.eq(group_id))
+                .filter(scores::post_id.eq(post_id))
+                .filter(scores::criterion_id.eq(criterion_id)),
+        )
+        .execute(conn)
+    }
+
+    pub fn get_by_group(conn: &mut SqliteConnection, group_id: &str) -> QueryResult<Vec<Score>> {
+        scores::table
+            .filter(scores::group_id.eq(group_id))
+            .load::<Score>(conn)
+    }
+}
+
+// ============ ADJUSTMENT MODELS ============
+
+/// Time penalty or bonus, `amount` in seconds, positive adds time
+pub const ADJUSTMENT_TIME: &str = "time";
+/// Points bonus or penalty, positive adds points
+pub const ADJUSTMENT_POINTS: &str = "points";
+
+/// Penalty or bonus given to a group by an admin, e.g. for a rule violation or for help
+/// requested.
+#[derive(Debug, Clone, Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = adjustments)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Adjustment {
+    pub id: String,
+    pub group_id: String,
+    /// [`ADJUSTMENT_TIME`] or [`ADJUSTMENT_POINTS`]
+    pub kind: String,
+    pub amount: i32,
+    pub reason: String,
+    /// Name of the admin that gave the adjustment
+    pub author: String,
+    pub created_at: NaiveDateTime,
+    /// Scan the adjustment was given for automatically, empty when given by an admin
+    pub scan_id: Option<String>,
+}
+
+impl Adjustment {
+    pub fn new(group_id: String, kind: &str, amount: i32, reason: String, author: String) -> Self {
+        Adjustment {
+            id: new_short_id(),
+            group_id,
+            kind: kind.to_string(),
+            amount,
+            reason,
+            author,
+            created_at: chrono::Utc::now().naive_utc(),
+            scan_id: None,
+        }
+    }
+
+    pub fn insert(conn: &mut SqliteConnection, adjustment: &Adjustment) -> QueryResult<usize> {
+        diesel::insert_into(adjustments::table)
+            .values(adjustment)
+            .execute(conn)
+    }
+
+    pub fn get_by_group(
+        conn: &mut SqliteConnection,
+        group_id: &str,
+    ) -> QueryResult<Vec<Adjustment>> {
+        adjustments::table
+            .filter(adjustments::group_id.eq(group_id))
+            .order(adjustments::created_at.asc())
+            .load::<Adjustment>(conn)
+    }
+
+    pub fn delete(
+        conn: &mut SqliteConnection,
+        group_id: &str,
+        adjustment_id: &str,
+    ) -> QueryResult<usize> {
+        diesel::delete(
+            adjustments::table
+                .filter(adjustments::id.eq(adjustment_id))
+                .filter(adjustments::group_id.eq(group_id)),
+        )
+        .execute(conn)
+    }
+
+    /// Remove the adjustments given automatically for a scan
+    pub fn delete_by_scan(conn: &mut SqliteConnection, scan_id: &str) -> QueryResult<usize> {
+        diesel::delete(adjustments::table.filter(adjustments::scan_id.eq(scan_id))).execute(conn)
+    }
+}
+
+// ============ AUDIT LOG MODELS ============
+
+/// Actor of changes made by an admin without a known session, post holders are recorded by the
+/// ID of their post
+pub const AUDIT_ADMIN: &str = "admin";
+
+/// Actor of changes made by an admin in a login session or with an API token, so every admin
+/// only undoes their own actions. Logins from before admins had sessions have an empty session.
+pub fn admin_actor(session: &str) -> String {
+    match session {
+        "" => AUDIT_ADMIN.to_string(),
+        session => format!("{AUDIT_ADMIN}:{session}"),
+    }
+}
+
+pub fn is_admin_actor(actor: &str) -> bool {
+    actor == AUDIT_ADMIN || actor.starts_with(&format!("{AUDIT_ADMIN}:"))
+}
+
+/// Actor of changes the application made by itself
+pub const AUDIT_AUTOMATIC: &str = "automatic";
+
+pub const AUDIT_START: &str = "start";
+pub const AUDIT_FINISH: &str = "finish";
+pub const AUDIT_ARRIVE: &str = "arrive";
+pub const AUDIT_LEAVE: &str = "leave";
+/// Start or finish time changed by hand
+pub const AUDIT_EDIT_TIMER: &str = "edit_timer";
+pub const AUDIT_ADD_SCAN: &str = "add_scan";
+pub const AUDIT_EDIT_SCAN: &str = "edit_scan";
+pub const AUDIT_DELETE_SCAN: &str = "delete_scan";
+/// A scan action reverted from the scan page
+pub const AUDIT_UNDO: &str = "undo";
+/// A deleted scan taken back out of the trash
+pub const AUDIT_RESTORE_SCAN: &str = "restore_scan";
+/// Group taken out of the race or put back by an admin
+pub const AUDIT_STATUS: &str = "status";
+/// Group taken out of the race for exceeding the max course time
+pub const AUDIT_OVER_TIME: &str = "over_time";
+/// Scan that skipped a post on the route accepted by an admin
+pub const AUDIT_APPROVE_ORDER: &str = "approve_order";
+
+/// Dutch label of an audit action, as shown in the audit log
+pub fn audit_action_label(action: &str) -> &'static str {
+    match action {
+        AUDIT_START => "Gestart",
+        AUDIT_FINISH => "Gefinisht",
+        AUDIT_ARRIVE => "Aangekomen",
+        AUDIT_LEAVE => "Vertrokken",
+        AUDIT_EDIT_TIMER => "Start/finish gewijzigd",
+        AUDIT_ADD_SCAN => "Scan toegevoegd",
+        AUDIT_EDIT_SCAN => "Scan gewijzigd",
+        AUDIT_DELETE_SCAN => "Scan verwijderd",
+        AUDIT_UNDO => "Ongedaan gemaakt",
+        AUDIT_RESTORE_SCAN => "Scan teruggezet",
+        AUDIT_STATUS => "Status gewijzigd",
+        AUDIT_OVER_TIME => "Maximale tijd overschreden",
+        AUDIT_APPROVE_ORDER => "Volgorde goedgekeurd",
+        _ => "Onbekend",
+    }
+}
+
+/// Arrival and departure of a scan, or start and finish of the group timer, around a change
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditTimes {
+    pub from: Option<NaiveDateTime>,
+    pub until: Option<NaiveDateTime>,
+}
+
+impl AuditTimes {
+    pub fn of_scan(scan: &Scan) -> Self {
+        AuditTimes {
+            from: Some(scan.arrival_time),
+            until: scan.departure_time,
+        }
+    }
+
+    pub fn of_group(group: &Group) -> Self {
+        AuditTimes {
+            from: group.start_time,
+            until: group.finish_time,
+        }
+    }
+
+    fn to_json(self) -> String {
+        serde_json::to_string(&self).expect("Failed to serialize audit times")
+    }
+}
+
+/// Out of race status of a group around a status change, an empty status is back in the race
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditStatus {
+    pub status: String,
+    pub reason: String,
+}
+
+impl AuditStatus {
+    pub fn of_group(group: &Group) -> Self {
+        AuditStatus {
+            status: group.status.clone(),
+            reason: group.status_reason.clone(),
+        }
+    }
+
+    /// Status as shown in the audit log, with the reason if given
+    pub fn label(&self) -> String {
+        let label = match self.status.as_str() {
+            "" => "Volgens start- en eindtijd",
+            status => group_status_label(status),
+        };
+        match self.reason.is_empty() {
+            true => label.to_string(),
+            false => format!("{label}: {}", self.reason),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Failed to serialize audit status")
+    }
+}
+
+/// A change to the scans, timer or status of a group, entries are never changed or removed
+#[derive(Debug, Clone, Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = audit_log)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct AuditEntry {
+    pub id: String,
+    pub event_id: String,
+    pub group_id: String,
+    /// Post of the changed scan, empty for changes to the group timer
+    pub post_id: Option<String>,
+    /// [`admin_actor`], [`AUDIT_AUTOMATIC`] or the ID of the post of the post holder
+    pub actor: String,
+    pub action: String,
+    /// [`AuditTimes`], or [`AuditStatus`] for status changes, as JSON. Empty when the scan did
+    /// not exist.
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl AuditEntry {
+    fn insert(
+        conn: &mut SqliteConnection,
+        group: &Group,
+        post_id: Option<&str>,
+        actor: &str,
+        action: &str,
+        before: Option<String>,
+        after: Option<String>,
+    ) -> QueryResult<()> {
+        let entry = AuditEntry {
+            id: new_short_id(),
+            event_id: group.event_id.clone(),
+            group_id: group.id.clone(),
+            post_id: post_id.map(str::to_string),
+            actor: actor.to_string(),
+            action: action.to_string(),
+            before,
+            after,
+            created_at: chrono::Utc::now().naive_utc(),
+        };
+        diesel::insert_into(audit_log::table)
+            .values(&entry)
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Record a change to the timer of a group or, with a post, to its scan at that post.
+    /// Nothing is recorded when the times did not change.
+    pub fn record(
+        conn: &mut SqliteConnection,
+        group: &Group,
+        post_id: Option<&str>,
+        actor: &str,
+        action: &str,
+        before: Option<AuditTimes>,
+        after: Option<AuditTimes>,
+    ) -> QueryResult<()> {
+        if before == after {
+            return Ok(());
+        }
+        let (before, after) = (
+            before.map(AuditTimes::to_json),
+            after.map(AuditTimes::to_json),
+        );
+        Self::insert(conn, group, post_id, actor, action, before, after)
+    }
+
+    /// Record that an admin accepted a scan that skipped a post, its times stay the same
+    pub fn record_order_approval(
+        conn: &mut SqliteConnection,
+        group: &Group,
+        scan: &Scan,
+        actor: &str,
+    ) -> QueryResult<()> {
+        let times = Some(AuditTimes::of_scan(scan).to_json());
+        let post_id = Some(scan.post_id.as_str());
+        Self::insert(
+            conn,
+            group,
+            post_id,
+            actor,
+            AUDIT_APPROVE_ORDER,
+            times.clone(),
+            times,
+        )
+    }
+
+    /// Record a change to the status of a group, from its current status to `after`. Nothing is
+    /// recorded when the status did not change.
+    pub fn record_status(
+        conn: &mut SqliteConnection,
+        group: &Group,
+        actor: &str,
+        action: &str,
+        after: AuditStatus,
+    ) -> QueryResult<()> {
+        let before = AuditStatus::of_group(group);
+        if before == after {
+            return Ok(());
+        }
+        let (before, after) = (Some(before.to_json()), Some(after.to_json()));
+        Self::insert(conn, group, None, actor, action, before, after)
+    }
+
+    pub fn is_status_change(&self) -> bool {
+        self.action == AUDIT_STATUS || self.action == AUDIT_OVER_TIME
+    }
+
+    pub fn before_times(&self) -> Option<AuditTimes> {
+        self.before
+            .as_deref()
+            .filter(|_| !self.is_status_change())
+            .and_then(|s| serde_json::from_str(s).ok())
+    }
+
+    pub fn after_times(&self) -> Option<AuditTimes> {
+        self.after
+            .as_deref()
+            .filter(|_| !self.is_status_change())
+            .and_then(|s| serde_json::from_str(s).ok())
+    }
+
+    pub fn before_status(&self) -> Option<AuditStatus> {
+        self.before
+            .as_deref()
+            .filter(|_| self.is_status_change())
+            .and_then(|s| serde_json::from_str(s).ok())
+    }
+
+    pub fn after_status(&self) -> Option<AuditStatus> {
+        self.after
+            .as_deref()
+            .filter(|_| self.is_status_change())
+            .and_then(|s| serde_json::from_str(s).ok())
+    }
+
+    /// Most recent change by an actor in an event
+    pub fn last_by_actor(
+        conn: &mut SqliteConnection,
+        event_id: &str,
+        actor: &str,
+    ) -> QueryResult<Option<AuditEntry>> {
+        audit_log::table
+            .filter(audit_log::event_id.eq(event_id))
+            .filter(audit_log::actor.eq(actor))
+            .order(audit_log::created_at.desc())
+            .first::<AuditEntry>(conn)
+            .optional()
+    }
+
+    /// Entries of an event, optionally of one group or post only, newest first
+    pub fn get_by_event(
+        conn: &mut SqliteConnection,
+        event_id: &str,
+        group_id: Option<&str>,
+        post_id: Option<&str>,
+    ) -> QueryResult<Vec<AuditEntry>> {
+        let mut query = audit_log::table
+            .filter(audit_log::event_id.eq(event_id))
+            .into_boxed();
+        if let Some(group_id) = group_id {
+            query = query.filter(audit_log::group_id.eq(group_id));
+        }
+        if let Some(post_id) = post_id {
+            query = query.filter(audit_log::post_id.eq(post_id));
+        }
+        query
+            .order(audit_log::created_at.desc())
+            .load::<AuditEntry>(conn)
+    }
+}
+
+// ============ API TOKEN MODELS ============
+
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = api_tokens)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub post_id: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = api_tokens)]
+pub struct NewApiToken {
+    pub id: String,
+    pub name: String,
+    pub token_hash: String,
+    pub post_id: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl ApiToken {
+    /// Create a token for a post holder, or an admin token when `post_id` is `None`.
+    ///
+    /// Returns the plain token, which is only shown once. Tokens have the form `<id>.<secret>`
+    /// so a single hash has to be checked when verifying. The secret is a random UUID, so a
+    /// plain SHA-256 digest is enough and keeps verification cheap on every API request.
+    pub fn create(
+        conn: &mut SqliteConnection,
+        name: String,
+        post_id: Option<String>,
+    ) -> QueryResult<String> {
+        let id = new_short_id();
+        let secret = Uuid::new_v4().simple().to_string();
+        let token_hash = hash_token_secret(&secret);
+        diesel::insert_into(api_tokens::table)
+            .values(&NewApiToken {
+                id: id.clone(),
+                name,
+                token_hash,
+                post_id,
+                created_at: chrono::Utc::now().naive_utc(),
+            })
+            .execute(conn)?;
+        Ok(format!("{id}.{secret}"))
+    }
+
+    pub fn get_all(conn: &mut SqliteConnection) -> QueryResult<Vec<ApiToken>> {
+        api_tokens::table
+            .order(api_tokens::created_at.desc())
+            .load::<ApiToken>(conn)
+    }
+
+    pub fn revoke(conn: &mut SqliteConnection, token_id: &str) -> QueryResult<usize> {
+        diesel::update(
+            api_tokens::table
+                .filter(api_tokens::id.eq(token_id))
+                .filter(api_tokens::revoked_at.is_null()),
+        )
+        .set(api_tokens::revoked_at.eq(Some(chrono::Utc::now().naive_utc())))
+        .execute(conn)
+    }
+
+    /// Find the non-revoked token matching a plain `<id>.<secret>` token.
+    pub fn find_valid(conn: &mut SqliteConnection, token: &str) -> QueryResult<Option<ApiToken>> {
+        let Some((id, secret)) = token.split_once('.') else {
+            return Ok(None);
+        };
+        let api_token = api_tokens::table
+            .filter(api_tokens::id.eq(id))
+            .filter(api_tokens::revoked_at.is_null())
+            .first::<ApiToken>(conn)
+            .optional()?;
+
+        Ok(api_token.filter(|t| t.token_hash == hash_token_secret(secret)))
+    }
+}
+
+fn hash_token_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+// ============ CLIENT ACTION MODELS ============
+
+/// Scan action replayed by an offline client, with the result sent back to it.
+#[derive(Debug, Clone, Queryable, Selectable, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = client_actions)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ClientAction {
+    pub client_id: String,
+    pub group_id: String,
+    pub action: String,
+    pub status: String,
+    pub message: Option<String>,
+    pub performed_at: NaiveDateTime,
+    pub received_at: NaiveDateTime,
+    /// Audit name of whoever performed the action
+    pub actor: String,
+}
+
+impl ClientAction {
+    /// Whether a repeated client ID was used for the same action and group, by the same kind of
+    /// actor. An admin may replay an action from another login session, a post holder only from
+    /// the same post.
+    pub fn matches(&self, group_id: &str, action: &str, actor: &str) -> bool {
+        let same_actor = match is_admin_actor(actor) {
+            true => is_admin_actor(&self.actor),
+            false => self.actor == actor,
+        };
+        self.group_id == group_id && self.action == action && same_actor
+    }
+
+    pub fn insert(conn: &mut SqliteConnection, action: &ClientAction) -> QueryResult<usize> {
+        diesel::insert_into(client_actions::table)
+            .values(action)
+            .execute(conn)
+    }
+
+    pub fn get_by_client_id(
+        conn: &mut SqliteConnection,
+        client_id: &str,
+    ) -> QueryResult<Option<ClientAction>> {
+        client_actions::table
+            .filter(client_actions::client_id.eq(client_id))
+            .first::<ClientAction>(conn)
+            .optional()
+    }
+}
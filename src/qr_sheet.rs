@@ -0,0 +1,279 @@
+use printpdf::{BuiltinFont, Color, Greyscale, Line, Mm, PdfDocument, Point, Rect};
+use qrcode::QrCode;
+
+/// A4 page size in millimeters
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 10.0;
+const COLUMNS: usize = 3;
+const ROWS: usize = 4;
+const QR_SIZE: f32 = 42.0;
+const CUT_MARK: f32 = 3.0;
+
+/// Millimeters per PostScript point
+const MM_PER_PT: f32 = 25.4 / 72.0;
+
+/// A single card on the sheet: one QR code with a few lines of text below it.
+pub struct Card {
+    pub url: String,
+    pub title: String,
+    pub subtitle: String,
+    pub footer: String,
+}
+
+enum Shape {
+    /// Filled black rectangle
+    Rect { x: f32, y: f32, w: f32, h: f32 },
+    /// Thin grey line
+    Line { x1: f32, y1: f32, x2: f32, y2: f32 },
+    /// Horizontally centered text, `y` is the baseline, size in points
+    Text {
+        x: f32,
+        y: f32,
+        size: f32,
+        bold: bool,
+        text: String,
+    },
+}
+
+/// Lay out cards on A4 pages. All coordinates are in millimeters from the top left corner.
+fn layout(cards: &[Card]) -> Vec<Vec<Shape>> {
+    let cell_w = (PAGE_WIDTH - 2.0 * MARGIN) / COLUMNS as f32;
+    let cell_h = (PAGE_HEIGHT - 2.0 * MARGIN) / ROWS as f32;
+
+    cards
+        .chunks(COLUMNS * ROWS)
+        .map(|page_cards| {
+            let mut shapes = Vec::new();
+
+            // Cut marks on every grid intersection
+            for col in 0..=COLUMNS {
+                for row in 0..=ROWS {
+                    let x = MARGIN + col as f32 * cell_w;
+                    let y = MARGIN + row as f32 * cell_h;
+                    shapes.push(Shape::Line {
+                        x1: x - CUT_MARK,
+                        y1: y,
+                        x2: x + CUT_MARK,
+                        y2: y,
+                    });
+                    shapes.push(Shape::Line {
+                        x1: x,
+                        y1: y - CUT_MARK,
+                        x2: x,
+                        y2: y + CUT_MARK,
+                    });
+                }
+            }
+
+            for (i, card) in page_cards.iter().enumerate() {
+                let cell_x = MARGIN + (i % COLUMNS) as f32 * cell_w;
+                let cell_y = MARGIN + (i / COLUMNS) as f32 * cell_h;
+                let center = cell_x + cell_w / 2.0;
+
+                let qr_x = center - QR_SIZE / 2.0;
+                let qr_y = cell_y + 6.0;
+                push_qr(&mut shapes, &card.url, qr_x, qr_y);
+
+                let text_y = qr_y + QR_SIZE + 7.0;
+                let lines = [
+                    (&card.title, 12.0, true),
+                    (&card.subtitle, 10.0, false),
+                    (&card.footer, 7.0, false),
+                ];
+                for (offset, (text, size, bold)) in lines.into_iter().enumerate() {
+                    if text.is_empty() {
+                        continue;
+                    }
+                    shapes.push(Shape::Text {
+                        x: center,
+                        y: text_y + offset as f32 * 5.5,
+                        size,
+                        bold,
+                        text: truncate(text, 30),
+                    });
+                }
+            }
+
+            shapes
+        })
+        .collect()
+}
+
+fn push_qr(shapes: &mut Vec<Shape>, url: &str, x: f32, y: f32) {
+    let Ok(code) = QrCode::new(url.as_bytes()) else {
+        return;
+    };
+    let width = code.width();
+    let module = QR_SIZE / width as f32;
+    let colors = code.to_colors();
+
+    // Merge horizontal runs of dark modules to keep the output small
+    for row in 0..width {
+        let mut col = 0;
+        while col < width {
+            if colors[row * width + col] != qrcode::Color::Dark {
+                col += 1;
+                continue;
+            }
+            let start = col;
+            while col < width && colors[row * width + col] == qrcode::Color::Dark {
+                col += 1;
+            }
+            shapes.push(Shape::Rect {
+                x: x + start as f32 * module,
+                y: y + row as f32 * module,
+                w: (col - start) as f32 * module,
+                h: module,
+            });
+        }
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_chars - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+pub fn render_svg(cards: &[Card]) -> String {
+    let pages = layout(cards);
+    let total_height = PAGE_HEIGHT * pages.len().max(1) as f32;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{PAGE_WIDTH}mm\" height=\"{total_height}mm\" viewBox=\"0 0 {PAGE_WIDTH} {total_height}\" font-family=\"Helvetica, Arial, sans-serif\">\n"
+    );
+    for (page, shapes) in pages.iter().enumerate() {
+        let offset = page as f32 * PAGE_HEIGHT;
+        svg.push_str(&format!("<g transform=\"translate(0 {offset})\">\n"));
+        for shape in shapes {
+            match shape {
+                Shape::Rect { x, y, w, h } => svg.push_str(&format!(
+                    "<rect x=\"{x:.3}\" y=\"{y:.3}\" width=\"{w:.3}\" height=\"{h:.3}\"/>\n"
+                )),
+                Shape::Line { x1, y1, x2, y2 } => svg.push_str(&format!(
+                    "<line x1=\"{x1:.3}\" y1=\"{y1:.3}\" x2=\"{x2:.3}\" y2=\"{y2:.3}\" stroke=\"#888\" stroke-width=\"0.2\"/>\n"
+                )),
+                Shape::Text {
+                    x,
+                    y,
+                    size,
+                    bold,
+                    text,
+                } => svg.push_str(&format!(
+                    "<text x=\"{x:.3}\" y=\"{y:.3}\" font-size=\"{:.3}\" text-anchor=\"middle\"{}>{}</text>\n",
+                    size * MM_PER_PT,
+                    if *bold { " font-weight=\"bold\"" } else { "" },
+                    escape_xml(text),
+                )),
+            }
+        }
+        svg.push_str("</g>\n");
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub fn render_pdf(cards: &[Card]) -> Result<Vec<u8>, printpdf::Error> {
+    let pages = layout(cards);
+    let (doc, first_page, first_layer) =
+        PdfDocument::new("QR codes", Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+
+    // PDF coordinates start at the bottom left
+    let flip = |y: f32| Mm(PAGE_HEIGHT - y);
+
+    for (i, shapes) in pages.iter().enumerate() {
+        let layer = if i == 0 {
+            doc.get_page(first_page).get_layer(first_layer)
+        } else {
+            let (page, layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+            doc.get_page(page).get_layer(layer)
+        };
+
+        layer.set_outline_color(Color::Greyscale(Greyscale::new(0.5, None)));
+        layer.set_outline_thickness(0.5);
+        layer.set_fill_color(Color::Greyscale(Greyscale::new(0.0, None)));
+        for shape in shapes {
+            match shape {
+                Shape::Rect { x, y, w, h } => {
+                    layer.add_rect(Rect::new(Mm(*x), flip(y + h), Mm(x + w), flip(*y)));
+                }
+                Shape::Line { x1, y1, x2, y2 } => layer.add_line(Line {
+                    points: vec![
+                        (Point::new(Mm(*x1), flip(*y1)), false),
+                        (Point::new(Mm(*x2), flip(*y2)), false),
+                    ],
+                    is_closed: false,
+                }),
+                Shape::Text {
+                    x,
+                    y,
+                    size,
+                    bold: is_bold,
+                    text,
+                } => {
+                    // Built-in fonts only cover ASCII here and carry no metrics, so fold the
+                    // text and center on an average glyph width
+                    let text = to_ascii(text);
+                    let width = text.chars().count() as f32 * size * 0.52 * MM_PER_PT;
+                    let font = if *is_bold { &bold } else { &regular };
+                    layer.use_text(text, *size, Mm(x - width / 2.0), flip(*y), font);
+                }
+            }
+        }
+    }
+
+    doc.save_to_bytes()
+}
+
+/// Fold text to ASCII for the built-in PDF fonts: accents are dropped and anything else
+/// outside ASCII becomes '?'.
+fn to_ascii(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for c in text.chars() {
+        let replacement = match c {
+            c if c.is_ascii() => {
+                folded.push(c);
+                continue;
+            }
+            '…' => "...",
+            '–' | '—' => "-",
+            '‘' | '’' => "'",
+            '“' | '”' => "\"",
+            'À'..='Å' => "A",
+            'à'..='å' => "a",
+            'Æ' => "AE",
+            'æ' => "ae",
+            'Ç' => "C",
+            'ç' => "c",
+            'È'..='Ë' => "E",
+            'è'..='ë' => "e",
+            'Ì'..='Ï' => "I",
+            'ì'..='ï' => "i",
+            'Ñ' => "N",
+            'ñ' => "n",
+            'Ò'..='Ö' | 'Ø' => "O",
+            'ò'..='ö' | 'ø' => "o",
+            'Ù'..='Ü' => "U",
+            'ù'..='ü' => "u",
+            'Ý' => "Y",
+            'ý' | 'ÿ' => "y",
+            'ß' => "ss",
+            _ => "?",
+        };
+        folded.push_str(replacement);
+    }
+    folded
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
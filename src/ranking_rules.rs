@@ -0,0 +1,326 @@
+use std::cmp::Ordering;
+
+use chrono::TimeDelta;
+
+use crate::models::Event;
+
+/// Criterion to order groups on, the first criterion on which two groups differ decides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Criterion {
+    /// Groups that visited all posts of their route first
+    Complete,
+    /// Most visited posts first, to rank incomplete groups among themselves
+    PostsVisited,
+    /// Shortest total time first, including penalties and bonuses
+    TotalTime,
+    /// Shortest walking time first, with idle time credited by the rules
+    WalkingTime,
+    /// Most points first
+    Points,
+    /// Shortest total time first, with points converted to time
+    Combined,
+}
+
+impl Criterion {
+    pub const ALL: [Criterion; 6] = [
+        Criterion::Complete,
+        Criterion::PostsVisited,
+        Criterion::TotalTime,
+        Criterion::WalkingTime,
+        Criterion::Points,
+        Criterion::Combined,
+    ];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Criterion::Complete => "complete",
+            Criterion::PostsVisited => "posts_visited",
+            Criterion::TotalTime => "total_time",
+            Criterion::WalkingTime => "walking_time",
+            Criterion::Points => "points",
+            Criterion::Combined => "combined",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Criterion::Complete => "Alle posten bezocht",
+            Criterion::PostsVisited => "Meeste posten bezocht",
+            Criterion::TotalTime => "Kortste totale tijd",
+            Criterion::WalkingTime => "Kortste looptijd",
+            Criterion::Points => "Meeste punten",
+            Criterion::Combined => "Kortste tijd + punten",
+        }
+    }
+
+    pub fn parse(key: &str) -> Option<Criterion> {
+        Self::ALL.into_iter().find(|c| c.key() == key.trim())
+    }
+
+    fn compare(self, a: &Standing, b: &Standing) -> Ordering {
+        match self {
+            Criterion::Complete => b.visited_all_posts.cmp(&a.visited_all_posts),
+            Criterion::PostsVisited => b.posts_visited.cmp(&a.posts_visited),
+            Criterion::TotalTime => shortest(a.total_time, b.total_time),
+            Criterion::WalkingTime => shortest(a.walking_time, b.walking_time),
+            Criterion::Points => b.points.cmp(&a.points),
+            Criterion::Combined => shortest(a.combined_time, b.combined_time),
+        }
+    }
+}
+
+/// Shortest time first, groups without a time last
+fn shortest(a: Option<TimeDelta>, b: Option<TimeDelta>) -> Ordering {
+    a.unwrap_or(TimeDelta::MAX)
+        .cmp(&b.unwrap_or(TimeDelta::MAX))
+}
+
+/// What a group is ranked on
+#[derive(Debug, Clone, Default)]
+pub struct Standing {
+    pub visited_all_posts: bool,
+    pub posts_visited: usize,
+    pub total_time: Option<TimeDelta>,
+    pub walking_time: Option<TimeDelta>,
+    pub points: i32,
+    pub combined_time: Option<TimeDelta>,
+}
+
+/// Ranking configuration of an event
+#[derive(Debug, Clone)]
+pub struct RankingRules {
+    pub criteria: Vec<Criterion>,
+    /// Idle time credited per visited post at least
+    pub min_idle_credit: TimeDelta,
+    /// Idle time per post at most, longer stays count as walking time
+    pub max_post_time: Option<TimeDelta>,
+}
+
+impl Default for RankingRules {
+    fn default() -> Self {
+        RankingRules {
+            criteria: vec![Criterion::Complete, Criterion::TotalTime],
+            min_idle_credit: TimeDelta::zero(),
+            max_post_time: None,
+        }
+    }
+}
+
+impl RankingRules {
+    pub fn from_event(event: &Event) -> Self {
+        let mut criteria = parse_criteria(&event.ranking_criteria);
+        // Without criteria all groups would tie
+        if criteria.is_empty() {
+            criteria = Self::default().criteria;
+        }
+        RankingRules {
+            criteria,
+            min_idle_credit: TimeDelta::seconds(event.min_idle_credit.max(0).into()),
+            max_post_time: event
+                .max_post_time
+                .map(|secs| TimeDelta::seconds(secs.max(0).into())),
+        }
+    }
+
+    /// The same rules with other criteria, for the fixed sorts of the ranking page
+    pub fn with_criteria(self, criteria: Vec<Criterion>) -> Self {
+        RankingRules { criteria, ..self }
+    }
+
+    /// Idle time at a post that counts, between the credit and the cap
+    pub fn credited_idle_time(&self, idle_time: TimeDelta) -> TimeDelta {
+        let credited = idle_time.max(self.min_idle_credit);
+        match self.max_post_time {
+            Some(max) => credited.min(max),
+            None => credited,
+        }
+    }
+
+    pub fn compare(&self, a: &Standing, b: &Standing) -> Ordering {
+        self.criteria
+            .iter()
+            .map(|criterion| criterion.compare(a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Criteria from a comma separated list, unknown and repeated criteria are skipped
+pub fn parse_criteria(list: &str) -> Vec<Criterion> {
+    let mut criteria = Vec::new();
+    for criterion in list.split(',').filter_map(Criterion::parse) {
+        if !criteria.contains(&criterion) {
+            criteria.push(criterion);
+        }
+    }
+    criteria
+}
+
+pub fn format_criteria(criteria: &[Criterion]) -> String {
+    criteria
+        .iter()
+        .map(|c| c.key())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minutes(minutes: i64) -> TimeDelta {
+        TimeDelta::minutes(minutes)
+    }
+
+    fn rules(criteria: &[Criterion]) -> RankingRules {
+        RankingRules::default().with_criteria(criteria.to_vec())
+    }
+
+    #[test]
+    fn complete_groups_first() {
+        let complete = Standing {
+            visited_all_posts: true,
+            total_time: Some(minutes(120)),
+            ..Default::default()
+        };
+        let incomplete = Standing {
+            total_time: Some(minutes(60)),
+            ..Default::default()
+        };
+        let rules = rules(&[Criterion::Complete, Criterion::TotalTime]);
+        assert_eq!(rules.compare(&complete, &incomplete), Ordering::Less);
+        assert_eq!(rules.compare(&incomplete, &complete), Ordering::Greater);
+    }
+
+    #[test]
+    fn most_posts_visited_first() {
+        let more = Standing {
+            posts_visited: 4,
+            ..Default::default()
+        };
+        let fewer = Standing {
+            posts_visited: 2,
+            ..Default::default()
+        };
+        let rules = rules(&[Criterion::PostsVisited]);
+        assert_eq!(rules.compare(&more, &fewer), Ordering::Less);
+    }
+
+    #[test]
+    fn shortest_total_time_first_and_missing_time_last() {
+        let fast = Standing {
+            total_time: Some(minutes(90)),
+            ..Default::default()
+        };
+        let slow = Standing {
+            total_time: Some(minutes(100)),
+            ..Default::default()
+        };
+        let rules = rules(&[Criterion::TotalTime]);
+        assert_eq!(rules.compare(&fast, &slow), Ordering::Less);
+        assert_eq!(
+            rules.compare(&Standing::default(), &slow),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn shortest_walking_time_first() {
+        let fast = Standing {
+            total_time: Some(minutes(200)),
+            walking_time: Some(minutes(80)),
+            ..Default::default()
+        };
+        let slow = Standing {
+            total_time: Some(minutes(100)),
+            walking_time: Some(minutes(90)),
+            ..Default::default()
+        };
+        let rules = rules(&[Criterion::WalkingTime]);
+        assert_eq!(rules.compare(&fast, &slow), Ordering::Less);
+    }
+
+    #[test]
+    fn most_points_first() {
+        let more = Standing {
+            points: 10,
+            ..Default::default()
+        };
+        let fewer = Standing {
+            points: 7,
+            ..Default::default()
+        };
+        let rules = rules(&[Criterion::Points]);
+        assert_eq!(rules.compare(&more, &fewer), Ordering::Less);
+    }
+
+    #[test]
+    fn shortest_combined_time_first() {
+        let fast = Standing {
+            combined_time: Some(minutes(50)),
+            ..Default::default()
+        };
+        let slow = Standing {
+            combined_time: Some(minutes(70)),
+            ..Default::default()
+        };
+        let rules = rules(&[Criterion::Combined]);
+        assert_eq!(rules.compare(&fast, &slow), Ordering::Less);
+    }
+
+    #[test]
+    fn ties_are_broken_by_the_next_criterion() {
+        let a = Standing {
+            visited_all_posts: true,
+            total_time: Some(minutes(100)),
+            points: 5,
+            ..Default::default()
+        };
+        let b = Standing {
+            visited_all_posts: true,
+            total_time: Some(minutes(100)),
+            points: 8,
+            ..Default::default()
+        };
+        let rules = rules(&[Criterion::Complete, Criterion::TotalTime, Criterion::Points]);
+        assert_eq!(rules.compare(&a, &b), Ordering::Greater);
+        assert_eq!(
+            rules
+                .with_criteria(vec![Criterion::Complete, Criterion::TotalTime])
+                .compare(&a, &b),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn idle_time_is_credited_at_least_the_minimum() {
+        let rules = RankingRules {
+            min_idle_credit: minutes(10),
+            ..Default::default()
+        };
+        assert_eq!(rules.credited_idle_time(minutes(4)), minutes(10));
+        assert_eq!(rules.credited_idle_time(minutes(15)), minutes(15));
+    }
+
+    #[test]
+    fn idle_time_is_capped_per_post() {
+        let rules = RankingRules {
+            max_post_time: Some(minutes(20)),
+            ..Default::default()
+        };
+        assert_eq!(rules.credited_idle_time(minutes(35)), minutes(20));
+        assert_eq!(rules.credited_idle_time(minutes(5)), minutes(5));
+    }
+
+    #[test]
+    fn criteria_are_parsed_in_order_without_unknown_or_repeated_criteria() {
+        assert_eq!(
+            parse_criteria("complete, points,nope,points,total_time"),
+            vec![Criterion::Complete, Criterion::Points, Criterion::TotalTime]
+        );
+        assert_eq!(
+            format_criteria(&parse_criteria("posts_visited,walking_time")),
+            "posts_visited,walking_time"
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use rocket::Route;
+use rocket_dyn_templates::{context, Template};
+use serde::Serialize;
+
+use crate::auth::Admin;
+use crate::db::DbConn;
+use crate::event::{viewed_event, ActiveEvent};
+use crate::models::{
+    audit_action_label, is_admin_actor, AuditEntry, AuditTimes, Group, Post, AUDIT_AUTOMATIC,
+};
+
+#[derive(Serialize)]
+pub struct AuditEntryInfo {
+    pub entry: AuditEntry,
+    pub group: Option<Group>,
+    pub post: Option<Post>,
+    /// Admin, or the name of the post of the post holder
+    pub actor: String,
+    pub action_label: &'static str,
+    pub before: Option<AuditTimes>,
+    pub after: Option<AuditTimes>,
+    /// Labels of the status before and after, for status changes only
+    pub before_status: Option<String>,
+    pub after_status: Option<String>,
+}
+
+/// Changes to scans, group timers and statuses, per group or per post
+#[get("/?<event>&<group>&<post>")]
+pub async fn audit_log(
+    _admin: Admin,
+    conn: DbConn,
+    active: ActiveEvent,
+    event: Option<String>,
+    group: Option<String>,
+    post: Option<String>,
+) -> Option<Template> {
+    let event = viewed_event(&conn, active, event).await?;
+    let group = group.filter(|g| !g.is_empty());
+    let post = post.filter(|p| !p.is_empty());
+
+    let event_id = event.id.clone();
+    let (group_id, post_id) = (group.clone(), post.clone());
+    let (groups, posts, entries) = conn
+        .run(move |c| {
+            let groups = Group::get_all(c, &event_id)?;
+            let posts = Post::get_all(c, &event_id)?;
+            let entries =
+                AuditEntry::get_by_event(c, &event_id, group_id.as_deref(), post_id.as_deref())?;
+            Ok::<_, diesel::result::Error>((groups, posts, entries))
+        })
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to load audit log: {err}");
+            Default::default()
+        });
+
+    let groups_by_id: HashMap<&str, &Group> = groups.iter().map(|g| (g.id.as_str(), g)).collect();
+    let posts_by_id: HashMap<&str, &Post> = posts.iter().map(|p| (p.id.as_str(), p)).collect();
+    let entries: Vec<AuditEntryInfo> = entries
+        .into_iter()
+        .map(|entry| {
+            let actor = match entry.actor.as_str() {
+                actor if is_admin_actor(actor) => "Admin".to_string(),
+                AUDIT_AUTOMATIC => "Automatisch".to_string(),
+                actor => posts_by_id
+                    .get(actor)
+                    .map_or_else(|| entry.actor.clone(), |p| p.name.clone()),
+            };
+            AuditEntryInfo {
+                group: groups_by_id.get(entry.group_id.as_str()).copied().cloned(),
+                post: entry
+                    .post_id
+                    .as_deref()
+                    .and_then(|id| posts_by_id.get(id))
+                    .copied()
+                    .cloned(),
+                actor,
+                action_label: audit_action_label(&entry.action),
+                before: entry.before_times(),
+                after: entry.after_times(),
+                before_status: entry.before_status().map(|s| s.label()),
+                after_status: entry.after_status().map(|s| s.label()),
+                entry,
+            }
+        })
+        .collect();
+
+    Some(Template::render(
+        "admin/audit",
+        context! {
+            event: event,
+            groups: groups,
+            posts: posts,
+            entries: entries,
+            group_id: group,
+            post_id: post,
+            is_admin: true,
+        },
+    ))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![audit_log]
+}
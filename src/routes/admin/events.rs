@@ -0,0 +1,288 @@
+use chrono::NaiveDateTime;
+use rocket::form::Form;
+use rocket::request::FlashMessage;
+use rocket::response::{Flash, Redirect};
+use rocket::{Route, State};
+use rocket_dyn_templates::{context, Template};
+use serde::Serialize;
+
+use crate::auth::Admin;
+use crate::db::DbConn;
+use crate::live::LiveUpdates;
+use crate::models::{Event, SCAN_ORDER_BLOCK, SCAN_ORDER_OFF, SCAN_ORDER_WARN};
+use crate::ranking_rules::{format_criteria, parse_criteria, Criterion};
+use crate::routes::leaderboard::{parse_public_fields, PUBLIC_FIELDS};
+
+/// Number of ranking criteria that can be picked, in order
+const CRITERIA_SLOTS: usize = 4;
+
+/// Route order checks for scans, with their label
+const SCAN_ORDERS: [(&str, &str); 3] = [
+    (SCAN_ORDER_OFF, "Elke volgorde toestaan"),
+    (SCAN_ORDER_WARN, "Markeren als buiten volgorde"),
+    (SCAN_ORDER_BLOCK, "Blokkeren, behalve voor beheerders"),
+];
+
+#[derive(Serialize)]
+pub struct EventWithRules {
+    pub event: Event,
+    /// Criterion key per slot, empty for an unused slot
+    pub criteria: Vec<&'static str>,
+    pub min_idle_credit: i32,
+    pub max_post_time: Option<i32>,
+    pub max_course_time: Option<i32>,
+    pub public_fields: Vec<&'static str>,
+}
+
+#[get("/")]
+pub async fn events(_admin: Admin, conn: DbConn, flash: Option<FlashMessage<'_>>) -> Template {
+    let events: Vec<EventWithRules> = conn
+        .run(Event::get_all)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|event| {
+            let mut criteria: Vec<&str> = parse_criteria(&event.ranking_criteria)
+                .into_iter()
+                .map(Criterion::key)
+                .collect();
+            criteria.resize(CRITERIA_SLOTS.max(criteria.len()), "");
+            EventWithRules {
+                min_idle_credit: event.min_idle_credit / 60,
+                max_post_time: event.max_post_time.map(|secs| secs / 60),
+                max_course_time: event.max_course_time.map(|secs| secs / 60),
+                public_fields: parse_public_fields(&event.public_fields),
+                criteria,
+                event,
+            }
+        })
+        .collect();
+    let criteria: Vec<(&str, &str)> = Criterion::ALL
+        .into_iter()
+        .map(|c| (c.key(), c.label()))
+        .collect();
+    Template::render(
+        "admin/events",
+        context! {
+            events: events,
+            criteria: criteria,
+            public_fields: PUBLIC_FIELDS,
+            scan_orders: SCAN_ORDERS,
+            error: flash.map(|f| f.message().to_string()),
+            is_admin: true,
+        },
+    )
+}
+
+#[derive(FromForm)]
+pub struct EventForm {
+    name: String,
+}
+
+#[post("/", data = "<form>")]
+pub async fn create_event(_admin: Admin, conn: DbConn, form: Form<EventForm>) -> Redirect {
+    let name = form.name.trim().to_string();
+    if !name.is_empty() {
+        if let Err(err) = conn.run(move |c| Event::insert(c, &name)).await {
+            eprintln!("Failed to create event: {err}");
+        }
+    }
+    Redirect::to("/admin/events")
+}
+
+#[post("/<id>/rename", data = "<form>")]
+pub async fn rename_event(
+    _admin: Admin,
+    conn: DbConn,
+    id: String,
+    form: Form<EventForm>,
+) -> Redirect {
+    let name = form.name.trim().to_string();
+    if !name.is_empty() {
+        if let Err(err) = conn.run(move |c| Event::rename(c, &id, &name)).await {
+            eprintln!("Failed to rename event: {err}");
+        }
+    }
+    Redirect::to("/admin/events")
+}
+
+#[derive(FromForm)]
+pub struct ActivateForm {
+    /// Checked to confirm reopening an archived event
+    reopen: bool,
+}
+
+#[post("/<id>/activate", data = "<form>")]
+pub async fn activate_event(
+    _admin: Admin,
+    conn: DbConn,
+    id: String,
+    form: Form<ActivateForm>,
+) -> Redirect {
+    let reopen = form.reopen;
+    if let Err(err) = conn.run(move |c| Event::activate(c, &id, reopen)).await {
+        eprintln!("Failed to activate event: {err}");
+    }
+    Redirect::to("/admin/events")
+}
+
+#[post("/<id>/archive")]
+pub async fn archive_event(_admin: Admin, conn: DbConn, id: String) -> Redirect {
+    conn.run(move |c| Event::archive(c, &id)).await.ok();
+    Redirect::to("/admin/events")
+}
+
+#[derive(FromForm)]
+pub struct RankingRulesForm {
+    /// Criterion keys in order, empty for an unused slot
+    criteria: Vec<String>,
+    /// Minutes of idle time credited per post at least
+    min_idle_credit: Option<i32>,
+    /// Minutes of idle time per post at most, empty for no cap
+    max_post_time: Option<i32>,
+    /// Minutes from start to finish at most, empty for no limit
+    max_course_time: Option<i32>,
+}
+
+impl RankingRulesForm {
+    /// Min idle credit, max post time and max course time in seconds, `None` when a time does
+    /// not fit
+    fn seconds(&self) -> Option<(i32, Option<i32>, Option<i32>)> {
+        let seconds = |minutes: i32| minutes.checked_mul(60);
+        let optional = |minutes: Option<i32>| match minutes.filter(|m| *m > 0) {
+            Some(minutes) => seconds(minutes).map(Some),
+            None => Some(None),
+        };
+        Some((
+            seconds(self.min_idle_credit.unwrap_or(0).max(0))?,
+            optional(self.max_post_time)?,
+            optional(self.max_course_time)?,
+        ))
+    }
+}
+
+#[post("/<id>/ranking", data = "<form>")]
+pub async fn set_ranking_rules(
+    _admin: Admin,
+    conn: DbConn,
+    live: &State<LiveUpdates>,
+    id: String,
+    form: Form<RankingRulesForm>,
+) -> Result<Redirect, Flash<Redirect>> {
+    let criteria = format_criteria(&parse_criteria(&form.criteria.join(",")));
+    let Some((min_idle_credit, max_post_time, max_course_time)) = form.seconds() else {
+        return Err(Flash::error(
+            Redirect::to("/admin/events"),
+            format!("Een tijd kan hoogstens {} minuten zijn", i32::MAX / 60),
+        ));
+    };
+    let result = conn
+        .run(move |c| {
+            Event::set_ranking_rules(
+                c,
+                &id,
+                &criteria,
+                min_idle_credit,
+                max_post_time,
+                max_course_time,
+            )?;
+            // A shorter max course time can put groups over it right away
+            match Event::get_by_id(c, &id)? {
+                Some(event) => event.apply_max_course_time(c),
+                None => Ok(Vec::new()),
+            }
+        })
+        .await;
+    match result {
+        Ok(marked) => marked.iter().for_each(|group_id| live.group(group_id)),
+        Err(err) => eprintln!("Failed to set ranking rules: {err}"),
+    }
+    Ok(Redirect::to("/admin/events"))
+}
+
+#[derive(FromForm)]
+pub struct PublicRankingForm {
+    enabled: bool,
+    fields: Vec<String>,
+    /// Empty to never freeze
+    freeze_at: Option<String>,
+}
+
+#[post("/<id>/public", data = "<form>")]
+pub async fn set_public_ranking(
+    _admin: Admin,
+    conn: DbConn,
+    id: String,
+    form: Form<PublicRankingForm>,
+) -> Redirect {
+    let enabled = form.enabled;
+    let fields = parse_public_fields(&form.fields.join(",")).join(",");
+    let freeze_at = form
+        .freeze_at
+        .as_ref()
+        .and_then(|dt| NaiveDateTime::parse_from_str(dt, "%Y-%m-%dT%H:%M").ok());
+    if let Err(err) = conn
+        .run(move |c| Event::set_public_ranking(c, &id, enabled, &fields, freeze_at))
+        .await
+    {
+        eprintln!("Failed to set public ranking: {err}");
+    }
+    Redirect::to("/admin/events")
+}
+
+#[derive(FromForm)]
+pub struct ScanOrderForm {
+    scan_order: String,
+}
+
+#[post("/<id>/scan-order", data = "<form>")]
+pub async fn set_scan_order(
+    _admin: Admin,
+    conn: DbConn,
+    id: String,
+    form: Form<ScanOrderForm>,
+) -> Redirect {
+    let Some((scan_order, _)) = SCAN_ORDERS.iter().find(|(key, _)| *key == form.scan_order) else {
+        return Redirect::to("/admin/events");
+    };
+    if let Err(err) = conn
+        .run(move |c| Event::set_scan_order(c, &id, scan_order))
+        .await
+    {
+        eprintln!("Failed to set scan order: {err}");
+    }
+    Redirect::to("/admin/events")
+}
+
+/// Show the final standings on the public leaderboard, for the prize ceremony
+#[post("/<id>/reveal")]
+pub async fn reveal_ranking(_admin: Admin, conn: DbConn, id: String) -> Redirect {
+    conn.run(move |c| Event::set_revealed(c, &id, true))
+        .await
+        .ok();
+    Redirect::to("/admin/events")
+}
+
+/// Freeze the public leaderboard again after revealing it
+#[post("/<id>/unreveal")]
+pub async fn unreveal_ranking(_admin: Admin, conn: DbConn, id: String) -> Redirect {
+    conn.run(move |c| Event::set_revealed(c, &id, false))
+        .await
+        .ok();
+    Redirect::to("/admin/events")
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        events,
+        create_event,
+        rename_event,
+        activate_event,
+        archive_event,
+        set_ranking_rules,
+        set_public_ranking,
+        set_scan_order,
+        reveal_ranking,
+        unreveal_ranking
+    ]
+}
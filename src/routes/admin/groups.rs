@@ -1,37 +1,73 @@
 use image::{ImageEncoder, Luma};
 use qrcode::QrCode;
-use rocket::http::ContentType;
+use rocket::form::Form;
+use rocket::fs::TempFile;
+use rocket::http::{ContentType, Status};
 use rocket::response::Redirect;
-use rocket::Route;
+use rocket::{Route, State};
 use rocket_dyn_templates::{context, Template};
-use uuid::Uuid;
 
 use crate::auth::Admin;
+use crate::config::AppConfig;
 use crate::db::DbConn;
-use crate::models::Group;
+use crate::event::ActiveEvent;
+use crate::import::{self, ImportPreview};
+use crate::live::LiveUpdates;
+use crate::models::{new_short_id, DuplicateScan, Group};
+use crate::qr_sheet::{self, Card};
+use crate::routes::scan::get_scout_groups;
+
+/// Maximum number of fresh or reserved IDs generated at once
+const MAX_FRESH_CARDS: usize = 240;
 
 #[get("/")]
-pub async fn groups(_admin: Admin, conn: DbConn) -> Template {
-    let groups = conn.run(Group::get_all).await.unwrap_or_default();
-    Template::render("admin/groups", context! { groups: groups, is_admin: true })
+pub async fn groups(_admin: Admin, conn: DbConn, event: ActiveEvent) -> Template {
+    let event_id = event.id();
+    let groups = conn
+        .run(move |c| Group::get_all(c, &event_id))
+        .await
+        .unwrap_or_default();
+    let event_id = event.id();
+    let reserved_count = conn
+        .run(move |c| Group::get_reserved(c, &event_id))
+        .await
+        .map(|r| r.len())
+        .unwrap_or_default();
+    let duplicate_count = conn.run(DuplicateScan::count).await.unwrap_or_default();
+    Template::render(
+        "admin/groups",
+        context! {
+            groups: groups,
+            reserved_count: reserved_count,
+            duplicate_count: duplicate_count,
+            event: event.0,
+            is_admin: true,
+        },
+    )
 }
 
-#[get("/<id>/delete")]
-pub async fn delete_group(_admin: Admin, conn: DbConn, id: String) -> Redirect {
-    conn.run(move |c| Group::delete(c, &id)).await.ok();
+#[post("/<id>/delete")]
+pub async fn delete_group(
+    _admin: Admin,
+    conn: DbConn,
+    live: &State<LiveUpdates>,
+    id: String,
+) -> Redirect {
+    let gid = id.clone();
+    conn.run(move |c| Group::delete(c, &gid)).await.ok();
+    live.group(&id);
     Redirect::to("/admin/groups")
 }
 
 #[get("/new")]
 pub fn new_group(_admin: Admin) -> Redirect {
-    let short_id = &Uuid::new_v4().to_string()[..8];
+    let short_id = new_short_id();
     Redirect::to(format!("/scan/{short_id}"))
 }
 
 #[get("/<id>/qr")]
-pub fn group_qr(_admin: Admin, id: &str) -> (ContentType, Vec<u8>) {
-    // TODO: Hardcoded url
-    let url = format!("https://hike.qvdijk.nl/scan/{id}");
+pub fn group_qr(_admin: Admin, config: &State<AppConfig>, id: &str) -> (ContentType, Vec<u8>) {
+    let url = config.scan_url(id);
 
     let code = QrCode::new(url.as_bytes()).unwrap();
     let image = code.render::<Luma<u8>>().min_dimensions(200, 200).build();
@@ -50,6 +86,265 @@ pub fn group_qr(_admin: Admin, id: &str) -> (ContentType, Vec<u8>) {
     (ContentType::PNG, png_data)
 }
 
+#[derive(FromForm)]
+pub struct ReserveForm {
+    count: usize,
+}
+
+#[post("/reserve", data = "<form>")]
+pub async fn reserve_groups(
+    _admin: Admin,
+    conn: DbConn,
+    event: ActiveEvent,
+    form: Form<ReserveForm>,
+) -> Redirect {
+    let count = form.count.min(MAX_FRESH_CARDS);
+    let event_id = event.id();
+    if let Err(err) = conn.run(move |c| Group::reserve(c, &event_id, count)).await {
+        eprintln!("Failed to reserve group IDs: {err}");
+    }
+    Redirect::to("/admin/groups")
+}
+
+/// Reserved IDs in the `LINK_RECORD` CSV format of NFC writer apps
+#[get("/reserved.csv")]
+pub async fn reserved_nfc_csv(
+    _admin: Admin,
+    conn: DbConn,
+    event: ActiveEvent,
+    config: &State<AppConfig>,
+) -> (ContentType, String) {
+    let event_id = event.id();
+    let reserved = conn
+        .run(move |c| Group::get_reserved(c, &event_id))
+        .await
+        .unwrap_or_default();
+    let csv: String = reserved
+        .iter()
+        .map(|group| format!("LINK_RECORD,{},URL\n", config.scan_url(&group.id)))
+        .collect();
+    (ContentType::CSV, csv)
+}
+
+/// A blank card per ID, to be filled in at the first scan
+fn id_cards(config: &AppConfig, ids: Vec<String>) -> Vec<Card> {
+    ids.into_iter()
+        .map(|id| Card {
+            url: config.scan_url(&id),
+            title: id,
+            subtitle: String::new(),
+            footer: String::new(),
+        })
+        .collect()
+}
+
+/// Cards for all groups, or for all reserved IDs
+async fn sheet_cards(
+    conn: &DbConn,
+    event_id: String,
+    config: &AppConfig,
+    reserved: bool,
+) -> Vec<Card> {
+    if reserved {
+        let reserved = conn
+            .run(move |c| Group::get_reserved(c, &event_id))
+            .await
+            .unwrap_or_default();
+        return id_cards(config, reserved.into_iter().map(|group| group.id).collect());
+    }
+
+    let mut groups = conn
+        .run(move |c| Group::get_all(c, &event_id))
+        .await
+        .unwrap_or_default();
+    groups.sort_by_key(|g| g.group_number);
+    groups
+        .into_iter()
+        .map(|group| Card {
+            url: config.scan_url(&group.id),
+            title: format!("{}. {}", group.group_number, group.name),
+            subtitle: if group.route.is_empty() {
+                group.scout_group
+            } else {
+                format!("{} - Route {}", group.scout_group, group.route)
+            },
+            footer: group.id,
+        })
+        .collect()
+}
+
+fn pdf_response(cards: &[Card]) -> Result<(ContentType, Vec<u8>), Status> {
+    qr_sheet::render_pdf(cards)
+        .map(|pdf| (ContentType::PDF, pdf))
+        .map_err(|err| {
+            eprintln!("Failed to render QR sheet: {err}");
+            Status::InternalServerError
+        })
+}
+
+#[get("/sheet.svg?<reserved>")]
+pub async fn qr_sheet_svg(
+    _admin: Admin,
+    conn: DbConn,
+    event: ActiveEvent,
+    config: &State<AppConfig>,
+    reserved: bool,
+) -> (ContentType, String) {
+    let cards = sheet_cards(&conn, event.id(), config, reserved).await;
+    (ContentType::SVG, qr_sheet::render_svg(&cards))
+}
+
+#[get("/sheet.pdf?<reserved>")]
+pub async fn qr_sheet_pdf(
+    _admin: Admin,
+    conn: DbConn,
+    event: ActiveEvent,
+    config: &State<AppConfig>,
+    reserved: bool,
+) -> Result<(ContentType, Vec<u8>), Status> {
+    let cards = sheet_cards(&conn, event.id(), config, reserved).await;
+    pdf_response(&cards)
+}
+
+#[derive(FromForm)]
+pub struct FreshCardsForm {
+    fresh: usize,
+}
+
+/// Blank cards for newly generated IDs. The IDs are reserved first so they can be claimed at
+/// the first scan and are never handed out twice.
+#[post("/sheet.pdf", data = "<form>")]
+pub async fn fresh_qr_sheet_pdf(
+    _admin: Admin,
+    conn: DbConn,
+    event: ActiveEvent,
+    config: &State<AppConfig>,
+    form: Form<FreshCardsForm>,
+) -> Result<(ContentType, Vec<u8>), Status> {
+    let count = form.fresh.min(MAX_FRESH_CARDS);
+    let event_id = event.id();
+    let ids = conn
+        .run(move |c| Group::reserve(c, &event_id, count))
+        .await
+        .map_err(|err| {
+            eprintln!("Failed to reserve group IDs: {err}");
+            Status::InternalServerError
+        })?;
+    pdf_response(&id_cards(config, ids))
+}
+
+#[derive(FromForm)]
+pub struct ImportUploadForm<'r> {
+    file: TempFile<'r>,
+    bind_reserved: bool,
+}
+
+#[derive(FromForm)]
+pub struct ImportConfirmForm {
+    csv: String,
+    bind_reserved: bool,
+}
+
+async fn import_preview(
+    conn: &DbConn,
+    event_id: String,
+    csv: String,
+    bind_reserved: bool,
+) -> ImportPreview {
+    let scout_groups = get_scout_groups();
+    let mut preview = import::parse_groups_csv(&csv, &scout_groups);
+    let result = conn
+        .run(move |c| {
+            import::resolve_ids(c, &event_id, &mut preview, bind_reserved).map(|_| preview)
+        })
+        .await;
+    result.unwrap_or_else(|err| ImportPreview {
+        errors: vec![format!("Databasefout: {err}")],
+        ..Default::default()
+    })
+}
+
+fn render_import(preview: &ImportPreview, csv: &str, bind_reserved: bool) -> Template {
+    Template::render(
+        "admin/groups_import",
+        context! {
+            preview: preview,
+            is_valid: preview.is_valid(),
+            csv: csv,
+            bind_reserved: bind_reserved,
+            is_admin: true,
+        },
+    )
+}
+
+#[post("/import", data = "<form>")]
+pub async fn import_upload(
+    _admin: Admin,
+    conn: DbConn,
+    event: ActiveEvent,
+    form: Form<ImportUploadForm<'_>>,
+) -> Template {
+    use rocket::tokio::io::AsyncReadExt;
+
+    let mut csv = String::new();
+    let read = match form.file.open().await {
+        Ok(mut file) => file.read_to_string(&mut csv).await.map(|_| ()),
+        Err(err) => Err(err),
+    };
+    if let Err(err) = read {
+        let preview = ImportPreview {
+            errors: vec![format!("Kan bestand niet lezen: {err}")],
+            ..Default::default()
+        };
+        return render_import(&preview, "", form.bind_reserved);
+    }
+
+    let preview = import_preview(&conn, event.id(), csv.clone(), form.bind_reserved).await;
+    render_import(&preview, &csv, form.bind_reserved)
+}
+
+#[post("/import/confirm", data = "<form>")]
+pub async fn import_confirm(
+    _admin: Admin,
+    conn: DbConn,
+    event: ActiveEvent,
+    form: Form<ImportConfirmForm>,
+) -> Result<Redirect, Template> {
+    let csv = form.csv.clone();
+    let bind_reserved = form.bind_reserved;
+    let preview = import_preview(&conn, event.id(), csv.clone(), bind_reserved).await;
+    if !preview.is_valid() {
+        return Err(render_import(&preview, &csv, bind_reserved));
+    }
+
+    let event_id = event.id();
+    if let Err(err) = conn
+        .run(move |c| import::apply(c, &event_id, preview))
+        .await
+    {
+        // Nothing was inserted, show the up-to-date preview with the error
+        let mut preview = import_preview(&conn, event.id(), csv.clone(), bind_reserved).await;
+        preview
+            .errors
+            .push(format!("Importeren mislukt, er is niets opgeslagen: {err}"));
+        return Err(render_import(&preview, &csv, bind_reserved));
+    }
+
+    Ok(Redirect::to("/admin/groups"))
+}
+
 pub fn routes() -> Vec<Route> {
-    routes![groups, new_group, delete_group, group_qr]
+    routes![
+        groups,
+        new_group,
+        delete_group,
+        group_qr,
+        qr_sheet_svg,
+        qr_sheet_pdf,
+        fresh_qr_sheet_pdf,
+        reserve_groups,
+        reserved_nfc_csv,
+        import_upload,
+        import_confirm
+    ]
 }
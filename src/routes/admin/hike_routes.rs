@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::Route;
+use rocket_dyn_templates::{context, Template};
+use serde::Serialize;
+
+use crate::auth::Admin;
+use crate::db::DbConn;
+use crate::event::ActiveEvent;
+use crate::models::{Group, HikeRoute, Post, RouteMap};
+
+#[derive(Serialize)]
+pub struct RoutePostEntry {
+    pub post: Post,
+    /// Position on the route, `None` if the post is not on it
+    pub position: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct RouteWithPosts {
+    pub route: HikeRoute,
+    pub posts: Vec<RoutePostEntry>,
+    pub group_count: usize,
+}
+
+#[get("/")]
+pub async fn routes_page(_admin: Admin, conn: DbConn, event: ActiveEvent) -> Template {
+    let event_id = event.id();
+    let route_map = conn.run(move |c| RouteMap::load(c, &event_id)).await.ok();
+    let event_id = event.id();
+    let groups = conn
+        .run(move |c| Group::get_all(c, &event_id))
+        .await
+        .unwrap_or_default();
+
+    let routes: Vec<RouteWithPosts> = route_map
+        .iter()
+        .flat_map(|map| {
+            map.routes.iter().map(|route| {
+                let route_posts = map.posts_for(Some(&route.id));
+                RouteWithPosts {
+                    route: route.clone(),
+                    posts: map
+                        .all_posts()
+                        .iter()
+                        .map(|post| {
+                            let position = route_posts.iter().position(|p| p.id == post.id);
+                            RoutePostEntry {
+                                post: post.clone(),
+                                position: position.map(|i| i + 1),
+                            }
+                        })
+                        .collect(),
+                    group_count: groups
+                        .iter()
+                        .filter(|g| g.route_id.as_ref() == Some(&route.id))
+                        .count(),
+                }
+            })
+        })
+        .collect();
+
+    Template::render("admin/routes", context! { routes: routes, is_admin: true })
+}
+
+#[derive(FromForm)]
+pub struct RouteForm {
+    name: String,
+}
+
+#[post("/", data = "<form>")]
+pub async fn create_route(
+    _admin: Admin,
+    conn: DbConn,
+    event: ActiveEvent,
+    form: Form<RouteForm>,
+) -> Redirect {
+    let name = form.name.trim().to_string();
+    let event_id = event.id();
+    if !name.is_empty() {
+        if let Err(err) = conn
+            .run(move |c| HikeRoute::insert(c, &event_id, &name))
+            .await
+        {
+            eprintln!("Failed to create route: {err}");
+        }
+    }
+    Redirect::to("/admin/routes")
+}
+
+#[post("/<id>/rename", data = "<form>")]
+pub async fn rename_route(
+    _admin: Admin,
+    conn: DbConn,
+    id: String,
+    form: Form<RouteForm>,
+) -> Redirect {
+    let name = form.name.trim().to_string();
+    if !name.is_empty() {
+        if let Err(err) = conn.run(move |c| HikeRoute::rename(c, &id, &name)).await {
+            eprintln!("Failed to rename route: {err}");
+        }
+    }
+    Redirect::to("/admin/routes")
+}
+
+#[derive(FromForm)]
+pub struct RoutePostsForm {
+    /// Position per post ID, empty for posts not on the route
+    positions: HashMap<String, String>,
+}
+
+#[post("/<id>/posts", data = "<form>")]
+pub async fn update_route_posts(
+    _admin: Admin,
+    conn: DbConn,
+    id: String,
+    form: Form<RoutePostsForm>,
+) -> Redirect {
+    let mut posts: Vec<(String, i32)> = form
+        .positions
+        .iter()
+        .filter_map(|(post_id, position)| {
+            let position = position.trim().parse::<i32>().ok()?;
+            Some((post_id.clone(), position))
+        })
+        .collect();
+    // Renumber, so positions are consecutive whatever was entered
+    posts.sort_by_key(|(_, position)| *position);
+    let posts = posts
+        .into_iter()
+        .enumerate()
+        .map(|(i, (post_id, _))| (post_id, i as i32 + 1))
+        .collect();
+
+    if let Err(err) = conn.run(move |c| HikeRoute::set_posts(c, &id, posts)).await {
+        eprintln!("Failed to update route posts: {err}");
+    }
+    Redirect::to("/admin/routes")
+}
+
+#[post("/<id>/delete")]
+pub async fn delete_route(_admin: Admin, conn: DbConn, id: String) -> Redirect {
+    conn.run(move |c| HikeRoute::delete(c, &id)).await.ok();
+    Redirect::to("/admin/routes")
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        routes_page,
+        create_route,
+        rename_route,
+        update_route_posts,
+        delete_route
+    ]
+}
@@ -1,2 +1,8 @@
+pub mod audit;
+pub mod events;
 pub mod groups;
+pub mod hike_routes;
 pub mod posts;
+pub mod scans;
+pub mod tokens;
+pub mod trash;
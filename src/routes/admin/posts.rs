@@ -1,3 +1,4 @@
+use chrono::NaiveDateTime;
 use rocket::form::Form;
 use rocket::response::Redirect;
 use rocket::Route;
@@ -6,7 +7,8 @@ use serde::Serialize;
 
 use crate::auth::Admin;
 use crate::db::DbConn;
-use crate::models::{Group, NewPost, Post, Scan};
+use crate::event::ActiveEvent;
+use crate::models::{Group, NewPost, Post, PostCriterion, Scan};
 
 #[derive(FromForm)]
 pub struct NewPostForm {
@@ -20,12 +22,21 @@ pub struct PostWithStats {
     pub arrived_count: usize,
     pub total_groups: usize,
     pub has_password: bool,
+    pub criteria: Vec<PostCriterion>,
 }
 
 #[get("/")]
-pub async fn posts(_admin: Admin, conn: DbConn) -> Template {
-    let posts = conn.run(Post::get_all).await.unwrap_or_default();
-    let groups = conn.run(Group::get_all).await.unwrap_or_default();
+pub async fn posts(_admin: Admin, conn: DbConn, event: ActiveEvent) -> Template {
+    let event_id = event.id();
+    let posts = conn
+        .run(move |c| Post::get_all(c, &event_id))
+        .await
+        .unwrap_or_default();
+    let event_id = event.id();
+    let groups = conn
+        .run(move |c| Group::get_all(c, &event_id))
+        .await
+        .unwrap_or_default();
     let total_groups = groups.len();
 
     let mut posts_with_stats = Vec::new();
@@ -37,12 +48,18 @@ pub async fn posts(_admin: Admin, conn: DbConn) -> Template {
             .unwrap_or_default();
         let arrived_count = scans.len();
         let has_password = post.password_hash.is_some();
+        let post_id = post.id.clone();
+        let criteria = conn
+            .run(move |c| PostCriterion::get_by_post(c, &post_id))
+            .await
+            .unwrap_or_default();
 
         posts_with_stats.push(PostWithStats {
             post,
             arrived_count,
             total_groups,
             has_password,
+            criteria,
         });
     }
 
@@ -53,12 +70,18 @@ pub async fn posts(_admin: Admin, conn: DbConn) -> Template {
 }
 
 #[post("/", data = "<form>")]
-pub async fn create_post(_admin: Admin, conn: DbConn, form: Form<NewPostForm>) -> Redirect {
+pub async fn create_post(
+    _admin: Admin,
+    conn: DbConn,
+    event: ActiveEvent,
+    form: Form<NewPostForm>,
+) -> Redirect {
     let name = form.name.clone();
     let order = form.order;
+    let event_id = event.id();
     let result = conn
         .run(move |c| {
-            let post = NewPost::new(name, order);
+            let post = NewPost::new(event_id, name, order);
             Post::insert(c, post)
         })
         .await;
@@ -69,7 +92,7 @@ pub async fn create_post(_admin: Admin, conn: DbConn, form: Form<NewPostForm>) -
     Redirect::to("/admin/posts")
 }
 
-#[get("/<id>/delete")]
+#[post("/<id>/delete")]
 pub async fn delete_post(_admin: Admin, conn: DbConn, id: String) -> Redirect {
     conn.run(move |c| Post::delete(c, &id)).await.ok();
     Redirect::to("/admin/posts")
@@ -105,12 +128,101 @@ pub async fn clear_password(_admin: Admin, conn: DbConn, id: String) -> Redirect
     Redirect::to("/admin/posts")
 }
 
+#[derive(FromForm)]
+pub struct MaxPointsForm {
+    /// Empty to not score the post as a whole
+    max_points: Option<i32>,
+}
+
+#[post("/<id>/points", data = "<form>")]
+pub async fn set_max_points(
+    _admin: Admin,
+    conn: DbConn,
+    id: String,
+    form: Form<MaxPointsForm>,
+) -> Redirect {
+    let max_points = form.max_points.filter(|p| *p > 0);
+    if let Err(err) = conn
+        .run(move |c| Post::set_max_points(c, &id, max_points))
+        .await
+    {
+        eprintln!("Failed to set max points: {err}");
+    }
+    Redirect::to("/admin/posts")
+}
+
+#[derive(FromForm)]
+pub struct HoursForm {
+    /// Empty when the post is open from the start
+    opens_at: Option<String>,
+    closes_at: Option<String>,
+    /// Empty to use the closing time
+    cutoff_at: Option<String>,
+    /// Minutes of penalty for arriving outside the opening hours, empty to only warn
+    hours_penalty: Option<i32>,
+}
+
+#[post("/<id>/hours", data = "<form>")]
+pub async fn set_hours(_admin: Admin, conn: DbConn, id: String, form: Form<HoursForm>) -> Redirect {
+    let parse = |time: &Option<String>| {
+        time.as_ref()
+            .and_then(|dt| NaiveDateTime::parse_from_str(dt, "%Y-%m-%dT%H:%M").ok())
+    };
+    let opens_at = parse(&form.opens_at);
+    let closes_at = parse(&form.closes_at);
+    let cutoff_at = parse(&form.cutoff_at);
+    let hours_penalty = form.hours_penalty.filter(|m| *m > 0).map(|m| m * 60);
+    if let Err(err) = conn
+        .run(move |c| Post::set_hours(c, &id, opens_at, closes_at, cutoff_at, hours_penalty))
+        .await
+    {
+        eprintln!("Failed to set post hours: {err}");
+    }
+    Redirect::to("/admin/posts")
+}
+
+#[derive(FromForm)]
+pub struct CriterionForm {
+    name: String,
+    max_points: i32,
+}
+
+#[post("/<id>/criteria", data = "<form>")]
+pub async fn add_criterion(
+    _admin: Admin,
+    conn: DbConn,
+    id: String,
+    form: Form<CriterionForm>,
+) -> Redirect {
+    let name = form.name.trim().to_string();
+    let max_points = form.max_points;
+    if !name.is_empty() && max_points > 0 {
+        if let Err(err) = conn
+            .run(move |c| PostCriterion::insert(c, &id, &name, max_points))
+            .await
+        {
+            eprintln!("Failed to add criterion: {err}");
+        }
+    }
+    Redirect::to("/admin/posts")
+}
+
+#[post("/criteria/<id>/delete")]
+pub async fn delete_criterion(_admin: Admin, conn: DbConn, id: String) -> Redirect {
+    conn.run(move |c| PostCriterion::delete(c, &id)).await.ok();
+    Redirect::to("/admin/posts")
+}
+
 pub fn routes() -> Vec<Route> {
     routes![
         posts,
         create_post,
         delete_post,
         set_password,
-        clear_password
+        clear_password,
+        set_max_points,
+        set_hours,
+        add_criterion,
+        delete_criterion
     ]
 }
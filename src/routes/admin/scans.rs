@@ -0,0 +1,79 @@
+use rocket::response::Redirect;
+use rocket::{Route, State};
+use rocket_dyn_templates::{context, Template};
+use serde::Serialize;
+
+use crate::auth::Admin;
+use crate::db::DbConn;
+use crate::live::LiveUpdates;
+use crate::models::{DuplicateScan, Group, Post, Scan};
+
+#[derive(Serialize)]
+pub struct DuplicateWithScan {
+    pub duplicate: DuplicateScan,
+    /// Scan that was kept for the same group and post
+    pub kept: Option<Scan>,
+    pub group: Option<Group>,
+    pub post: Option<Post>,
+}
+
+/// Scans that were recorded more than once for the same group and post, before scans were
+/// made unique
+#[get("/duplicates")]
+pub async fn duplicates(_admin: Admin, conn: DbConn) -> Template {
+    let duplicates = conn
+        .run(|c| {
+            DuplicateScan::get_all(c)?
+                .into_iter()
+                .map(|duplicate| {
+                    Ok(DuplicateWithScan {
+                        kept: Scan::get_by_id(c, &duplicate.kept_scan_id)?,
+                        group: Group::get_by_id(c, &duplicate.group_id)?,
+                        post: Post::get_by_id(c, &duplicate.post_id)?,
+                        duplicate,
+                    })
+                })
+                .collect::<diesel::QueryResult<Vec<_>>>()
+        })
+        .await
+        .unwrap_or_default();
+
+    Template::render(
+        "admin/duplicates",
+        context! { duplicates: duplicates, is_admin: true },
+    )
+}
+
+/// Use the times of the duplicate instead of the kept scan
+#[post("/duplicates/<id>/keep")]
+pub async fn keep_duplicate(
+    admin: Admin,
+    conn: DbConn,
+    live: &State<LiveUpdates>,
+    id: String,
+) -> Redirect {
+    let actor = admin.audit_name();
+    let result = conn
+        .run(move |c| {
+            let duplicate = DuplicateScan::get_by_id(c, &id)?;
+            DuplicateScan::keep(c, &id, &actor)?;
+            Ok::<_, diesel::result::Error>(duplicate)
+        })
+        .await;
+    match result {
+        Ok(Some(duplicate)) => live.scan(&duplicate.group_id, Some(&duplicate.post_id)),
+        Ok(None) => {}
+        Err(err) => eprintln!("Failed to keep duplicate scan: {err}"),
+    }
+    Redirect::to("/admin/scans/duplicates")
+}
+
+#[post("/duplicates/<id>/delete")]
+pub async fn delete_duplicate(_admin: Admin, conn: DbConn, id: String) -> Redirect {
+    conn.run(move |c| DuplicateScan::delete(c, &id)).await.ok();
+    Redirect::to("/admin/scans/duplicates")
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![duplicates, keep_duplicate, delete_duplicate]
+}
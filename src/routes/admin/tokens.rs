@@ -0,0 +1,90 @@
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::Route;
+use rocket_dyn_templates::{context, Template};
+use serde::Serialize;
+
+use crate::auth::Admin;
+use crate::db::DbConn;
+use crate::event::ActiveEvent;
+use crate::models::{ApiToken, Post};
+
+#[derive(FromForm)]
+pub struct NewTokenForm {
+    name: String,
+    /// Empty for an admin token
+    post_id: String,
+}
+
+#[derive(Serialize)]
+pub struct TokenWithPost {
+    pub token: ApiToken,
+    pub post_name: Option<String>,
+}
+
+async fn render_tokens(conn: &DbConn, event: ActiveEvent, new_token: Option<String>) -> Template {
+    let tokens = conn.run(ApiToken::get_all).await.unwrap_or_default();
+    let event_id = event.id();
+    let posts = conn
+        .run(move |c| Post::get_all(c, &event_id))
+        .await
+        .unwrap_or_default();
+
+    let tokens: Vec<TokenWithPost> = tokens
+        .into_iter()
+        .map(|token| {
+            let post_name =
+                token
+                    .post_id
+                    .as_ref()
+                    .map(|id| match posts.iter().find(|p| &p.id == id) {
+                        Some(post) => post.name.clone(),
+                        None => id.clone(),
+                    });
+            TokenWithPost { token, post_name }
+        })
+        .collect();
+
+    Template::render(
+        "admin/tokens",
+        context! { tokens: tokens, posts: posts, new_token: new_token, is_admin: true },
+    )
+}
+
+#[get("/")]
+pub async fn tokens(_admin: Admin, conn: DbConn, event: ActiveEvent) -> Template {
+    render_tokens(&conn, event, None).await
+}
+
+/// Create a token and show it once, only its hash is stored
+#[post("/", data = "<form>")]
+pub async fn create_token(
+    _admin: Admin,
+    conn: DbConn,
+    event: ActiveEvent,
+    form: Form<NewTokenForm>,
+) -> Result<Template, Redirect> {
+    let name = form.name.trim().to_string();
+    let post_id = Some(form.post_id.clone()).filter(|id| !id.is_empty());
+    if name.is_empty() {
+        return Err(Redirect::to("/admin/tokens"));
+    }
+
+    match conn.run(move |c| ApiToken::create(c, name, post_id)).await {
+        Ok(token) => Ok(render_tokens(&conn, event, Some(token)).await),
+        Err(err) => {
+            eprintln!("Failed to create API token: {err}");
+            Err(Redirect::to("/admin/tokens"))
+        }
+    }
+}
+
+#[post("/<id>/revoke")]
+pub async fn revoke_token(_admin: Admin, conn: DbConn, id: String) -> Redirect {
+    conn.run(move |c| ApiToken::revoke(c, &id)).await.ok();
+    Redirect::to("/admin/tokens")
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![tokens, create_token, revoke_token]
+}
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use diesel::Connection;
+use rocket::form::Form;
+use rocket::request::FlashMessage;
+use rocket::response::{Flash, Redirect};
+use rocket::{Route, State};
+use rocket_dyn_templates::{context, Template};
+use serde::Serialize;
+
+use crate::actions;
+use crate::auth::Admin;
+use crate::db::DbConn;
+use crate::event::ActiveEvent;
+use crate::live::LiveUpdates;
+use crate::models::{AuditEntry, AuditTimes, Group, Post, Scan, AUDIT_RESTORE_SCAN};
+
+#[derive(Serialize)]
+pub struct DeletedScan {
+    pub scan: Scan,
+    pub group: Option<Group>,
+    pub post: Option<Post>,
+}
+
+#[derive(FromForm)]
+pub struct PurgeForm {
+    /// Checked to confirm the item is gone for good
+    confirm: bool,
+}
+
+/// Deleted groups, posts and scans of the active event, to restore or purge
+#[get("/")]
+pub async fn trash(
+    _admin: Admin,
+    conn: DbConn,
+    event: ActiveEvent,
+    flash: Option<FlashMessage<'_>>,
+) -> Template {
+    let event_id = event.id();
+    let (groups, posts, scans) = conn
+        .run(move |c| {
+            let groups = Group::get_deleted(c, &event_id)?;
+            let posts = Post::get_deleted(c, &event_id)?;
+            // Deleted scans may belong to groups and posts in the trash as well
+            let all_groups: HashMap<String, Group> = Group::get_all(c, &event_id)?
+                .into_iter()
+                .chain(groups.iter().cloned())
+                .map(|g| (g.id.clone(), g))
+                .collect();
+            let all_posts: HashMap<String, Post> = Post::get_all(c, &event_id)?
+                .into_iter()
+                .chain(posts.iter().cloned())
+                .map(|p| (p.id.clone(), p))
+                .collect();
+            let scans: Vec<DeletedScan> = Scan::get_deleted(c, &event_id)?
+                .into_iter()
+                .map(|scan| DeletedScan {
+                    group: all_groups.get(&scan.group_id).cloned(),
+                    post: all_posts.get(&scan.post_id).cloned(),
+                    scan,
+                })
+                .collect();
+            Ok::<_, diesel::result::Error>((groups, posts, scans))
+        })
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to load trash: {err}");
+            Default::default()
+        });
+
+    Template::render(
+        "admin/trash",
+        context! {
+            groups: groups,
+            posts: posts,
+            scans: scans,
+            error: flash.map(|f| f.message().to_string()),
+            is_admin: true,
+        },
+    )
+}
+
+/// A deleted group of the active event
+async fn deleted_group(conn: &DbConn, event: &ActiveEvent, id: &str) -> Option<Group> {
+    let id = id.to_string();
+    conn.run(move |c| Group::get_deleted_by_id(c, &id))
+        .await
+        .ok()
+        .flatten()
+        .filter(|g| g.event_id == event.0.id)
+}
+
+/// A deleted post of the active event
+async fn deleted_post(conn: &DbConn, event: &ActiveEvent, id: &str) -> Option<Post> {
+    let id = id.to_string();
+    conn.run(move |c| Post::get_deleted_by_id(c, &id))
+        .await
+        .ok()
+        .flatten()
+        .filter(|p| p.event_id == event.0.id)
+}
+
+/// A deleted scan of a group of the active event, with the group, which may be deleted too
+async fn deleted_scan(conn: &DbConn, event: &ActiveEvent, id: &str) -> Option<(Group, Scan)> {
+    let id = id.to_string();
+    conn.run(move |c| {
+        let Some(scan) = Scan::get_deleted_by_id(c, &id)? else {
+            return Ok(None);
+        };
+        let group = match Group::get_by_id(c, &scan.group_id)? {
+            Some(group) => Some(group),
+            None => Group::get_deleted_by_id(c, &scan.group_id)?,
+        };
+        Ok::<_, diesel::result::Error>(group.map(|group| (group, scan)))
+    })
+    .await
+    .ok()
+    .flatten()
+    .filter(|(group, _)| group.event_id == event.0.id)
+}
+
+fn purge_unconfirmed() -> Flash<Redirect> {
+    Flash::error(
+        Redirect::to("/admin/trash"),
+        "Vink aan dat je het definitief wilt verwijderen",
+    )
+}
+
+#[post("/groups/<id>/restore")]
+pub async fn restore_group(
+    _admin: Admin,
+    conn: DbConn,
+    event: ActiveEvent,
+    live: &State<LiveUpdates>,
+    id: String,
+) -> Redirect {
+    if deleted_group(&conn, &event, &id).await.is_some() {
+        let gid = id.clone();
+        if let Err(err) = conn.run(move |c| Group::restore(c, &gid)).await {
+            eprintln!("Failed to restore group: {err}");
+        }
+        live.group(&id);
+    }
+    Redirect::to("/admin/trash")
+}
+
+#[post("/groups/<id>/purge", data = "<form>")]
+pub async fn purge_group(
+    _admin: Admin,
+    conn: DbConn,
+    event: ActiveEvent,
+    id: String,
+    form: Form<PurgeForm>,
+) -> Result<Redirect, Flash<Redirect>> {
+    if !form.confirm {
+        return Err(purge_unconfirmed());
+    }
+    if deleted_group(&conn, &event, &id).await.is_some() {
+        if let Err(err) = conn.run(move |c| Group::purge(c, &id)).await {
+            eprintln!("Failed to purge group: {err}");
+        }
+    }
+    Ok(Redirect::to("/admin/trash"))
+}
+
+#[post("/posts/<id>/restore")]
+pub async fn restore_post(_admin: Admin, conn: DbConn, event: ActiveEvent, id: String) -> Redirect {
+    if deleted_post(&conn, &event, &id).await.is_some() {
+        if let Err(err) = conn.run(move |c| Post::restore(c, &id)).await {
+            eprintln!("Failed to restore post: {err}");
+        }
+    }
+    Redirect::to("/admin/trash")
+}
+
+#[post("/posts/<id>/purge", data = "<form>")]
+pub async fn purge_post(
+    _admin: Admin,
+    conn: DbConn,
+    event: ActiveEvent,
+    id: String,
+    form: Form<PurgeForm>,
+) -> Result<Redirect, Flash<Redirect>> {
+    if !form.confirm {
+        return Err(purge_unconfirmed());
+    }
+    if deleted_post(&conn, &event, &id).await.is_some() {
+        if let Err(err) = conn.run(move |c| Post::purge(c, &id)).await {
+            eprintln!("Failed to purge post: {err}");
+        }
+    }
+    Ok(Redirect::to("/admin/trash"))
+}
+
+#[post("/scans/<id>/restore")]
+pub async fn restore_scan(
+    admin: Admin,
+    conn: DbConn,
+    event: ActiveEvent,
+    live: &State<LiveUpdates>,
+    id: String,
+) -> Result<Redirect, Flash<Redirect>> {
+    let Some((group, scan)) = deleted_scan(&conn, &event, &id).await else {
+        return Ok(Redirect::to("/admin/trash"));
+    };
+
+    let (group_id, post_id) = (group.id.clone(), scan.post_id.clone());
+    let actor = admin.audit_name();
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                Scan::restore(c, &scan.id)?;
+                actions::penalize_outside_hours(
+                    c,
+                    &group,
+                    &scan.post_id,
+                    &scan.id,
+                    scan.arrival_time,
+                )?;
+                AuditEntry::record(
+                    c,
+                    &group,
+                    Some(&scan.post_id),
+                    &actor,
+                    AUDIT_RESTORE_SCAN,
+                    None,
+                    Some(AuditTimes::of_scan(&scan)),
+                )
+            })
+        })
+        .await;
+    match result {
+        Ok(()) => {
+            live.scan(&group_id, Some(&post_id));
+            Ok(Redirect::to("/admin/trash"))
+        }
+        Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            Err(Flash::error(
+                Redirect::to("/admin/trash"),
+                "De groep heeft inmiddels een nieuwe scan bij deze post",
+            ))
+        }
+        Err(err) => {
+            eprintln!("Failed to restore scan: {err}");
+            Ok(Redirect::to("/admin/trash"))
+        }
+    }
+}
+
+#[post("/scans/<id>/purge", data = "<form>")]
+pub async fn purge_scan(
+    _admin: Admin,
+    conn: DbConn,
+    event: ActiveEvent,
+    id: String,
+    form: Form<PurgeForm>,
+) -> Result<Redirect, Flash<Redirect>> {
+    if !form.confirm {
+        return Err(purge_unconfirmed());
+    }
+    if deleted_scan(&conn, &event, &id).await.is_some() {
+        if let Err(err) = conn.run(move |c| Scan::purge(c, &id)).await {
+            eprintln!("Failed to purge scan: {err}");
+        }
+    }
+    Ok(Redirect::to("/admin/trash"))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        trash,
+        restore_group,
+        purge_group,
+        restore_post,
+        purge_post,
+        restore_scan,
+        purge_scan
+    ]
+}
@@ -0,0 +1,18 @@
+pub mod v1;
+
+use rocket::http::Status;
+use rocket::serde::json::{json, Value};
+use rocket::{Catcher, Request};
+
+/// Errors of the JSON API are JSON as well, instead of the default HTML error pages
+#[catch(default)]
+fn api_error(status: Status, _req: &Request) -> Value {
+    json!({
+        "error": status.reason().unwrap_or("Unknown error"),
+        "status": status.code,
+    })
+}
+
+pub fn catchers() -> Vec<Catcher> {
+    catchers![api_error]
+}
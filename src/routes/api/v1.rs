@@ -0,0 +1,282 @@
+use chrono::Utc;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::Json;
+use rocket::{Route, State};
+use serde::Deserialize;
+
+use crate::actions::{self, ActionError, ActionOutcome, Actor, ScanAction};
+
+use crate::auth::ApiAuth;
+use crate::config::AppConfig;
+use crate::db::DbConn;
+use crate::event::{viewed_event, ActiveEvent};
+use crate::live::LiveUpdates;
+use crate::models::{Adjustment, Event, Group, Post, RouteMap, Scan};
+use crate::routes::ranking::{rank_groups, RankedGroup};
+use crate::stats::{calculate_group_stats, GroupStats};
+
+type ApiResult<T> = Result<Json<T>, Status>;
+
+fn db_error(err: diesel::result::Error) -> Status {
+    eprintln!("API database error: {err}");
+    Status::InternalServerError
+}
+
+/// Look up a group, reserved IDs without details are not exposed
+async fn find_group(conn: &DbConn, id: String) -> Result<Group, Status> {
+    conn.run(move |c| Group::get_by_id(c, &id))
+        .await
+        .map_err(db_error)?
+        .filter(|g| !g.reserved)
+        .ok_or(Status::NotFound)
+}
+
+/// Event asked for by `?event=`, or the active event
+async fn find_event(
+    conn: &DbConn,
+    active: ActiveEvent,
+    event_id: Option<String>,
+) -> Result<Event, Status> {
+    viewed_event(conn, active, event_id)
+        .await
+        .ok_or(Status::NotFound)
+}
+
+#[get("/events")]
+pub async fn events(_auth: ApiAuth, conn: DbConn) -> ApiResult<Vec<Event>> {
+    conn.run(Event::get_all).await.map(Json).map_err(db_error)
+}
+
+#[get("/groups?<event>")]
+pub async fn groups(
+    _auth: ApiAuth,
+    conn: DbConn,
+    active: ActiveEvent,
+    event: Option<String>,
+) -> ApiResult<Vec<Group>> {
+    let event = find_event(&conn, active, event).await?;
+    conn.run(move |c| Group::get_all(c, &event.id))
+        .await
+        .map(Json)
+        .map_err(db_error)
+}
+
+#[get("/groups/<id>")]
+pub async fn group(_auth: ApiAuth, conn: DbConn, id: String) -> ApiResult<Group> {
+    find_group(&conn, id).await.map(Json)
+}
+
+#[get("/groups/<id>/scans")]
+pub async fn group_scans(_auth: ApiAuth, conn: DbConn, id: String) -> ApiResult<Vec<Scan>> {
+    let group = find_group(&conn, id).await?;
+    conn.run(move |c| Scan::get_by_group(c, &group.id))
+        .await
+        .map(Json)
+        .map_err(db_error)
+}
+
+#[get("/groups/<id>/stats")]
+pub async fn group_stats(_auth: ApiAuth, conn: DbConn, id: String) -> ApiResult<GroupStats> {
+    let group = find_group(&conn, id).await?;
+    conn.run(move |c| {
+        let posts = RouteMap::load(c, &group.event_id)?.posts_for(group.route_id.as_deref());
+        let scans = Scan::get_by_group(c, &group.id)?;
+        let adjustments = Adjustment::get_by_group(c, &group.id)?;
+        Ok(calculate_group_stats(&group, &scans, posts, &adjustments))
+    })
+    .await
+    .map(Json)
+    .map_err(db_error)
+}
+
+#[get("/posts?<event>")]
+pub async fn posts(
+    _auth: ApiAuth,
+    conn: DbConn,
+    active: ActiveEvent,
+    event: Option<String>,
+) -> ApiResult<Vec<Post>> {
+    let event = find_event(&conn, active, event).await?;
+    conn.run(move |c| Post::get_all(c, &event.id))
+        .await
+        .map(Json)
+        .map_err(db_error)
+}
+
+#[get("/posts/<id>")]
+pub async fn post(_auth: ApiAuth, conn: DbConn, id: String) -> ApiResult<Post> {
+    conn.run(move |c| Post::get_by_id(c, &id))
+        .await
+        .map_err(db_error)?
+        .map(Json)
+        .ok_or(Status::NotFound)
+}
+
+#[get("/posts/<id>/scans")]
+pub async fn post_scans(_auth: ApiAuth, conn: DbConn, id: String) -> ApiResult<Vec<Scan>> {
+    conn.run(move |c| {
+        if Post::get_by_id(c, &id)?.is_none() {
+            return Ok(None);
+        }
+        Scan::get_by_post(c, &id).map(Some)
+    })
+    .await
+    .map_err(db_error)?
+    .map(Json)
+    .ok_or(Status::NotFound)
+}
+
+/// Ranking of finished groups, admin only like the ranking page
+#[get("/ranking?<sort>&<event>")]
+pub async fn ranking(
+    auth: ApiAuth,
+    conn: DbConn,
+    config: &State<AppConfig>,
+    active: ActiveEvent,
+    sort: Option<String>,
+    event: Option<String>,
+) -> ApiResult<Vec<RankedGroup>> {
+    if !auth.is_admin {
+        return Err(Status::Forbidden);
+    }
+    let event = find_event(&conn, active, event).await?;
+    let sort = sort.unwrap_or_else(|| "rules".to_string());
+    let seconds_per_point = config.seconds_per_point;
+    conn.run(move |c| rank_groups(c, &event, &sort, seconds_per_point))
+        .await
+        .map(Json)
+        .map_err(db_error)
+}
+
+/// Optional `Idempotency-Key` header, a repeated request with the same key has no further effect
+pub struct IdempotencyKey(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IdempotencyKey {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, ()> {
+        let key = request
+            .headers()
+            .get_one("Idempotency-Key")
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty());
+        Outcome::Success(IdempotencyKey(key))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ScanRequest {
+    /// Defaults to the post of a post holder token
+    post_id: Option<String>,
+}
+
+async fn perform(
+    auth: ApiAuth,
+    conn: DbConn,
+    live: &LiveUpdates,
+    key: IdempotencyKey,
+    group_id: String,
+    action: ScanAction,
+) -> ApiResult<ActionOutcome> {
+    let actor = Actor {
+        is_admin: auth.is_admin,
+        post_id: auth.post_id,
+        admin_session: auth.admin_session,
+    };
+    let now = Utc::now().naive_utc();
+    let performed = action.clone();
+    let outcome = conn
+        .run(move |c| {
+            actions::perform_once(c, &actor, key.0.as_deref(), &group_id, &performed, now)
+        })
+        .await;
+    match &outcome {
+        Ok(outcome) if !outcome.replayed => live.action(&outcome.group.id, &action),
+        _ => {}
+    }
+    outcome.map(Json).map_err(|err| match err {
+        ActionError::GroupNotFound | ActionError::PostNotFound => Status::NotFound,
+        ActionError::Forbidden => Status::Forbidden,
+        ActionError::Conflict(_) | ActionError::OutOfOrder(_) | ActionError::EventClosed => {
+            Status::Conflict
+        }
+        ActionError::Database(err) => db_error(err),
+    })
+}
+
+fn scan_post_id(auth: &ApiAuth, request: Option<Json<ScanRequest>>) -> Result<String, Status> {
+    request
+        .and_then(|r| r.into_inner().post_id)
+        .or_else(|| auth.post_id.clone())
+        .ok_or(Status::UnprocessableEntity)
+}
+
+/// Start the timer of a group, admin only
+#[post("/groups/<id>/start")]
+pub async fn start(
+    auth: ApiAuth,
+    conn: DbConn,
+    live: &State<LiveUpdates>,
+    key: IdempotencyKey,
+    id: String,
+) -> ApiResult<ActionOutcome> {
+    perform(auth, conn, live, key, id, ScanAction::StartTimer).await
+}
+
+/// Stop the timer of a group, admin only
+#[post("/groups/<id>/finish")]
+pub async fn finish(
+    auth: ApiAuth,
+    conn: DbConn,
+    live: &State<LiveUpdates>,
+    key: IdempotencyKey,
+    id: String,
+) -> ApiResult<ActionOutcome> {
+    perform(auth, conn, live, key, id, ScanAction::StopTimer).await
+}
+
+#[post("/groups/<id>/arrive", data = "<request>")]
+pub async fn arrive(
+    auth: ApiAuth,
+    conn: DbConn,
+    live: &State<LiveUpdates>,
+    key: IdempotencyKey,
+    id: String,
+    request: Option<Json<ScanRequest>>,
+) -> ApiResult<ActionOutcome> {
+    let post_id = scan_post_id(&auth, request)?;
+    perform(auth, conn, live, key, id, ScanAction::Arrive(post_id)).await
+}
+
+#[post("/groups/<id>/leave", data = "<request>")]
+pub async fn leave(
+    auth: ApiAuth,
+    conn: DbConn,
+    live: &State<LiveUpdates>,
+    key: IdempotencyKey,
+    id: String,
+    request: Option<Json<ScanRequest>>,
+) -> ApiResult<ActionOutcome> {
+    let post_id = scan_post_id(&auth, request)?;
+    perform(auth, conn, live, key, id, ScanAction::Leave(post_id)).await
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        events,
+        groups,
+        group,
+        group_scans,
+        group_stats,
+        posts,
+        post,
+        post_scans,
+        ranking,
+        start,
+        finish,
+        arrive,
+        leave
+    ]
+}
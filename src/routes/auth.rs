@@ -6,7 +6,7 @@ use rocket_dyn_templates::{context, Template};
 
 use crate::auth::{self, Admin, AuthSession};
 use crate::db::DbConn;
-use crate::models::Post;
+use crate::models::{Event, Post};
 
 #[derive(FromForm)]
 pub struct LoginForm {
@@ -54,9 +54,12 @@ pub async fn login(
         return Ok(Redirect::to(next));
     }
 
-    // Then, check if it matches any post's password
+    // Then, check if it matches the password of any post of the active event
     let post = conn
-        .run(move |c| Post::find_by_password(c, &password))
+        .run(move |c| {
+            let event = Event::get_active(c)?;
+            Post::find_by_password(c, &event.id, &password)
+        })
         .await
         .ok()
         .flatten();
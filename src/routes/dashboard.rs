@@ -1,34 +1,85 @@
 use chrono::TimeDelta;
 use rocket::http::CookieJar;
-use rocket::Route;
+use rocket::{Route, State};
 use rocket_dyn_templates::{context, Template};
 use serde::Serialize;
 
 use crate::auth::get_auth_context;
+use crate::config::AppConfig;
 use crate::db::DbConn;
-use crate::models::{Group, Post, Scan};
-use crate::stats::{calculate_group_stats, PostScanInfo};
+use crate::eta::{load_etas, Eta};
+use crate::event::{viewed_event, ActiveEvent};
+use crate::models::{group_status_label, Group, HikeRoute, Post, RouteMap, Scan};
+use crate::stats::{calculate_group_stats, secs, PostScanInfo};
 
 #[derive(Serialize)]
 pub struct GroupDetail {
     pub group: Group,
     pub post_scans: Vec<PostScanInfo>,
+    #[serde(with = "secs::option")]
     pub total_time: Option<TimeDelta>,
+    #[serde(with = "secs")]
     pub idle_time: TimeDelta,
+    #[serde(with = "secs::option")]
     pub walking_time: Option<TimeDelta>,
+    pub status: String,
+    pub status_label: &'static str,
 }
 
-#[get("/")]
-pub async fn dashboard(cookies: &CookieJar<'_>, conn: DbConn) -> Template {
+/// Groups on the same route, with a column per post on that route
+#[derive(Serialize)]
+pub struct RouteTable {
+    /// `None` for groups without a defined route
+    pub route: Option<HikeRoute>,
+    pub posts: Vec<Post>,
+    pub group_stats: Vec<GroupDetail>,
+}
+
+#[get("/?<event>")]
+pub async fn dashboard(
+    cookies: &CookieJar<'_>,
+    conn: DbConn,
+    config: &State<AppConfig>,
+    active: ActiveEvent,
+    event: Option<String>,
+) -> Option<Template> {
+    let event = viewed_event(&conn, active, event).await?;
     let auth_ctx = get_auth_context(cookies);
     let is_admin = auth_ctx.is_admin;
     let is_post_holder = auth_ctx.is_post_holder;
     let holder_post_id = auth_ctx.holder_post_id;
 
-    let groups = conn.run(Group::get_all).await.unwrap_or_default();
-    let posts = conn.run(Post::get_all).await.unwrap_or_default();
+    let event_id = event.id.clone();
+    let groups = conn
+        .run(move |c| Group::get_all(c, &event_id))
+        .await
+        .unwrap_or_default();
+    let event_id = event.id.clone();
+    let route_map = match conn.run(move |c| RouteMap::load(c, &event_id)).await {
+        Ok(route_map) => route_map,
+        Err(err) => {
+            eprintln!("Failed to load routes: {err}");
+            return Some(Template::render(
+                "dashboard",
+                context! { tables: Vec::<RouteTable>::new(), event: event },
+            ));
+        }
+    };
 
-    let mut group_stats: Vec<GroupDetail> = Vec::new();
+    let mut tables: Vec<RouteTable> = route_map
+        .routes
+        .iter()
+        .map(|route| RouteTable {
+            route: Some(route.clone()),
+            posts: route_map.posts_for(Some(&route.id)),
+            group_stats: Vec::new(),
+        })
+        .chain(std::iter::once(RouteTable {
+            route: None,
+            posts: route_map.all_posts().to_vec(),
+            group_stats: Vec::new(),
+        }))
+        .collect();
 
     for group in groups {
         let gid = group.id.clone();
@@ -36,8 +87,20 @@ pub async fn dashboard(cookies: &CookieJar<'_>, conn: DbConn) -> Template {
             .run(move |c| Scan::get_by_group(c, &gid))
             .await
             .unwrap_or_default();
-        let stats = calculate_group_stats(&group, &scans, posts.clone());
-        group_stats.push(GroupDetail {
+        let table = match route_map.route(group.route_id.as_deref()) {
+            Some(route) => tables
+                .iter_mut()
+                .find(|t| t.route.as_ref().is_some_and(|r| r.id == route.id)),
+            None => tables.last_mut(),
+        }
+        .expect("Every group has a table");
+
+        // The overview shows raw times, adjustments only count in the ranking
+        let stats = calculate_group_stats(&group, &scans, table.posts.clone(), &[]);
+        let status = group.status().to_string();
+        table.group_stats.push(GroupDetail {
+            status_label: group_status_label(&status),
+            status,
             group,
             post_scans: stats.post_scans,
             total_time: stats.total_time,
@@ -45,17 +108,36 @@ pub async fn dashboard(cookies: &CookieJar<'_>, conn: DbConn) -> Template {
             walking_time: stats.walking_time,
         });
     }
+    tables.retain(|t| !t.group_stats.is_empty());
+
+    // Phone numbers are only for the organisation
+    let mut overdue: Vec<Eta> = Vec::new();
+    if is_admin || is_post_holder {
+        let event_id = event.id.clone();
+        let margin = config.overdue_margin;
+        overdue = conn
+            .run(move |c| load_etas(c, &event_id, margin))
+            .await
+            .unwrap_or_else(|err| {
+                eprintln!("Failed to predict arrivals: {err}");
+                Vec::new()
+            });
+        overdue.retain(|eta| eta.overdue);
+    }
+    let emergency_info = std::env::var("EMERGENCY_INFO").ok();
 
-    Template::render(
+    Some(Template::render(
         "dashboard",
         context! {
-            group_stats: group_stats,
-            posts: posts,
+            tables: tables,
+            event: event,
+            overdue: overdue,
+            emergency_info: emergency_info,
             is_admin: is_admin,
             is_post_holder: is_post_holder,
             holder_post_id: holder_post_id,
         },
-    )
+    ))
 }
 
 pub fn routes() -> Vec<Route> {
@@ -0,0 +1,177 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use rocket::{Route, State};
+use rocket_dyn_templates::{context, Template};
+use serde::{Deserialize, Serialize};
+
+use crate::config::AppConfig;
+use crate::db::DbConn;
+use crate::event::ActiveEvent;
+use crate::models::Event;
+use crate::routes::ranking::{rank_groups, renumber_ranks, RankedGroup};
+use crate::stats::now_naive;
+
+/// Group columns that can be shown on the public leaderboard, with their header
+pub const PUBLIC_FIELDS: [(&str, &str); 7] = [
+    ("group_number", "Nr"),
+    ("name", "Groep"),
+    ("scout_group", "Speltak"),
+    ("route", "Route"),
+    ("category", "Categorie"),
+    ("time", "Tijd"),
+    ("points", "Punten"),
+];
+
+/// Known public fields from a comma separated list, in the order of [`PUBLIC_FIELDS`]
+pub fn parse_public_fields(list: &str) -> Vec<&'static str> {
+    let chosen: Vec<&str> = list.split(',').map(str::trim).collect();
+    PUBLIC_FIELDS
+        .iter()
+        .map(|(field, _)| *field)
+        .filter(|field| chosen.contains(field))
+        .collect()
+}
+
+/// A row of the public leaderboard. Only the fields chosen by an admin are kept before it is
+/// rendered, so nothing else ends up in the page.
+#[derive(Serialize, Deserialize)]
+pub struct PublicEntry {
+    /// Empty for groups that did not visit all posts
+    pub rank: Option<usize>,
+    pub group_number: Option<i32>,
+    pub name: Option<String>,
+    pub scout_group: Option<String>,
+    pub route: Option<String>,
+    pub category: Option<String>,
+    pub time: Option<String>,
+    pub points: Option<i32>,
+}
+
+impl PublicEntry {
+    fn new(ranked: RankedGroup) -> Self {
+        let group = ranked.group;
+        PublicEntry {
+            rank: Some(ranked.rank).filter(|_| ranked.visited_all_posts),
+            group_number: Some(group.group_number),
+            name: Some(group.name),
+            scout_group: Some(group.scout_group),
+            route: Some(group.route),
+            category: Some(group.category),
+            time: ranked.total_time,
+            points: Some(ranked.points),
+        }
+    }
+
+    /// The same entry with only `fields` filled in
+    fn only(self, fields: &[&str]) -> Self {
+        let show = |field: &str| fields.contains(&field);
+        PublicEntry {
+            rank: self.rank,
+            group_number: self.group_number.filter(|_| show("group_number")),
+            name: self.name.filter(|_| show("name")),
+            scout_group: self.scout_group.filter(|_| show("scout_group")),
+            route: self.route.filter(|_| show("route")),
+            category: self.category.filter(|_| show("category")),
+            time: self.time.filter(|_| show("time")),
+            points: self.points.filter(|_| show("points")),
+        }
+    }
+}
+
+/// Current standings of an event with every field filled in, ranking only groups that finished
+/// before `finished_before` if given
+fn public_standings(
+    conn: &mut SqliteConnection,
+    event: &Event,
+    seconds_per_point: i64,
+    finished_before: Option<NaiveDateTime>,
+) -> QueryResult<Vec<PublicEntry>> {
+    let mut ranked_groups = rank_groups(conn, event, "rules", seconds_per_point)?;
+    if let Some(freeze_at) = finished_before {
+        ranked_groups.retain(|g| g.group.finish_time.is_some_and(|t| t <= freeze_at));
+        renumber_ranks(&mut ranked_groups);
+    }
+    Ok(ranked_groups.into_iter().map(PublicEntry::new).collect())
+}
+
+/// Standings of an event as they were when its leaderboard froze, empty before the freeze. They
+/// are kept the first time they are asked for after the freeze, so later scans, scores,
+/// adjustments and status changes stay hidden until revealed.
+pub fn frozen_standings(
+    conn: &mut SqliteConnection,
+    event: &Event,
+    seconds_per_point: i64,
+) -> QueryResult<Option<Vec<PublicEntry>>> {
+    let Some(freeze_at) = event.public_freeze_at.filter(|t| now_naive() >= *t) else {
+        return Ok(None);
+    };
+    if let Some(entries) = event
+        .public_snapshot
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok())
+    {
+        return Ok(Some(entries));
+    }
+
+    let entries = public_standings(conn, event, seconds_per_point, Some(freeze_at))?;
+    match serde_json::to_string(&entries) {
+        Ok(json) => {
+            Event::set_public_snapshot(conn, &event.id, &json)?;
+        }
+        Err(err) => eprintln!("Failed to keep frozen standings: {err}"),
+    }
+    Ok(Some(entries))
+}
+
+/// Standings of the active event for everyone, if an admin made them public. While frozen the
+/// standings of the moment of the freeze are shown, admins keep seeing everything on `/ranking`.
+#[get("/")]
+pub async fn leaderboard(
+    conn: DbConn,
+    config: &State<AppConfig>,
+    active: ActiveEvent,
+) -> Option<Template> {
+    let event = active.0;
+    if !event.public_ranking {
+        return None;
+    }
+
+    let freeze_at = event
+        .public_freeze_at
+        .filter(|freeze_at| !event.public_revealed && now_naive() >= *freeze_at);
+    let ranked_event = event.clone();
+    let seconds_per_point = config.seconds_per_point;
+    let entries = conn
+        .run(move |c| match freeze_at {
+            Some(_) => {
+                frozen_standings(c, &ranked_event, seconds_per_point).map(Option::unwrap_or_default)
+            }
+            None => public_standings(c, &ranked_event, seconds_per_point, None),
+        })
+        .await
+        .unwrap_or_default();
+
+    let fields = parse_public_fields(&event.public_fields);
+    let headers: Vec<&str> = PUBLIC_FIELDS
+        .iter()
+        .filter(|(field, _)| fields.contains(field))
+        .map(|(_, header)| *header)
+        .collect();
+    let entries: Vec<PublicEntry> = entries.into_iter().map(|e| e.only(&fields)).collect();
+
+    Some(Template::render(
+        "leaderboard",
+        context! {
+            event_name: event.name,
+            entries: entries,
+            fields: fields,
+            headers: headers,
+            frozen_at: freeze_at,
+        },
+    ))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![leaderboard]
+}
@@ -0,0 +1,32 @@
+use rocket::response::stream::{Event, EventStream};
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::{Route, Shutdown, State};
+
+use crate::auth::AnyAuth;
+use crate::live::LiveUpdates;
+
+/// Server-Sent Events stream with an `update` event per change. A `resync` event is sent when
+/// events were dropped, clients should then reload everything. Only for logged in users, pages
+/// for visitors poll instead.
+#[get("/")]
+pub fn events(_auth: AnyAuth, live: &State<LiveUpdates>, mut end: Shutdown) -> EventStream![] {
+    let mut receiver = live.subscribe();
+    EventStream! {
+        loop {
+            let event = select! {
+                event = receiver.recv() => match event {
+                    Ok(event) => Event::json(&event).event("update"),
+                    Err(RecvError::Lagged(_)) => Event::data("").event("resync"),
+                    Err(RecvError::Closed) => break,
+                },
+                _ = &mut end => break,
+            };
+            yield event;
+        }
+    }
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![events]
+}
@@ -1,6 +1,10 @@
 pub mod admin;
+pub mod api;
 pub mod auth;
 pub mod dashboard;
+pub mod leaderboard;
+pub mod live;
 pub mod post;
+pub mod pwa;
 pub mod ranking;
 pub mod scan;
@@ -1,10 +1,13 @@
-use rocket::Route;
+use rocket::{Route, State};
 use rocket_dyn_templates::{context, Template};
 use serde::Serialize;
 
 use crate::auth::{get_auth_context, is_admin};
+use crate::config::AppConfig;
 use crate::db::DbConn;
+use crate::eta::{load_etas, Eta};
 use crate::models::{Group, Post, Scan};
+use crate::stats::now_naive;
 
 #[derive(Serialize)]
 struct GroupStatus {
@@ -13,6 +16,10 @@ struct GroupStatus {
     arrival_time: Option<String>,
     departure_time: Option<String>,
     time_at_post: Option<String>,
+    /// Expected arrival, for groups on their way to this post
+    eta: Option<Eta>,
+    /// Whether a group on its way can no longer arrive before the cut-off
+    too_late: bool,
 }
 
 #[get("/<post_id>")]
@@ -20,6 +27,7 @@ pub async fn post_overview(
     conn: DbConn,
     post_id: String,
     cookies: &rocket::http::CookieJar<'_>,
+    config: &State<AppConfig>,
 ) -> Option<Template> {
     let post_id_clone = post_id.clone();
     let post = conn
@@ -32,8 +40,22 @@ pub async fn post_overview(
         .run(move |c| Scan::get_by_post(c, &post_id_clone))
         .await
         .unwrap_or_default();
-    let all_groups = conn.run(Group::get_all).await.unwrap_or_default();
+    let event_id = post.event_id.clone();
+    let all_groups = conn
+        .run(move |c| Group::get_all(c, &event_id))
+        .await
+        .unwrap_or_default();
+    let event_id = post.event_id.clone();
+    let margin = config.overdue_margin;
+    let etas = conn
+        .run(move |c| load_etas(c, &event_id, margin))
+        .await
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to predict arrivals: {err}");
+            Vec::new()
+        });
 
+    let now = now_naive();
     let mut groups_at_post = Vec::new();
     let mut groups_left = Vec::new();
     let mut groups_coming = Vec::new();
@@ -61,6 +83,8 @@ pub async fn post_overview(
                     arrival_time,
                     departure_time: departure_time.clone(),
                     time_at_post,
+                    eta: None,
+                    too_late: false,
                 };
 
                 if s.departure_time.is_some() {
@@ -71,12 +95,24 @@ pub async fn post_overview(
             }
             None => {
                 // Group hasn't arrived at this post yet - show as coming
+                let eta = etas
+                    .iter()
+                    .find(|eta| {
+                        eta.group.id == group.id
+                            && eta.next_post.as_ref().is_some_and(|p| p.id == post_id)
+                    })
+                    .cloned();
+                let expected_at = eta.as_ref().map_or(now, |eta| eta.expected_at.max(now));
+                let too_late = group.finish_time.is_none()
+                    && post.last_arrival().is_some_and(|last| expected_at > last);
                 groups_coming.push(GroupStatus {
                     group,
                     scan: None,
                     arrival_time: None,
                     departure_time: None,
                     time_at_post: None,
+                    eta,
+                    too_late,
                 });
             }
         }
@@ -89,6 +125,24 @@ pub async fn post_overview(
         Some(pid) if pid == &post_id
     );
     let holder_post_id = auth_ctx.holder_post_id;
+    let is_admin = is_admin(cookies);
+    let emergency_info = std::env::var("EMERGENCY_INFO").ok();
+
+    // Groups expected here first, overdue groups on top
+    groups_coming.sort_by_key(|gs| match &gs.eta {
+        Some(eta) => (!eta.overdue, false, Some(eta.expected_at)),
+        None => (true, true, None),
+    });
+    let has_overdue = groups_coming
+        .iter()
+        .any(|gs| gs.eta.as_ref().is_some_and(|eta| eta.overdue));
+
+    // Countdown to closing, while the post is still open
+    let closes_in = post
+        .closes_at
+        .map(|closes_at| (closes_at - now).num_seconds())
+        .filter(|secs| *secs > 0);
+    let closed = post.closes_at.is_some_and(|closes_at| closes_at <= now);
 
     Some(Template::render(
         "post_overview",
@@ -97,9 +151,13 @@ pub async fn post_overview(
             groups_at_post: groups_at_post,
             groups_left: groups_left,
             groups_coming: groups_coming,
-            is_admin: is_admin(cookies),
+            is_admin: is_admin,
             is_post_holder: is_post_holder,
             holder_post_id: holder_post_id,
+            has_overdue: has_overdue,
+            closes_in: closes_in,
+            closed: closed,
+            emergency_info: emergency_info,
         },
     ))
 }
@@ -0,0 +1,24 @@
+use rocket::fs::NamedFile;
+use rocket::http::Header;
+use rocket::Route;
+
+#[derive(Responder)]
+pub struct ServiceWorker {
+    file: NamedFile,
+    cache_control: Header<'static>,
+}
+
+/// Service worker for offline scanning, served from the root so its scope covers all pages
+#[get("/sw.js")]
+pub async fn service_worker() -> Option<ServiceWorker> {
+    let file = NamedFile::open("static/sw.js").await.ok()?;
+    Some(ServiceWorker {
+        file,
+        // Browsers should pick up a new service worker right after a deploy
+        cache_control: Header::new("Cache-Control", "no-cache"),
+    })
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![service_worker]
+}
@@ -1,112 +1,488 @@
-use rocket::Route;
+use chrono::TimeDelta;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use rocket::http::{ContentType, Header, Status};
+use rocket::{Route, State};
 use rocket_dyn_templates::{context, Template};
 use serde::Serialize;
 
 use crate::auth::Admin;
+use crate::config::AppConfig;
 use crate::db::DbConn;
-use crate::models::{Group, Post, Scan};
-use crate::stats::{calculate_group_stats, format_duration};
+use crate::event::{viewed_event, ActiveEvent};
+use crate::export;
+use crate::models::{group_status_label, Adjustment, Event, Group, RouteMap, Scan, Score};
+use crate::ranking_rules::{Criterion, RankingRules, Standing};
+use crate::scoring::ScoreSchemes;
+use crate::stats::{calculate_group_stats, compare_legs, format_duration, AdjustmentInfo};
 
 #[derive(Serialize)]
 pub struct RankedGroup {
     pub rank: usize,
     pub group: Group,
+    /// Total time including penalties and bonuses
     pub total_time: Option<String>,
+    /// Walking time including penalties and bonuses, with idle time credited by the ranking rules
     pub walking_time: Option<String>,
+    pub raw_total_time: Option<String>,
+    pub raw_walking_time: Option<String>,
+    /// Idle time as credited by the ranking rules
     pub idle_time: String,
     pub total_time_secs: Option<i64>,
     pub walking_time_secs: Option<i64>,
     pub posts_visited: usize,
     pub total_posts: usize,
     pub visited_all_posts: bool,
+    /// Scored points including bonuses and deductions
+    pub points: i32,
+    pub raw_points: i32,
+    /// Sum of time penalties and bonuses, empty without time adjustments
+    pub time_adjustment: Option<String>,
+    pub points_adjustment: i32,
+    pub adjustments: Vec<AdjustmentInfo>,
+    /// Points that could be scored on the route of the group
+    pub max_points: i32,
+    /// Total time minus the time the points are worth
+    pub combined_time: Option<String>,
+    pub combined_time_secs: Option<i64>,
 }
 
-#[get("/?<sort>")]
-pub async fn ranking(_admin: Admin, conn: DbConn, sort: Option<String>) -> Template {
-    let sort_by = sort.unwrap_or_else(|| "total".to_string());
+/// A group left out of the ranking, because it did not finish yet or is out of the race
+#[derive(Serialize)]
+pub struct UnrankedGroup {
+    pub group: Group,
+    pub status: String,
+    pub label: &'static str,
+}
+
+/// Number of groups per category at the prize ceremony
+const PODIUM_SIZE: usize = 3;
 
-    let groups = conn.run(Group::get_all).await.unwrap_or_default();
-    let posts = conn.run(Post::get_all).await.unwrap_or_default();
-    let total_posts = posts.len();
+/// Sorts of the ranking page, `rules` sorts by the ranking rules of the event
+const SORTS: [&str; 5] = ["rules", "total", "walking", "points", "combined"];
 
-    let mut ranked_groups: Vec<RankedGroup> = Vec::new();
+/// Rules to rank on for a sort of the ranking page. The fixed sorts `total`, `walking`,
+/// `points` and `combined` replace the criteria of the event, `rules` and others use them.
+pub fn ranking_rules(event: &Event, sort_by: &str) -> RankingRules {
+    let rules = RankingRules::from_event(event);
+    let criteria = match sort_by {
+        "total" => vec![Criterion::Complete, Criterion::TotalTime],
+        "walking" => vec![Criterion::Complete, Criterion::WalkingTime],
+        // Equal points are decided by total time
+        "points" => vec![Criterion::Complete, Criterion::Points, Criterion::TotalTime],
+        "combined" => vec![Criterion::Complete, Criterion::Combined],
+        _ => return rules,
+    };
+    rules.with_criteria(criteria)
+}
+
+/// Finished groups of an event in ranking order, as decided by the ranking rules for `sort_by`.
+/// Every point is worth `seconds_per_point` seconds off the total time in the combined time.
+pub fn rank_groups(
+    conn: &mut SqliteConnection,
+    event: &Event,
+    sort_by: &str,
+    seconds_per_point: i64,
+) -> QueryResult<Vec<RankedGroup>> {
+    let rules = ranking_rules(event, sort_by);
+    let groups = Group::get_all(conn, &event.id)?;
+    let route_map = RouteMap::load(conn, &event.id)?;
+    let schemes = ScoreSchemes::load(conn, &event.id)?;
+
+    let mut ranked_groups: Vec<(Standing, RankedGroup)> = Vec::new();
 
     for group in groups {
-        // Only include groups that have finished
-        if group.finish_time.is_none() {
+        // Only include groups that have finished and are still in the race
+        if group.finish_time.is_none() || group.is_out_of_race() {
             continue;
         }
 
-        let group_id = group.id.clone();
-        let scans = conn
-            .run(move |c| Scan::get_by_group(c, &group_id))
-            .await
-            .unwrap_or_default();
+        let scans = Scan::get_by_group(conn, &group.id)?;
+        let scores = Score::get_by_group(conn, &group.id)?;
+        let route_posts = route_map.posts_for(group.route_id.as_deref());
+        let adjustments = Adjustment::get_by_group(conn, &group.id)?;
+        let raw_points = schemes.points(&scores);
+        let max_points = schemes.max_points(&route_posts);
+        let stats = calculate_group_stats(&group, &scans, route_posts, &adjustments);
+        let points = raw_points + stats.points_adjustment;
 
-        let posts_visited = scans.len();
+        // Only posts on the route of the group count
+        let total_posts = stats.post_scans.len();
+        let posts_visited = stats
+            .post_scans
+            .iter()
+            .filter(|ps| ps.scan.is_some())
+            .count();
         let visited_all_posts = posts_visited >= total_posts;
 
-        let stats = calculate_group_stats(&group, &scans, posts.clone());
-        let total_time = stats.total_time;
-        let idle_time = stats.idle_time;
-        let walking_time = stats.walking_time;
-
-        ranked_groups.push(RankedGroup {
-            rank: 0, // Will be set after sorting
-            group,
-            total_time: total_time.map(format_duration),
-            walking_time: walking_time.map(format_duration),
-            idle_time: format_duration(idle_time),
-            total_time_secs: total_time.map(|t| t.num_seconds()),
-            walking_time_secs: walking_time.map(|t| t.num_seconds()),
-            posts_visited,
-            total_posts,
+        // Rank on times including penalties and bonuses, with idle time as credited by the rules
+        let total_time = stats.adjusted_total_time;
+        let idle_time: TimeDelta = stats
+            .post_scans
+            .iter()
+            .filter_map(|ps| ps.idle_time)
+            .map(|idle| rules.credited_idle_time(idle))
+            .sum();
+        let walking_time = total_time.map(|t| t - idle_time);
+        let combined_time =
+            total_time.map(|t| t - TimeDelta::seconds(i64::from(points) * seconds_per_point));
+
+        let standing = Standing {
             visited_all_posts,
-        });
+            posts_visited,
+            total_time,
+            walking_time,
+            points,
+            combined_time,
+        };
+        ranked_groups.push((
+            standing,
+            RankedGroup {
+                rank: 0, // Will be set after sorting
+                group,
+                total_time: total_time.map(format_duration),
+                walking_time: walking_time.map(format_duration),
+                raw_total_time: stats.total_time.map(format_duration),
+                raw_walking_time: stats.walking_time.map(format_duration),
+                idle_time: format_duration(idle_time),
+                total_time_secs: total_time.map(|t| t.num_seconds()),
+                walking_time_secs: walking_time.map(|t| t.num_seconds()),
+                posts_visited,
+                total_posts,
+                visited_all_posts,
+                points,
+                raw_points,
+                time_adjustment: Some(stats.time_adjustment)
+                    .filter(|t| !t.is_zero())
+                    .map(|t| match t > TimeDelta::zero() {
+                        true => format!("+{}", format_duration(t)),
+                        false => format_duration(t),
+                    }),
+                points_adjustment: stats.points_adjustment,
+                adjustments: adjustments.into_iter().map(AdjustmentInfo::new).collect(),
+                max_points,
+                combined_time: combined_time.map(format_duration),
+                combined_time_secs: combined_time.map(|t| t.num_seconds()),
+            },
+        ));
     }
 
-    // Sort: complete groups first (by time), then incomplete groups (by time)
-    match sort_by.as_str() {
-        "walking" => {
-            ranked_groups.sort_by(|a, b| {
-                // First sort by visited_all_posts (complete groups first)
-                b.visited_all_posts.cmp(&a.visited_all_posts).then_with(|| {
-                    // Then by walking time
-                    a.walking_time_secs
-                        .unwrap_or(i64::MAX)
-                        .cmp(&b.walking_time_secs.unwrap_or(i64::MAX))
-                })
-            });
-        }
-        _ => {
-            // Default: sort by total time
-            ranked_groups.sort_by(|a, b| {
-                // First sort by visited_all_posts (complete groups first)
-                b.visited_all_posts.cmp(&a.visited_all_posts).then_with(|| {
-                    // Then by total time
-                    a.total_time_secs
-                        .unwrap_or(i64::MAX)
-                        .cmp(&b.total_time_secs.unwrap_or(i64::MAX))
-                })
-            });
+    ranked_groups.sort_by(|(a, _), (b, _)| rules.compare(a, b));
+
+    // Assign ranks, groups that tie on every criterion share the rank of the first of them
+    let mut groups: Vec<RankedGroup> = Vec::with_capacity(ranked_groups.len());
+    let mut previous: Option<Standing> = None;
+    for (i, (standing, mut group)) in ranked_groups.into_iter().enumerate() {
+        group.rank = match (&previous, groups.last()) {
+            (Some(prev), Some(last)) if rules.compare(prev, &standing).is_eq() => last.rank,
+            _ => i + 1,
+        };
+        previous = Some(standing);
+        groups.push(group);
+    }
+    Ok(groups)
+}
+
+/// Number the ranks of a part of the ranking again, in order. Groups that shared a rank in the
+/// whole ranking keep sharing one, so ties still read "1, 1, 3".
+pub fn renumber_ranks(groups: &mut [RankedGroup]) {
+    let mut previous: Option<(usize, usize)> = None;
+    for (i, group) in groups.iter_mut().enumerate() {
+        let rank = match previous {
+            Some((old, new)) if old == group.rank => new,
+            _ => i + 1,
+        };
+        previous = Some((group.rank, rank));
+        group.rank = rank;
+    }
+}
+
+/// Groups of an event that [`rank_groups`] leaves out, out of the race first
+pub fn unranked_groups(
+    conn: &mut SqliteConnection,
+    event: &Event,
+) -> QueryResult<Vec<UnrankedGroup>> {
+    let mut groups: Vec<UnrankedGroup> = Group::get_all(conn, &event.id)?
+        .into_iter()
+        .filter(|g| g.finish_time.is_none() || g.is_out_of_race())
+        .map(|group| {
+            let status = group.status().to_string();
+            UnrankedGroup {
+                label: group_status_label(&status),
+                status,
+                group,
+            }
+        })
+        .collect();
+    groups.sort_by_key(|g| !g.group.is_out_of_race());
+    Ok(groups)
+}
+
+/// Group fields the ranking can be split by, each value gets its own rank numbering
+const SPLITS: [&str; 3] = ["route", "scout_group", "category"];
+
+/// Ranking within one route, scout group or category
+#[derive(Serialize)]
+pub struct CategoryRanking {
+    /// Empty for groups without a value, or for the whole ranking when it is not split
+    pub name: String,
+    pub groups: Vec<RankedGroup>,
+}
+
+/// Split ranked groups by `route`, `scout_group` or `category`, numbering the ranks per
+/// category. Categories are sorted by name, groups without a value come last.
+pub fn split_ranking(groups: Vec<RankedGroup>, split: &str) -> Vec<CategoryRanking> {
+    let mut categories: Vec<CategoryRanking> = Vec::new();
+    for group in groups {
+        let name = match split {
+            "route" => group.group.route.trim(),
+            "scout_group" => group.group.scout_group.trim(),
+            _ => group.group.category.trim(),
+        };
+        match categories
+            .iter_mut()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+        {
+            Some(category) => category.groups.push(group),
+            None => categories.push(CategoryRanking {
+                name: name.to_string(),
+                groups: vec![group],
+            }),
         }
     }
 
-    // Assign ranks
-    for (i, group) in ranked_groups.iter_mut().enumerate() {
-        group.rank = i + 1;
+    categories.sort_by_key(|c| (c.name.is_empty(), c.name.to_lowercase()));
+    for category in &mut categories {
+        renumber_ranks(&mut category.groups);
+    }
+    categories
+}
+
+/// Ranking of an event, as a single category or split by `split`
+async fn load_categories(
+    conn: &DbConn,
+    event: &Event,
+    sort_by: &str,
+    split: Option<&str>,
+    seconds_per_point: i64,
+) -> Vec<CategoryRanking> {
+    let ranked_event = event.clone();
+    let sort = sort_by.to_string();
+    let ranked_groups = conn
+        .run(move |c| rank_groups(c, &ranked_event, &sort, seconds_per_point))
+        .await
+        .unwrap_or_default();
+
+    let Some(split) = split else {
+        return vec![CategoryRanking {
+            name: String::new(),
+            groups: ranked_groups,
+        }];
+    };
+    split_ranking(ranked_groups, split)
+}
+
+#[get("/?<sort>&<event>&<split>&<category>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn ranking(
+    _admin: Admin,
+    conn: DbConn,
+    config: &State<AppConfig>,
+    active: ActiveEvent,
+    sort: Option<String>,
+    event: Option<String>,
+    split: Option<String>,
+    category: Option<String>,
+) -> Option<Template> {
+    let event = viewed_event(&conn, active, event).await?;
+    let sort_by = sort
+        .filter(|s| SORTS.contains(&s.as_str()))
+        .unwrap_or_else(|| "rules".to_string());
+    let split = split.filter(|s| SPLITS.contains(&s.as_str()));
+    let rules = ranking_rules(&event, &sort_by);
+    let criteria: Vec<&str> = rules.criteria.iter().map(|c| c.label()).collect();
+    let show_combined = rules.criteria.contains(&Criterion::Combined);
+
+    let seconds_per_point = config.seconds_per_point;
+    let mut categories =
+        load_categories(&conn, &event, &sort_by, split.as_deref(), seconds_per_point).await;
+    // All category names for the filter, also when only one category is shown
+    let category_names: Vec<String> = match split {
+        Some(_) => categories.iter().map(|c| c.name.clone()).collect(),
+        None => Vec::new(),
+    };
+    if let Some(category) = &category {
+        categories.retain(|c| c.name.eq_ignore_ascii_case(category.trim()));
     }
+    let unranked_event = event.clone();
+    let unranked = conn
+        .run(move |c| unranked_groups(c, &unranked_event))
+        .await
+        .unwrap_or_default();
 
-    Template::render(
+    Some(Template::render(
         "ranking",
         context! {
-            ranked_groups: ranked_groups,
+            categories: categories,
+            unranked: unranked,
+            category_names: category_names,
+            split: split,
+            category_filter: category,
             sort_by: sort_by,
+            criteria: criteria,
+            show_combined: show_combined,
+            min_idle_credit: rules.min_idle_credit.num_minutes(),
+            max_post_time: rules.max_post_time.map(|t| t.num_minutes()),
+            seconds_per_point: seconds_per_point,
+            event: event,
             is_admin: true,
         },
+    ))
+}
+
+/// Podium of every category, to read out at the prize ceremony
+#[get("/ceremony?<sort>&<event>&<split>")]
+pub async fn ceremony(
+    _admin: Admin,
+    conn: DbConn,
+    config: &State<AppConfig>,
+    active: ActiveEvent,
+    sort: Option<String>,
+    event: Option<String>,
+    split: Option<String>,
+) -> Option<Template> {
+    let event = viewed_event(&conn, active, event).await?;
+    let sort_by = sort
+        .filter(|s| SORTS.contains(&s.as_str()))
+        .unwrap_or_else(|| "rules".to_string());
+    let split = split.filter(|s| SPLITS.contains(&s.as_str()));
+
+    let mut categories = load_categories(
+        &conn,
+        &event,
+        &sort_by,
+        split.as_deref(),
+        config.seconds_per_point,
     )
+    .await;
+    // Only groups that visited all posts can win a prize, groups tied for the last prize all get it
+    for category in &mut categories {
+        category.groups.retain(|g| g.visited_all_posts);
+        if let Some(last_rank) = category.groups.get(PODIUM_SIZE - 1).map(|g| g.rank) {
+            category.groups.retain(|g| g.rank <= last_rank);
+        }
+    }
+    categories.retain(|c| !c.groups.is_empty());
+
+    Some(Template::render(
+        "ranking_ceremony",
+        context! {
+            categories: categories,
+            split: split,
+            sort_by: sort_by,
+            event: event,
+            is_admin: true,
+        },
+    ))
+}
+
+/// Fastest, median and slowest walking time per leg between posts, to plan routes with
+#[get("/legs?<event>")]
+pub async fn legs(
+    _admin: Admin,
+    conn: DbConn,
+    active: ActiveEvent,
+    event: Option<String>,
+) -> Option<Template> {
+    let event = viewed_event(&conn, active, event).await?;
+    let event_id = event.id.clone();
+    let legs = conn
+        .run(move |c| {
+            let route_map = RouteMap::load(c, &event_id)?;
+            let groups = Group::get_all(c, &event_id)?
+                .into_iter()
+                .map(|group| {
+                    let scans = Scan::get_by_group(c, &group.id)?;
+                    let posts = route_map.posts_for(group.route_id.as_deref());
+                    let stats = calculate_group_stats(&group, &scans, posts, &[]);
+                    Ok((group, stats.legs))
+                })
+                .collect::<QueryResult<Vec<_>>>()?;
+            Ok::<_, diesel::result::Error>(compare_legs(groups))
+        })
+        .await
+        .unwrap_or_default();
+
+    Some(Template::render(
+        "legs",
+        context! { legs: legs, event: event, is_admin: true },
+    ))
+}
+
+#[derive(Responder)]
+pub struct Download {
+    content: (ContentType, Vec<u8>),
+    disposition: Header<'static>,
+}
+
+impl Download {
+    fn new(content_type: ContentType, file_name: &str, content: Vec<u8>) -> Self {
+        Download {
+            content: (content_type, content),
+            disposition: Header::new(
+                "Content-Disposition",
+                format!("attachment; filename=\"{file_name}\""),
+            ),
+        }
+    }
+}
+
+#[get("/export.csv?<event>")]
+pub async fn export_csv(
+    _admin: Admin,
+    conn: DbConn,
+    active: ActiveEvent,
+    event: Option<String>,
+) -> Result<Download, Status> {
+    let event = viewed_event(&conn, active, event)
+        .await
+        .ok_or(Status::NotFound)?;
+    let (route_map, results) = conn
+        .run(move |c| export::load_results(c, &event.id))
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+    let csv = export::to_csv(route_map.all_posts(), &results).map_err(|err| {
+        eprintln!("Failed to export results as CSV: {err}");
+        Status::InternalServerError
+    })?;
+    Ok(Download::new(ContentType::CSV, "uitslag.csv", csv))
+}
+
+#[get("/export.xlsx?<event>")]
+pub async fn export_xlsx(
+    _admin: Admin,
+    conn: DbConn,
+    active: ActiveEvent,
+    event: Option<String>,
+) -> Result<Download, Status> {
+    let event = viewed_event(&conn, active, event)
+        .await
+        .ok_or(Status::NotFound)?;
+    let (route_map, results) = conn
+        .run(move |c| export::load_results(c, &event.id))
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+    let xlsx = export::to_xlsx(&route_map, &results).map_err(|err| {
+        eprintln!("Failed to export results as XLSX: {err}");
+        Status::InternalServerError
+    })?;
+    let content_type = ContentType::new(
+        "application",
+        "vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    );
+    Ok(Download::new(content_type, "uitslag.xlsx", xlsx))
 }
 
 pub fn routes() -> Vec<Route> {
-    routes![ranking]
+    routes![ranking, ceremony, legs, export_csv, export_xlsx]
 }
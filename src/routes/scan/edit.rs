@@ -1,15 +1,92 @@
 use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
 use rocket::form::Form;
 use rocket::http::CookieJar;
-use rocket::response::Redirect;
-use rocket::Route;
+use rocket::request::FlashMessage;
+use rocket::response::{Flash, Redirect};
+use rocket::{Route, State};
 use rocket_dyn_templates::{context, Template};
 
+use crate::actions::{self, ActionError, Actor};
 use crate::auth::{self, Admin, AnyAuth};
 use crate::db::DbConn;
-use crate::models::{Group, NewScan, Post, Scan};
+use crate::live::LiveUpdates;
+use crate::models::{
+    admin_actor, Adjustment, AuditEntry, AuditStatus, AuditTimes, Event, Group, HikeRoute, NewScan,
+    Post, Scan, ADJUSTMENT_POINTS, ADJUSTMENT_TIME, AUDIT_ADD_SCAN, AUDIT_DELETE_SCAN,
+    AUDIT_EDIT_SCAN, AUDIT_EDIT_TIMER, AUDIT_START, AUDIT_STATUS, GROUP_OUT_OF_RACE,
+};
+use crate::stats::AdjustmentInfo;
+
+use super::{get_category_names, get_routes, get_scout_groups};
+
+/// Only groups of the active event and of draft events can be changed, those of archived events
+/// are read-only
+async fn editable_group(conn: &DbConn, group_id: &str) -> Option<Group> {
+    let gid = group_id.to_string();
+    conn.run(move |c| {
+        let Some(group) = Group::get_by_id(c, &gid)?.filter(|g| !g.reserved) else {
+            return Ok(None);
+        };
+        let editable = Event::get_by_id(c, &group.event_id)?.is_some_and(|e| e.is_editable());
+        Ok::<_, diesel::result::Error>(Some(group).filter(|_| editable))
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+/// A scan of an editable group, with the group
+async fn editable_scan(conn: &DbConn, group_id: &str, scan_id: &str) -> Option<(Group, Scan)> {
+    let group = editable_group(conn, group_id).await?;
+    let sid = scan_id.to_string();
+    let scan = conn
+        .run(move |c| Scan::get_by_id(c, &sid))
+        .await
+        .ok()
+        .flatten()
+        .filter(|s| s.group_id == group_id)?;
+    Some((group, scan))
+}
 
-use super::get_scout_groups;
+/// Record the change of the scan of a group at a post in the audit log, from the times before
+/// to the times it has now. Call it in the transaction that made the change.
+fn audit_scan_change(
+    c: &mut SqliteConnection,
+    actor: &str,
+    group: &Group,
+    post_id: &str,
+    action: &str,
+    before: Option<AuditTimes>,
+) -> QueryResult<()> {
+    let after = Scan::get_by_group_and_post(c, &group.id, post_id)?
+        .as_ref()
+        .map(AuditTimes::of_scan);
+    AuditEntry::record(c, group, Some(post_id), actor, action, before, after)
+}
+
+/// Record the change of the start and finish time of a group in the audit log, from the times
+/// of `group` to the times it has now. Call it in the transaction that made the change.
+fn audit_timer_change(
+    c: &mut SqliteConnection,
+    group: &Group,
+    actor: &str,
+    action: &str,
+) -> QueryResult<()> {
+    let Some(updated) = Group::get_by_id(c, &group.id)? else {
+        return Ok(());
+    };
+    AuditEntry::record(
+        c,
+        group,
+        None,
+        actor,
+        action,
+        Some(AuditTimes::of_group(group)),
+        Some(AuditTimes::of_group(&updated)),
+    )
+}
 
 #[get("/<group_id>/edit")]
 pub async fn edit_page(
@@ -17,28 +94,27 @@ pub async fn edit_page(
     cookies: &CookieJar<'_>,
     conn: DbConn,
     group_id: String,
+    flash: Option<FlashMessage<'_>>,
 ) -> Result<Template, Redirect> {
     let is_admin = auth::is_admin(cookies);
-    let gid = group_id.clone();
-    let group = conn
-        .run(move |c| Group::get_by_id(c, &gid))
-        .await
-        .ok()
-        .flatten();
-
-    let group = match group {
-        Some(g) => g,
-        None => return Err(Redirect::to("/dashboard")),
+    let Some(group) = editable_group(&conn, &group_id).await else {
+        return Err(Redirect::to(format!("/scan/{group_id}")));
     };
 
     let gid = group_id.clone();
-    let posts = conn.run(Post::get_all).await.unwrap_or_default();
+    let event_id = group.event_id.clone();
+    let posts = conn
+        .run(move |c| Post::get_all(c, &event_id))
+        .await
+        .unwrap_or_default();
     let scans = conn
         .run(move |c| Scan::get_by_group(c, &gid))
         .await
         .unwrap_or_default();
 
     let scout_groups = get_scout_groups();
+    let routes = get_routes(&conn, group.event_id.clone()).await;
+    let category_names = get_category_names(&conn, group.event_id.clone()).await;
 
     // For post holders, filter to only show their post
     let filtered_posts = if let Some(ref holder_post_id) = auth.post_id {
@@ -52,16 +128,33 @@ pub async fn edit_page(
 
     let is_post_holder = auth.post_id.is_some();
 
+    let adjustments: Vec<AdjustmentInfo> = if is_admin {
+        let gid = group_id.clone();
+        conn.run(move |c| Adjustment::get_by_group(c, &gid))
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(AdjustmentInfo::new)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
     Ok(Template::render(
         "scan_edit",
         context! {
             group: group,
             posts: filtered_posts,
             scans: scans,
+            adjustments: adjustments,
             is_admin: is_admin,
             scout_groups: scout_groups,
+            routes: routes,
+            category_names: category_names,
             holder_post_id: auth.post_id,
             is_post_holder: is_post_holder,
+            error: flash.map(|f| f.message().to_string()),
+            out_of_race: GROUP_OUT_OF_RACE,
         },
     ))
 }
@@ -77,78 +170,105 @@ pub struct UpdateScanForm {
 pub async fn update_scan(
     auth: AnyAuth,
     conn: DbConn,
+    live: &State<LiveUpdates>,
     group_id: String,
     scan_id: String,
     form: Form<UpdateScanForm>,
 ) -> Redirect {
+    let Some((group, scan)) = editable_scan(&conn, &group_id, &scan_id).await else {
+        return Redirect::to(format!("/scan/{group_id}"));
+    };
+
     // For post holders, verify they can only edit scans for their post
     if let Some(ref holder_post_id) = auth.post_id {
-        let sid = scan_id.clone();
-        let scan = conn
-            .run(move |c| Scan::get_by_id(c, &sid))
-            .await
-            .ok()
-            .flatten();
-
-        if let Some(scan) = scan {
-            if &scan.post_id != holder_post_id {
-                return Redirect::to(format!("/scan/{group_id}/edit"));
-            }
+        if &scan.post_id != holder_post_id {
+            return Redirect::to(format!("/scan/{group_id}/edit"));
         }
     }
 
-    // Parse arrival time
-    if let Ok(arrival) = NaiveDateTime::parse_from_str(&form.arrival_time, "%Y-%m-%dT%H:%M") {
-        let sid = scan_id.clone();
-        conn.run(move |c| Scan::set_arrival_time(c, &sid, arrival))
-            .await
-            .ok();
-    }
+    let arrival = NaiveDateTime::parse_from_str(&form.arrival_time, "%Y-%m-%dT%H:%M").ok();
+    // Cleared when the clear checkbox is checked, unchanged when empty
+    let departure = match form.clear_departure.is_some() {
+        true => Some(None),
+        false => form
+            .departure_time
+            .as_ref()
+            .filter(|dt| !dt.is_empty())
+            .and_then(|dt| NaiveDateTime::parse_from_str(dt, "%Y-%m-%dT%H:%M").ok())
+            .map(Some),
+    };
 
-    // Check if clear checkbox is checked
-    if form.clear_departure.is_some() {
-        let sid = scan_id.clone();
-        conn.run(move |c| Scan::clear_departure_time(c, &sid))
-            .await
-            .ok();
-    } else if let Some(dt) = &form.departure_time {
-        if !dt.is_empty() {
-            if let Ok(departure) = NaiveDateTime::parse_from_str(dt, "%Y-%m-%dT%H:%M") {
-                let sid = scan_id.clone();
-                conn.run(move |c| Scan::set_departure_time(c, &sid, departure))
-                    .await
-                    .ok();
-            }
-        }
+    let actor = Actor {
+        is_admin: auth.is_admin,
+        post_id: auth.post_id,
+        admin_session: auth.admin_session,
     }
-
+    .audit_name();
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                if let Some(arrival) = arrival.filter(|a| *a != scan.arrival_time) {
+                    Scan::set_arrival_time(c, &scan.id, arrival)?;
+                    // The penalty for arriving outside the opening hours follows the new time
+                    Adjustment::delete_by_scan(c, &scan.id)?;
+                    actions::penalize_outside_hours(c, &group, &scan.post_id, &scan.id, arrival)?;
+                }
+                match departure {
+                    Some(Some(departure)) => Scan::set_departure_time(c, &scan.id, departure)?,
+                    Some(None) => Scan::clear_departure_time(c, &scan.id)?,
+                    None => 0,
+                };
+                let before = Some(AuditTimes::of_scan(&scan));
+                audit_scan_change(c, &actor, &group, &scan.post_id, AUDIT_EDIT_SCAN, before)
+            })
+        })
+        .await;
+    if let Err(err) = result {
+        eprintln!("Failed to update scan: {err}");
+    }
+    live.scan(&group_id, None);
     Redirect::to(format!("/scan/{group_id}/edit"))
 }
 
-#[get("/<group_id>/edit/scan/<scan_id>/delete")]
+#[post("/<group_id>/edit/scan/<scan_id>/delete")]
 pub async fn delete_scan(
     auth: AnyAuth,
     conn: DbConn,
+    live: &State<LiveUpdates>,
     group_id: String,
     scan_id: String,
 ) -> Redirect {
+    let Some((group, scan)) = editable_scan(&conn, &group_id, &scan_id).await else {
+        return Redirect::to(format!("/scan/{group_id}"));
+    };
+
     // For post holders, verify they can only delete scans for their post
     if let Some(ref holder_post_id) = auth.post_id {
-        let sid = scan_id.clone();
-        let scan = conn
-            .run(move |c| Scan::get_by_id(c, &sid))
-            .await
-            .ok()
-            .flatten();
-
-        if let Some(scan) = scan {
-            if &scan.post_id != holder_post_id {
-                return Redirect::to(format!("/scan/{group_id}/edit"));
-            }
+        if &scan.post_id != holder_post_id {
+            return Redirect::to(format!("/scan/{group_id}/edit"));
         }
     }
 
-    conn.run(move |c| Scan::delete(c, &scan_id)).await.ok();
+    let actor = Actor {
+        is_admin: auth.is_admin,
+        post_id: auth.post_id,
+        admin_session: auth.admin_session,
+    }
+    .audit_name();
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                Scan::delete(c, &scan.id)?;
+                Adjustment::delete_by_scan(c, &scan.id)?;
+                let before = Some(AuditTimes::of_scan(&scan));
+                audit_scan_change(c, &actor, &group, &scan.post_id, AUDIT_DELETE_SCAN, before)
+            })
+        })
+        .await;
+    if let Err(err) = result {
+        eprintln!("Failed to delete scan: {err}");
+    }
+    live.scan(&group_id, None);
     Redirect::to(format!("/scan/{group_id}/edit"))
 }
 
@@ -163,18 +283,27 @@ pub struct AddScanForm {
 pub async fn add_scan(
     auth: AnyAuth,
     conn: DbConn,
+    live: &State<LiveUpdates>,
     group_id: String,
     form: Form<AddScanForm>,
-) -> Redirect {
+) -> Result<Redirect, Flash<Redirect>> {
+    let Some(group) = editable_group(&conn, &group_id).await else {
+        return Ok(Redirect::to(format!("/scan/{group_id}")));
+    };
+
     // Post holders can only add scans for their assigned post
     if let Some(ref holder_post_id) = auth.post_id {
         if &form.post_id != holder_post_id {
-            return Redirect::to(format!("/scan/{group_id}/edit"));
+            return Ok(Redirect::to(format!("/scan/{group_id}/edit")));
         }
     }
 
     if let Ok(arrival) = NaiveDateTime::parse_from_str(&form.arrival_time, "%Y-%m-%dT%H:%M") {
-        let gid = group_id.clone();
+        let actor = Actor {
+            is_admin: auth.is_admin,
+            post_id: auth.post_id,
+            admin_session: auth.admin_session,
+        };
         let post_id = form.post_id.clone();
         let departure = form
             .departure_time
@@ -182,20 +311,134 @@ pub async fn add_scan(
             .filter(|s| !s.is_empty())
             .and_then(|dt| NaiveDateTime::parse_from_str(dt, "%Y-%m-%dT%H:%M").ok());
 
-        conn.run(move |c| {
-            let scan = NewScan {
-                id: uuid::Uuid::new_v4().to_string(),
-                group_id: gid,
-                post_id,
-                arrival_time: arrival,
-                departure_time: departure,
-            };
-            Scan::insert(c, scan)
+        let result = conn
+            .run(move |c| {
+                c.transaction(|c| {
+                    Post::get_by_id(c, &post_id)?
+                        .filter(|p| p.event_id == group.event_id)
+                        .ok_or(ActionError::PostNotFound)?;
+                    let out_of_order = actions::check_order(c, &actor, &group, &post_id, arrival)?;
+                    let scan_id = uuid::Uuid::new_v4().to_string();
+                    let scan = NewScan {
+                        id: scan_id.clone(),
+                        group_id: group.id.clone(),
+                        post_id: post_id.clone(),
+                        arrival_time: arrival,
+                        departure_time: departure,
+                        out_of_order,
+                    };
+                    Scan::insert(c, scan)?;
+                    actions::penalize_outside_hours(c, &group, &post_id, &scan_id, arrival)?;
+                    let after = AuditTimes {
+                        from: Some(arrival),
+                        until: departure,
+                    };
+                    AuditEntry::record(
+                        c,
+                        &group,
+                        Some(&post_id),
+                        &actor.audit_name(),
+                        AUDIT_ADD_SCAN,
+                        None,
+                        Some(after),
+                    )?;
+                    Ok::<_, ActionError>(())
+                })
+            })
+            .await;
+        match result {
+            Ok(()) => live.scan(&group_id, Some(&form.post_id)),
+            Err(ActionError::OutOfOrder(message)) => {
+                return Err(Flash::error(
+                    Redirect::to(format!("/scan/{group_id}/edit")),
+                    message,
+                ));
+            }
+            Err(_) => {}
+        }
+    }
+
+    Ok(Redirect::to(format!("/scan/{group_id}/edit")))
+}
+
+/// Accept a scan that skipped a post on the route, e.g. when the group was sent around a closed
+/// post
+#[post("/<group_id>/edit/scan/<scan_id>/approve")]
+pub async fn approve_scan_order(
+    admin: Admin,
+    conn: DbConn,
+    live: &State<LiveUpdates>,
+    group_id: String,
+    scan_id: String,
+) -> Redirect {
+    let Some((group, scan)) = editable_scan(&conn, &group_id, &scan_id).await else {
+        return Redirect::to(format!("/scan/{group_id}"));
+    };
+    if !scan.out_of_order {
+        return Redirect::to(format!("/scan/{group_id}/edit"));
+    }
+
+    let post_id = scan.post_id.clone();
+    let actor = admin.audit_name();
+    if let Err(err) = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                Scan::approve_order(c, &scan.id)?;
+                AuditEntry::record_order_approval(c, &group, &scan, &actor)
+            })
         })
         .await
-        .ok();
+    {
+        eprintln!("Failed to approve scan order: {err}");
     }
+    live.scan(&group_id, Some(&post_id));
+    Redirect::to(format!("/scan/{group_id}/edit"))
+}
 
+#[derive(FromForm)]
+pub struct GroupStatusForm {
+    /// One of the out of race statuses, empty to follow the start and finish time again
+    status: String,
+    reason: String,
+}
+
+/// Take a group out of the race or put it back
+#[post("/<group_id>/edit/group/status", data = "<form>")]
+pub async fn update_group_status(
+    admin: Admin,
+    conn: DbConn,
+    live: &State<LiveUpdates>,
+    group_id: String,
+    form: Form<GroupStatusForm>,
+) -> Redirect {
+    let Some(group) = editable_group(&conn, &group_id).await else {
+        return Redirect::to(format!("/scan/{group_id}"));
+    };
+    let status = form.status.clone();
+    if !status.is_empty() && !GROUP_OUT_OF_RACE.iter().any(|(key, _)| *key == status) {
+        return Redirect::to(format!("/scan/{group_id}/edit"));
+    }
+    // Groups back in the race have no reason to be out of it
+    let reason = if status.is_empty() {
+        String::new()
+    } else {
+        form.reason.trim().to_string()
+    };
+
+    let actor = admin.audit_name();
+    if let Err(err) = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                Group::set_status(c, &group.id, &status, &reason)?;
+                let after = AuditStatus { status, reason };
+                AuditEntry::record_status(c, &group, &actor, AUDIT_STATUS, after)
+            })
+        })
+        .await
+    {
+        eprintln!("Failed to set group status: {err}");
+    }
+    live.group(&group_id);
     Redirect::to(format!("/scan/{group_id}/edit"))
 }
 
@@ -209,45 +452,50 @@ pub struct UpdateGroupForm {
 
 #[post("/<group_id>/edit/group/update", data = "<form>")]
 pub async fn update_group(
-    _admin: Admin, // Group timer edits are admin-only
+    admin: Admin, // Group timer edits are admin-only
     conn: DbConn,
+    live: &State<LiveUpdates>,
     group_id: String,
     form: Form<UpdateGroupForm>,
 ) -> Redirect {
-    // Handle start time
-    if form.clear_start.is_some() {
-        let gid = group_id.clone();
-        conn.run(move |c| Group::clear_start_time(c, &gid))
-            .await
-            .ok();
-    } else if let Some(dt) = &form.start_time {
-        if !dt.is_empty() {
-            if let Ok(start) = NaiveDateTime::parse_from_str(dt, "%Y-%m-%dT%H:%M") {
-                let gid = group_id.clone();
-                conn.run(move |c| Group::set_start_time(c, &gid, start))
-                    .await
-                    .ok();
-            }
-        }
-    }
+    let Some(group) = editable_group(&conn, &group_id).await else {
+        return Redirect::to(format!("/scan/{group_id}"));
+    };
 
-    // Handle finish time
-    if form.clear_finish.is_some() {
-        let gid = group_id.clone();
-        conn.run(move |c| Group::clear_finish_time(c, &gid))
-            .await
-            .ok();
-    } else if let Some(dt) = &form.finish_time {
-        if !dt.is_empty() {
-            if let Ok(finish) = NaiveDateTime::parse_from_str(dt, "%Y-%m-%dT%H:%M") {
-                let gid = group_id.clone();
-                conn.run(move |c| Group::set_finish_time(c, &gid, finish))
-                    .await
-                    .ok();
-            }
-        }
+    // Cleared when the clear checkbox is checked, unchanged when empty
+    let parse = |clear: &Option<String>, time: &Option<String>| match clear.is_some() {
+        true => Some(None),
+        false => time
+            .as_ref()
+            .filter(|dt| !dt.is_empty())
+            .and_then(|dt| NaiveDateTime::parse_from_str(dt, "%Y-%m-%dT%H:%M").ok())
+            .map(Some),
+    };
+    let start = parse(&form.clear_start, &form.start_time);
+    let finish = parse(&form.clear_finish, &form.finish_time);
+    let actor = admin.audit_name();
+
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                match start {
+                    Some(Some(start)) => Group::set_start_time(c, &group.id, start)?,
+                    Some(None) => Group::clear_start_time(c, &group.id)?,
+                    None => 0,
+                };
+                match finish {
+                    Some(Some(finish)) => Group::set_finish_time(c, &group.id, finish)?,
+                    Some(None) => Group::clear_finish_time(c, &group.id)?,
+                    None => 0,
+                };
+                audit_timer_change(c, &group, &actor, AUDIT_EDIT_TIMER)
+            })
+        })
+        .await;
+    if let Err(err) = result {
+        eprintln!("Failed to update group timer: {err}");
     }
-
+    live.group(&group_id);
     Redirect::to(format!("/scan/{group_id}/edit"))
 }
 
@@ -258,7 +506,9 @@ pub struct UpdateGroupDetailsForm {
     members: String,
     phone_number: String,
     group_number: i32,
-    route: String,
+    /// ID of the route, empty for no route
+    route_id: String,
+    category: String,
     start_timer: Option<String>,
 }
 
@@ -266,22 +516,16 @@ pub struct UpdateGroupDetailsForm {
 pub async fn update_group_details(
     cookies: &CookieJar<'_>,
     conn: DbConn,
+    live: &State<LiveUpdates>,
     group_id: String,
     form: Form<UpdateGroupDetailsForm>,
 ) -> Redirect {
-    let is_admin = auth::is_admin(cookies);
+    let admin_session = auth::get_auth_context(cookies).admin_session;
+    let is_admin = admin_session.is_some();
 
     // Check if group exists and whether it has started
-    let gid = group_id.clone();
-    let group = conn
-        .run(move |c| Group::get_by_id(c, &gid))
-        .await
-        .ok()
-        .flatten();
-
-    let group = match group {
-        Some(g) => g,
-        None => return Redirect::to(format!("/scan/{group_id}")),
+    let Some(group) = editable_group(&conn, &group_id).await else {
+        return Redirect::to(format!("/scan/{group_id}"));
     };
 
     // Only admin can edit details of started groups
@@ -295,30 +539,45 @@ pub async fn update_group_details(
     let members = form.members.clone();
     let phone_number = form.phone_number.clone();
     let group_number = form.group_number;
-    let route = form.route.clone();
-
-    conn.run(move |c| {
-        Group::update_details(
-            c,
-            &gid,
-            &name,
-            &scout_group,
-            &members,
-            &phone_number,
-            group_number,
-            &route,
-        )
-    })
-    .await
-    .ok();
-
+    let route_id = form.route_id.clone();
+    let category = form.category.trim().to_string();
     // If start_timer was requested (admin only), start the timer
-    if form.start_timer.is_some() && is_admin {
-        let gid = group_id.clone();
-        let now = Utc::now().naive_utc();
-        conn.run(move |c| Group::set_start_time(c, &gid, now))
-            .await
-            .ok();
+    let start_timer = form.start_timer.is_some() && is_admin;
+    let actor = admin_session
+        .as_deref()
+        .map(admin_actor)
+        .unwrap_or_default();
+    let unchanged = group.clone();
+
+    let result = conn
+        .run(move |c| {
+            c.transaction(|c| {
+                let route = HikeRoute::get_in_event(c, &unchanged.event_id, &route_id)?;
+                Group::update_details(
+                    c,
+                    &gid,
+                    &name,
+                    &scout_group,
+                    &members,
+                    &phone_number,
+                    group_number,
+                    route.as_ref(),
+                    &category,
+                )?;
+                if start_timer {
+                    Group::set_start_time(c, &gid, Utc::now().naive_utc())?;
+                    audit_timer_change(c, &unchanged, &actor, AUDIT_START)?;
+                }
+                Ok::<_, diesel::result::Error>(())
+            })
+        })
+        .await;
+    if let Err(err) = result {
+        eprintln!("Failed to update group details: {err}");
+    }
+    live.group(&group_id);
+
+    if start_timer {
         return Redirect::to(format!("/scan/{group_id}"));
     }
 
@@ -331,13 +590,100 @@ pub async fn update_group_details(
     }
 }
 
+/// Most minutes of penalty or bonus time an admin can give at once
+const MAX_ADJUSTMENT_MINUTES: i32 = 24 * 60;
+
+/// Most points an admin can give or take at once
+const MAX_ADJUSTMENT_POINTS: i32 = 10_000;
+
+#[derive(FromForm)]
+pub struct AdjustmentForm {
+    /// `penalty_time`, `bonus_time`, `bonus_points` or `penalty_points`
+    kind: String,
+    /// Minutes for time, points otherwise
+    amount: i32,
+    reason: String,
+}
+
+#[post("/<group_id>/edit/adjustments", data = "<form>")]
+pub async fn add_adjustment(
+    admin: Admin,
+    conn: DbConn,
+    live: &State<LiveUpdates>,
+    group_id: String,
+    form: Form<AdjustmentForm>,
+) -> Result<Redirect, Flash<Redirect>> {
+    if editable_group(&conn, &group_id).await.is_none() {
+        return Ok(Redirect::to(format!("/scan/{group_id}")));
+    }
+
+    let edit_page = || Redirect::to(format!("/scan/{group_id}/edit"));
+    // Kind, most units that can be entered and what one unit is worth
+    let (kind, max, unit) = match form.kind.as_str() {
+        "penalty_time" => (ADJUSTMENT_TIME, MAX_ADJUSTMENT_MINUTES, 60),
+        "bonus_time" => (ADJUSTMENT_TIME, MAX_ADJUSTMENT_MINUTES, -60),
+        "bonus_points" => (ADJUSTMENT_POINTS, MAX_ADJUSTMENT_POINTS, 1),
+        "penalty_points" => (ADJUSTMENT_POINTS, MAX_ADJUSTMENT_POINTS, -1),
+        _ => return Ok(edit_page()),
+    };
+    let amount = Some(form.amount)
+        .filter(|amount| (1..=max).contains(amount))
+        .and_then(|amount| amount.checked_mul(unit));
+    let Some(amount) = amount else {
+        let units = if kind == ADJUSTMENT_TIME {
+            "minuten"
+        } else {
+            "punten"
+        };
+        return Err(Flash::error(
+            edit_page(),
+            format!("Vul tussen 1 en {max} {units} in"),
+        ));
+    };
+    let reason = form.reason.trim().to_string();
+    if reason.is_empty() {
+        return Ok(edit_page());
+    }
+
+    let adjustment = Adjustment::new(group_id.clone(), kind, amount, reason, admin.audit_name());
+    if let Err(err) = conn.run(move |c| Adjustment::insert(c, &adjustment)).await {
+        eprintln!("Failed to add adjustment: {err}");
+    }
+    live.group(&group_id);
+    Ok(edit_page())
+}
+
+#[post("/<group_id>/edit/adjustments/<adjustment_id>/delete")]
+pub async fn delete_adjustment(
+    _admin: Admin,
+    conn: DbConn,
+    live: &State<LiveUpdates>,
+    group_id: String,
+    adjustment_id: String,
+) -> Redirect {
+    if editable_group(&conn, &group_id).await.is_none() {
+        return Redirect::to(format!("/scan/{group_id}"));
+    }
+
+    let gid = group_id.clone();
+    conn.run(move |c| Adjustment::delete(c, &gid, &adjustment_id))
+        .await
+        .ok();
+    live.group(&group_id);
+    Redirect::to(format!("/scan/{group_id}/edit"))
+}
+
 pub fn routes() -> Vec<Route> {
     routes![
         edit_page,
         update_scan,
         delete_scan,
         add_scan,
+        approve_scan_order,
         update_group,
-        update_group_details
+        update_group_status,
+        update_group_details,
+        add_adjustment,
+        delete_adjustment
     ]
 }
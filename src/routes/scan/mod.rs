@@ -1,21 +1,64 @@
 pub mod edit;
+pub mod offline;
 
-use chrono::Utc;
+use std::collections::HashMap;
+
+use chrono::{NaiveDateTime, TimeDelta, Utc};
 use rocket::form::Form;
 use rocket::http::CookieJar;
-use rocket::response::Redirect;
-use rocket::Route;
+use rocket::request::FlashMessage;
+use rocket::response::{Flash, Redirect};
+use rocket::{Route, State};
 use rocket_dyn_templates::{context, Template};
 use serde::Serialize;
+use uuid::Uuid;
 
+use crate::actions::{self, ActionError, Actor, ScanAction};
 use crate::auth::{get_auth_context, AnyAuth, CurrentPath};
+use crate::config::AppConfig;
 use crate::db::DbConn;
-use crate::models::{Group, NewGroup, NewScan, Post, Scan};
-use crate::stats::calculate_group_stats;
+use crate::event::ActiveEvent;
+use crate::live::LiveUpdates;
+use crate::models::{
+    audit_action_label, group_status_label, Adjustment, AuditEntry, Event, Group, HikeRoute,
+    NewGroup, Post, RouteMap, Scan, Score,
+};
+use crate::scoring::{self, FieldScore, ScoreSchemes};
+use crate::stats::{calculate_group_stats, AdjustmentInfo};
 
 #[derive(FromForm)]
 pub struct ScanForm {
     action: String,
+    /// Idempotency key rendered into the form, so a double submit is only recorded once. Every
+    /// form on the page has its own key.
+    request_id: Option<String>,
+}
+
+/// Scores of a group at a post that is scored
+#[derive(Serialize)]
+pub struct PostScore {
+    pub post: Post,
+    pub fields: Vec<FieldScore>,
+    pub points: i32,
+    pub max_points: i32,
+    /// Whether the current user can enter scores for this post
+    pub can_score: bool,
+}
+
+/// Last scan action of the current user on this group, which can still be undone
+#[derive(Serialize)]
+pub struct UndoAction {
+    pub entry: AuditEntry,
+    pub label: &'static str,
+    pub post: Option<Post>,
+    /// Until when the action can be undone
+    pub until: NaiveDateTime,
+}
+
+#[derive(FromForm)]
+pub struct UndoForm {
+    /// Audit log entry of the action, so only the action shown is undone
+    entry_id: String,
 }
 
 #[derive(Serialize)]
@@ -33,6 +76,20 @@ pub fn get_scout_groups() -> Vec<String> {
         .collect()
 }
 
+/// Routes defined for an event, to choose from in the group forms
+pub async fn get_routes(conn: &DbConn, event_id: String) -> Vec<HikeRoute> {
+    conn.run(move |c| HikeRoute::get_all(c, &event_id))
+        .await
+        .unwrap_or_default()
+}
+
+/// Categories already in use in an event, suggested in the group forms
+pub async fn get_category_names(conn: &DbConn, event_id: String) -> Vec<String> {
+    conn.run(move |c| Group::get_categories(c, &event_id))
+        .await
+        .unwrap_or_default()
+}
+
 fn get_next_admin_action(group: &Group) -> Option<NextAction> {
     // If group is finished, no next action
     if group.finish_time.is_some() {
@@ -58,13 +115,17 @@ fn get_next_admin_action(group: &Group) -> Option<NextAction> {
 pub async fn scan_page(
     cookies: &CookieJar<'_>,
     conn: DbConn,
+    active: ActiveEvent,
     group_id: String,
     path: CurrentPath,
+    flash: Option<FlashMessage<'_>>,
+    config: &State<AppConfig>,
 ) -> Template {
     let auth_ctx = get_auth_context(cookies);
     let is_admin = auth_ctx.is_admin;
     let is_post_holder = auth_ctx.is_post_holder;
     let holder_post_id = auth_ctx.holder_post_id;
+    let admin_session = auth_ctx.admin_session;
 
     let gid = group_id.clone();
     let group = conn
@@ -74,10 +135,21 @@ pub async fn scan_page(
         .flatten();
 
     let scout_groups = get_scout_groups();
+    let routes = get_routes(&conn, active.id()).await;
+    let category_names = get_category_names(&conn, active.id()).await;
 
+    // Reserved IDs have no details yet and are claimed through the new group form
     let group = match group {
-        Some(g) => g,
-        None => {
+        Some(g) if !g.reserved => g,
+        _ => {
+            // The ID of a group in the trash cannot be used for a new group
+            let gid = group_id.clone();
+            let deleted = conn
+                .run(move |c| Group::get_deleted_by_id(c, &gid))
+                .await
+                .ok()
+                .flatten()
+                .is_some();
             return Template::render(
                 "scan_new_group",
                 context! {
@@ -86,13 +158,30 @@ pub async fn scan_page(
                     is_post_holder: is_post_holder,
                     holder_post_id: holder_post_id,
                     scout_groups: scout_groups,
+                    routes: routes,
+                    category_names: category_names,
+                    deleted: deleted,
                 },
             );
         }
     };
 
+    // Only groups of the active event are scanned. Groups of a draft event can still be edited,
+    // those of an archived event can only be looked at.
+    let can_scan = group.event_id == active.0.id;
+    let other_event = if can_scan {
+        None
+    } else {
+        let event_id = group.event_id.clone();
+        conn.run(move |c| Event::get_by_id(c, &event_id))
+            .await
+            .ok()
+            .flatten()
+    };
+    let read_only = !can_scan && !other_event.as_ref().is_some_and(|e| e.is_editable());
+
     // If group hasn't started yet, show the edit form (same as new group form but pre-filled)
-    if group.start_time.is_none() {
+    if group.start_time.is_none() && !read_only {
         return Template::render(
             "scan_new_group",
             context! {
@@ -102,35 +191,117 @@ pub async fn scan_page(
                 is_post_holder: is_post_holder,
                 holder_post_id: holder_post_id,
                 scout_groups: scout_groups,
+                routes: routes,
+                category_names: category_names,
                 is_existing: true,
             },
         );
     }
 
     let gid = group_id.clone();
-    let posts = conn.run(Post::get_all).await.unwrap_or_default();
+    let route_id = group.route_id.clone();
+    let event_id = group.event_id.clone();
+    let posts = conn
+        .run(move |c| RouteMap::load(c, &event_id).map(|m| m.posts_for(route_id.as_deref())))
+        .await
+        .unwrap_or_default();
     let scans = conn
         .run(move |c| Scan::get_by_group(c, &gid))
         .await
         .unwrap_or_default();
+    let gid = group_id.clone();
+    let event_id = group.event_id.clone();
+    let (schemes, scores, adjustments) = conn
+        .run(move |c| {
+            let schemes = ScoreSchemes::load(c, &event_id)?;
+            let scores = Score::get_by_group(c, &gid)?;
+            let adjustments = Adjustment::get_by_group(c, &gid)?;
+            Ok::<_, diesel::result::Error>((schemes, scores, adjustments))
+        })
+        .await
+        .unwrap_or_default();
 
     let next_action = get_next_admin_action(&group);
-    let stats = calculate_group_stats(&group, &scans, posts.clone());
+    let stats = calculate_group_stats(&group, &scans, posts.clone(), &adjustments);
+    // Penalties and bonuses are only shown to admins
+    let adjustments: Vec<AdjustmentInfo> = if is_admin {
+        adjustments.into_iter().map(AdjustmentInfo::new).collect()
+    } else {
+        Vec::new()
+    };
+    // Admins see the scores of every post, post holders only those of their own post
+    let post_scores: Vec<PostScore> = posts
+        .iter()
+        .filter(|post| is_admin || holder_post_id.as_ref() == Some(&post.id))
+        .filter(|post| !schemes.fields(&post.id).is_empty())
+        .map(|post| {
+            let fields = schemes.field_scores(&post.id, &scores);
+            let arrived = scans.iter().any(|s| s.post_id == post.id);
+            PostScore {
+                post: post.clone(),
+                points: fields.iter().filter_map(|f| f.points).sum(),
+                max_points: fields.iter().map(|f| f.field.max_points).sum(),
+                fields,
+                can_score: arrived && can_scan,
+            }
+        })
+        .collect();
+    let holder_post_on_route = holder_post_id
+        .as_ref()
+        .is_some_and(|id| posts.iter().any(|p| &p.id == id));
     let emergency_info = std::env::var("EMERGENCY_INFO").ok();
+    let status_label = group_status_label(group.status());
+
+    let undo = if !can_scan || !(is_admin || is_post_holder) {
+        None
+    } else {
+        let actor = Actor {
+            is_admin,
+            post_id: holder_post_id.clone(),
+            admin_session,
+        };
+        let window = TimeDelta::minutes(config.undo_window);
+        let undo_group = group.clone();
+        conn.run(move |c| actions::undoable(c, &actor, &undo_group, Utc::now().naive_utc(), window))
+            .await
+            .unwrap_or_else(|err| {
+                eprintln!("Failed to load last action: {err}");
+                None
+            })
+            .map(|entry| UndoAction {
+                label: audit_action_label(&entry.action),
+                post: posts
+                    .iter()
+                    .find(|p| entry.post_id.as_ref() == Some(&p.id))
+                    .cloned(),
+                until: entry.created_at + window,
+                entry,
+            })
+    };
 
     Template::render(
         "scan",
         context! {
+            status_label: status_label,
             group: group,
             posts: posts,
             scans: scans,
             is_admin: is_admin,
             is_post_holder: is_post_holder,
             holder_post_id: holder_post_id,
+            holder_post_on_route: holder_post_on_route,
             next_action: next_action,
+            request_id: Uuid::new_v4().to_string(),
             stats: stats,
+            post_scores: post_scores,
+            adjustments: adjustments,
             emergency_info: emergency_info,
-            current_path: path.0
+            read_only: read_only,
+            can_scan: can_scan,
+            other_event: other_event,
+            current_path: path.0,
+            error: flash.map(|f| f.message().to_string()),
+            undo: undo,
         },
     )
 }
@@ -139,98 +310,129 @@ pub async fn scan_page(
 pub async fn record_scan(
     auth: AnyAuth,
     conn: DbConn,
+    live: &State<LiveUpdates>,
     group_id: String,
     form: Form<ScanForm>,
-) -> Redirect {
-    let gid = group_id.clone();
+) -> Result<Redirect, Flash<Redirect>> {
+    let Some(action) = ScanAction::parse(&form.action) else {
+        return Ok(Redirect::to(format!("/scan/{group_id}")));
+    };
+    let actor = Actor {
+        is_admin: auth.is_admin,
+        post_id: auth.post_id,
+        admin_session: auth.admin_session,
+    };
 
-    // Verify group exists
-    let group_exists = conn
-        .run(move |c| Group::get_by_id(c, &gid))
-        .await
-        .ok()
-        .flatten()
-        .is_some();
+    let gid = group_id.clone();
+    let now = Utc::now().naive_utc();
+    let performed = action.clone();
+    let request_id = form.request_id.clone().filter(|id| !id.is_empty());
+    let result = conn
+        .run(move |c| {
+            actions::perform_once(c, &actor, request_id.as_deref(), &gid, &performed, now)
+        })
+        .await;
 
-    if !group_exists {
-        return Redirect::to("/");
+    match result {
+        Ok(outcome) => {
+            if !outcome.replayed {
+                live.action(&group_id, &action);
+            }
+            Ok(Redirect::to(format!("/scan/{group_id}")))
+        }
+        Err(ActionError::GroupNotFound) => Ok(Redirect::to("/")),
+        Err(ActionError::OutOfOrder(message) | ActionError::Conflict(message)) => Err(
+            Flash::error(Redirect::to(format!("/scan/{group_id}")), message),
+        ),
+        Err(_) => Ok(Redirect::to(format!("/scan/{group_id}"))),
     }
+}
 
-    let action = form.action.clone();
+/// Revert the last scan action of the current user on a group, e.g. after tapping the wrong
+/// button or scanning the wrong group card
+#[post("/<group_id>/undo", data = "<form>")]
+pub async fn undo_scan(
+    auth: AnyAuth,
+    conn: DbConn,
+    live: &State<LiveUpdates>,
+    config: &State<AppConfig>,
+    group_id: String,
+    form: Form<UndoForm>,
+) -> Result<Redirect, Flash<Redirect>> {
+    let actor = Actor {
+        is_admin: auth.is_admin,
+        post_id: auth.post_id,
+        admin_session: auth.admin_session,
+    };
 
-    // Handle start timer (admin only)
-    if action == "__START_TIMER__" {
-        if !auth.is_admin {
-            return Redirect::to(format!("/scan/{group_id}"));
-        }
-        let gid = group_id.clone();
-        let now = Utc::now().naive_utc();
-        conn.run(move |c| Group::set_start_time(c, &gid, now))
-            .await
-            .ok();
-        return Redirect::to(format!("/scan/{group_id}"));
-    }
+    let gid = group_id.clone();
+    let entry_id = form.entry_id.clone();
+    let now = Utc::now().naive_utc();
+    let window = TimeDelta::minutes(config.undo_window);
+    let result = conn
+        .run(move |c| actions::undo(c, &actor, &gid, &entry_id, now, window))
+        .await;
 
-    // Handle stop timer (admin only)
-    if action == "__STOP_TIMER__" {
-        if !auth.is_admin {
-            return Redirect::to(format!("/scan/{group_id}"));
+    match result {
+        Ok(action) => {
+            live.action(&group_id, &action);
+            Ok(Redirect::to(format!("/scan/{group_id}")))
         }
-        let gid = group_id.clone();
-        let now = Utc::now().naive_utc();
-        conn.run(move |c| Group::set_finish_time(c, &gid, now))
-            .await
-            .ok();
-        return Redirect::to(format!("/scan/{group_id}"));
+        Err(ActionError::Conflict(message)) => Err(Flash::error(
+            Redirect::to(format!("/scan/{group_id}")),
+            message,
+        )),
+        Err(_) => Ok(Redirect::to(format!("/scan/{group_id}"))),
     }
+}
+
+#[derive(FromForm)]
+pub struct ScoresForm {
+    /// Points per criterion ID, empty to clear a score
+    points: HashMap<String, String>,
+}
 
-    // Handle arrive at post
-    if let Some(post_id) = action.strip_prefix("ARRIVE_") {
-        // Post holders can only scan for their assigned post
-        if let Some(ref holder_post_id) = auth.post_id {
-            if holder_post_id != post_id {
-                return Redirect::to(format!("/scan/{group_id}"));
+#[post("/<group_id>/score/<post_id>", data = "<form>")]
+pub async fn record_scores(
+    auth: AnyAuth,
+    conn: DbConn,
+    live: &State<LiveUpdates>,
+    group_id: String,
+    post_id: String,
+    form: Form<ScoresForm>,
+) -> Redirect {
+    let actor = Actor {
+        is_admin: auth.is_admin,
+        post_id: auth.post_id,
+        admin_session: auth.admin_session,
+    };
+    let points: Vec<(String, Option<i32>)> = form
+        .points
+        .iter()
+        .filter_map(|(criterion_id, points)| {
+            let points = points.trim();
+            if points.is_empty() {
+                return Some((criterion_id.clone(), None));
             }
-        }
-        let gid = group_id.clone();
-        let post_id = post_id.to_string();
-        conn.run(move |c| {
-            let scan = NewScan::new(gid, post_id);
-            Scan::insert(c, scan)
+            // Ignore what is not a number, instead of clearing the score
+            points
+                .parse::<i32>()
+                .ok()
+                .map(|p| (criterion_id.clone(), Some(p)))
         })
-        .await
-        .ok();
-        return Redirect::to(format!("/scan/{group_id}"));
-    }
+        .collect();
 
-    // Handle leave post
-    if let Some(post_id) = action.strip_prefix("LEAVE_") {
-        // Post holders can only scan for their assigned post
-        if let Some(ref holder_post_id) = auth.post_id {
-            if holder_post_id != post_id {
-                return Redirect::to(format!("/scan/{group_id}"));
-            }
-        }
-        let gid = group_id.clone();
-        let post_id = post_id.to_string();
-        let existing_scan = conn
-            .run(move |c| Scan::get_by_group_and_post(c, &gid, &post_id))
-            .await
-            .ok()
-            .flatten();
-
-        if let Some(scan) = existing_scan {
-            if scan.departure_time.is_none() {
-                let scan_id = scan.id.clone();
-                let now = Utc::now().naive_utc();
-                conn.run(move |c| Scan::set_departure_time(c, &scan_id, now))
-                    .await
-                    .ok();
-            }
-        }
-        return Redirect::to(format!("/scan/{group_id}"));
+    let gid = group_id.clone();
+    let pid = post_id.clone();
+    let now = Utc::now().naive_utc();
+    let result = conn
+        .run(move |c| scoring::record_scores(c, &actor, &gid, &pid, points, now))
+        .await;
+    match result {
+        Ok(()) => live.scan(&group_id, Some(&post_id)),
+        Err(ActionError::Database(err)) => eprintln!("Failed to record scores: {err}"),
+        Err(_) => {}
     }
-
     Redirect::to(format!("/scan/{group_id}"))
 }
 
@@ -241,12 +443,16 @@ pub struct NewGroupForm {
     members: String,
     phone_number: String,
     group_number: i32,
-    route: String,
+    /// ID of the route, empty for no route
+    route_id: String,
+    category: String,
 }
 
 #[post("/<group_id>/create", data = "<form>")]
 pub async fn create_group_from_scan(
     conn: DbConn,
+    event: ActiveEvent,
+    live: &State<LiveUpdates>,
     group_id: String,
     form: Form<NewGroupForm>,
 ) -> Redirect {
@@ -255,28 +461,43 @@ pub async fn create_group_from_scan(
     let members = form.members.clone();
     let phone_number = form.phone_number.clone();
     let group_number = form.group_number;
-    let route = form.route.clone();
+    let route_id = form.route_id.clone();
+    let category = form.category.trim().to_string();
     let gid = group_id.clone();
+    let event_id = event.id();
     let result = conn
         .run(move |c| {
-            let group = NewGroup::new_with_id(
+            let route = HikeRoute::get_in_event(c, &event_id, &route_id)?;
+            let mut group = NewGroup::new_with_id(
+                event_id,
                 gid,
                 name,
                 scout_group,
                 members,
                 phone_number,
                 group_number,
-                route,
             );
-            Group::insert(c, group)
+            group.set_route(route.as_ref());
+            group.category = category;
+            match Group::get_by_id(c, &group.id)? {
+                Some(existing) if existing.reserved => Group::claim(c, group),
+                _ => Group::insert(c, group),
+            }
         })
         .await;
-    if let Err(err) = result {
-        eprintln!("Failed to create group from scan: {err}");
+    match result {
+        Ok(_) => live.group(&group_id),
+        Err(err) => eprintln!("Failed to create group from scan: {err}"),
     }
     Redirect::to(format!("/scan/{group_id}"))
 }
 
 pub fn routes() -> Vec<Route> {
-    routes![scan_page, record_scan, create_group_from_scan]
+    routes![
+        scan_page,
+        record_scan,
+        undo_scan,
+        record_scores,
+        create_group_from_scan
+    ]
 }
@@ -0,0 +1,93 @@
+use chrono::Utc;
+use rocket::http::{CookieJar, Status};
+use rocket::serde::json::Json;
+use rocket::{Route, State};
+use rocket_dyn_templates::{context, Template};
+use serde::{Deserialize, Serialize};
+
+use crate::actions::{Actor, ScanAction};
+use crate::auth::{get_auth_context, ApiAuth};
+use crate::db::DbConn;
+use crate::event::ActiveEvent;
+use crate::live::LiveUpdates;
+use crate::models::Post;
+use crate::sync::{self, QueuedAction, SyncResult, SyncStatus};
+
+/// Generic scan page, cached by the service worker and shown for any group while offline
+#[get("/offline")]
+pub async fn offline_page(cookies: &CookieJar<'_>, conn: DbConn, event: ActiveEvent) -> Template {
+    let auth_ctx = get_auth_context(cookies);
+    let holder_post_id = auth_ctx.holder_post_id.clone();
+    let event_id = event.id();
+    let posts: Vec<Post> = conn
+        .run(move |c| Post::get_all(c, &event_id))
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|p| auth_ctx.is_admin || holder_post_id.as_ref() == Some(&p.id))
+        .collect();
+
+    Template::render(
+        "scan_offline",
+        context! {
+            posts: posts,
+            is_admin: auth_ctx.is_admin,
+            is_post_holder: auth_ctx.is_post_holder,
+            holder_post_id: holder_post_id,
+        },
+    )
+}
+
+#[derive(Deserialize)]
+pub struct SyncRequest {
+    actions: Vec<QueuedAction>,
+}
+
+#[derive(Serialize)]
+pub struct SyncResponse {
+    results: Vec<SyncResult>,
+}
+
+/// Replay actions queued while offline, in the order they were performed
+#[post("/sync", format = "json", data = "<request>")]
+pub async fn sync_actions(
+    auth: ApiAuth,
+    conn: DbConn,
+    live: &State<LiveUpdates>,
+    request: Json<SyncRequest>,
+) -> Result<Json<SyncResponse>, Status> {
+    let actor = Actor {
+        is_admin: auth.is_admin,
+        post_id: auth.post_id,
+        admin_session: auth.admin_session,
+    };
+    let actions = request.into_inner().actions;
+    let now = Utc::now().naive_utc();
+    let results = conn
+        .run(move |c| {
+            actions
+                .into_iter()
+                .map(|queued| sync::apply(c, &actor, queued, now))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .await
+        .map_err(|err| {
+            eprintln!("Failed to sync offline actions: {err}");
+            Status::InternalServerError
+        })?;
+
+    for result in results
+        .iter()
+        .filter(|r| r.status == SyncStatus::Applied && !r.replayed)
+    {
+        if let Some(action) = ScanAction::parse(&result.action) {
+            live.action(&result.group_id, &action);
+        }
+    }
+
+    Ok(Json(SyncResponse { results }))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![offline_page, sync_actions]
+}
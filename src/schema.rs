@@ -1,5 +1,87 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    adjustments (id) {
+        id -> Text,
+        group_id -> Text,
+        kind -> Text,
+        amount -> Integer,
+        reason -> Text,
+        author -> Text,
+        created_at -> Timestamp,
+        scan_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    api_tokens (id) {
+        id -> Text,
+        name -> Text,
+        token_hash -> Text,
+        post_id -> Nullable<Text>,
+        created_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    audit_log (id) {
+        id -> Text,
+        event_id -> Text,
+        group_id -> Text,
+        post_id -> Nullable<Text>,
+        actor -> Text,
+        action -> Text,
+        before -> Nullable<Text>,
+        after -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    client_actions (client_id) {
+        client_id -> Text,
+        group_id -> Text,
+        action -> Text,
+        status -> Text,
+        message -> Nullable<Text>,
+        performed_at -> Timestamp,
+        received_at -> Timestamp,
+        actor -> Text,
+    }
+}
+
+diesel::table! {
+    duplicate_scans (id) {
+        id -> Text,
+        group_id -> Text,
+        post_id -> Text,
+        arrival_time -> Timestamp,
+        departure_time -> Nullable<Timestamp>,
+        kept_scan_id -> Text,
+        detected_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    events (id) {
+        id -> Text,
+        name -> Text,
+        status -> Text,
+        created_at -> Timestamp,
+        ranking_criteria -> Text,
+        min_idle_credit -> Integer,
+        max_post_time -> Nullable<Integer>,
+        public_ranking -> Bool,
+        public_fields -> Text,
+        public_freeze_at -> Nullable<Timestamp>,
+        public_revealed -> Bool,
+        public_snapshot -> Nullable<Text>,
+        scan_order -> Text,
+        max_course_time -> Nullable<Integer>,
+    }
+}
+
 diesel::table! {
     groups (id) {
         id -> Text,
@@ -12,6 +94,23 @@ diesel::table! {
         created_at -> Timestamp,
         group_number -> Integer,
         route -> Text,
+        reserved -> Bool,
+        route_id -> Nullable<Text>,
+        event_id -> Text,
+        category -> Text,
+        status -> Text,
+        status_reason -> Text,
+        status_override -> Bool,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    hike_routes (id) {
+        id -> Text,
+        event_id -> Text,
+        name -> Text,
+        created_at -> Timestamp,
     }
 }
 
@@ -22,6 +121,31 @@ diesel::table! {
         post_order -> Integer,
         created_at -> Timestamp,
         password_hash -> Nullable<Text>,
+        event_id -> Text,
+        max_points -> Nullable<Integer>,
+        opens_at -> Nullable<Timestamp>,
+        closes_at -> Nullable<Timestamp>,
+        cutoff_at -> Nullable<Timestamp>,
+        hours_penalty -> Nullable<Integer>,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    post_criteria (id) {
+        id -> Text,
+        post_id -> Text,
+        name -> Text,
+        max_points -> Integer,
+        position -> Integer,
+    }
+}
+
+diesel::table! {
+    route_posts (route_id, post_id) {
+        route_id -> Text,
+        post_id -> Text,
+        position -> Integer,
     }
 }
 
@@ -32,10 +156,47 @@ diesel::table! {
         post_id -> Text,
         arrival_time -> Timestamp,
         departure_time -> Nullable<Timestamp>,
+        out_of_order -> Bool,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    scores (group_id, post_id, criterion_id) {
+        group_id -> Text,
+        post_id -> Text,
+        criterion_id -> Text,
+        points -> Integer,
+        judged_by -> Nullable<Text>,
+        updated_at -> Timestamp,
     }
 }
 
+diesel::joinable!(adjustments -> groups (group_id));
+diesel::joinable!(api_tokens -> posts (post_id));
+diesel::joinable!(groups -> events (event_id));
+diesel::joinable!(hike_routes -> events (event_id));
+diesel::joinable!(posts -> events (event_id));
+diesel::joinable!(post_criteria -> posts (post_id));
+diesel::joinable!(route_posts -> hike_routes (route_id));
+diesel::joinable!(route_posts -> posts (post_id));
 diesel::joinable!(scans -> groups (group_id));
 diesel::joinable!(scans -> posts (post_id));
+diesel::joinable!(scores -> groups (group_id));
+diesel::joinable!(scores -> posts (post_id));
 
-diesel::allow_tables_to_appear_in_same_query!(groups, posts, scans,);
+diesel::allow_tables_to_appear_in_same_query!(
+    adjustments,
+    api_tokens,
+    audit_log,
+    client_actions,
+    duplicate_scans,
+    events,
+    groups,
+    hike_routes,
+    post_criteria,
+    posts,
+    route_posts,
+    scans,
+    scores,
+);
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::Serialize;
+
+use crate::actions::{ActionError, Actor};
+use crate::models::{Event, Group, Post, PostCriterion, Scan, Score, TOTAL_SCORE};
+
+/// Something to score at a post: one of its criteria, or the post as a whole.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoreField {
+    /// Criterion ID, or [`TOTAL_SCORE`]
+    pub criterion_id: String,
+    pub name: String,
+    pub max_points: i32,
+}
+
+/// A score field with the points a group got for it, if scored yet
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldScore {
+    pub field: ScoreField,
+    pub points: Option<i32>,
+}
+
+/// Score fields per post of an event.
+///
+/// A post with criteria is scored per criterion, a post with only `max_points` gets a single
+/// score, other posts are not scored.
+#[derive(Debug, Clone, Default)]
+pub struct ScoreSchemes {
+    fields_by_post: HashMap<String, Vec<ScoreField>>,
+}
+
+impl ScoreSchemes {
+    pub fn load(conn: &mut SqliteConnection, event_id: &str) -> QueryResult<ScoreSchemes> {
+        let posts = Post::get_all(conn, event_id)?;
+        let criteria = PostCriterion::get_by_event(conn, event_id)?;
+        Ok(Self::new(&posts, &criteria))
+    }
+
+    pub fn new(posts: &[Post], criteria: &[PostCriterion]) -> ScoreSchemes {
+        let fields_by_post = posts
+            .iter()
+            .map(|post| {
+                let mut fields: Vec<ScoreField> = criteria
+                    .iter()
+                    .filter(|c| c.post_id == post.id)
+                    .map(|c| ScoreField {
+                        criterion_id: c.id.clone(),
+                        name: c.name.clone(),
+                        max_points: c.max_points,
+                    })
+                    .collect();
+                if fields.is_empty() {
+                    fields.extend(post.max_points.map(|max_points| ScoreField {
+                        criterion_id: TOTAL_SCORE.to_string(),
+                        name: "Punten".to_string(),
+                        max_points,
+                    }));
+                }
+                (post.id.clone(), fields)
+            })
+            .collect();
+        ScoreSchemes { fields_by_post }
+    }
+
+    pub fn fields(&self, post_id: &str) -> &[ScoreField] {
+        self.fields_by_post
+            .get(post_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn field(&self, post_id: &str, criterion_id: &str) -> Option<&ScoreField> {
+        self.fields(post_id)
+            .iter()
+            .find(|f| f.criterion_id == criterion_id)
+    }
+
+    /// Points that can be scored on these posts
+    pub fn max_points(&self, posts: &[Post]) -> i32 {
+        posts
+            .iter()
+            .flat_map(|p| self.fields(&p.id))
+            .map(|f| f.max_points)
+            .sum()
+    }
+
+    /// Total of the scores of a group. Scores for criteria that were removed since do not count.
+    pub fn points(&self, scores: &[Score]) -> i32 {
+        scores
+            .iter()
+            .filter(|s| self.field(&s.post_id, &s.criterion_id).is_some())
+            .map(|s| s.points)
+            .sum()
+    }
+
+    /// Fields of a post with the scores a group got for them
+    pub fn field_scores(&self, post_id: &str, scores: &[Score]) -> Vec<FieldScore> {
+        self.fields(post_id)
+            .iter()
+            .map(|field| FieldScore {
+                field: field.clone(),
+                points: scores
+                    .iter()
+                    .find(|s| s.post_id == post_id && s.criterion_id == field.criterion_id)
+                    .map(|s| s.points),
+            })
+            .collect()
+    }
+}
+
+/// Enter the points of a group at a post, by criterion ID. `None` clears a score.
+///
+/// Points are clamped to what the criterion allows. A group can only be scored at a post it
+/// arrived at.
+pub fn record_scores(
+    conn: &mut SqliteConnection,
+    actor: &Actor,
+    group_id: &str,
+    post_id: &str,
+    points: Vec<(String, Option<i32>)>,
+    now: NaiveDateTime,
+) -> Result<(), ActionError> {
+    conn.transaction(|conn| {
+        let group = Group::get_by_id(conn, group_id)?
+            .filter(|g| !g.reserved)
+            .ok_or(ActionError::GroupNotFound)?;
+        if group.event_id != Event::get_active(conn)?.id {
+            return Err(ActionError::EventClosed);
+        }
+        if !actor.may_score(post_id) {
+            return Err(ActionError::Forbidden);
+        }
+        Post::get_by_id(conn, post_id)?
+            .filter(|p| p.event_id == group.event_id)
+            .ok_or(ActionError::PostNotFound)?;
+        if Scan::get_by_group_and_post(conn, group_id, post_id)?.is_none() {
+            return Err(ActionError::Conflict(
+                "Groep is nog niet aangekomen bij deze post".to_string(),
+            ));
+        }
+
+        let schemes = ScoreSchemes::load(conn, &group.event_id)?;
+        for (criterion_id, points) in points {
+            let Some(field) = schemes.field(post_id, &criterion_id) else {
+                continue;
+            };
+            match points {
+                Some(points) => {
+                    Score::set(
+                        conn,
+                        &Score {
+                            group_id: group_id.to_string(),
+                            post_id: post_id.to_string(),
+                            criterion_id,
+                            points: points.clamp(0, field.max_points),
+                            judged_by: actor.post_id.clone(),
+                            updated_at: now,
+                        },
+                    )?;
+                }
+                None => {
+                    Score::clear(conn, group_id, post_id, &criterion_id)?;
+                }
+            }
+        }
+        Ok(())
+    })
+}
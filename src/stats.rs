@@ -1,28 +1,246 @@
 use chrono::{NaiveDateTime, TimeDelta, Utc};
 use serde::Serialize;
 
-use crate::models::{Group, Post, Scan};
+use crate::models::{
+    is_admin_actor, Adjustment, Group, Post, Scan, ADJUSTMENT_POINTS, ADJUSTMENT_TIME,
+};
 
 #[derive(Serialize, Clone)]
 pub struct PostScanInfo {
     pub post: Post,
     pub scan: Option<Scan>,
+    #[serde(with = "secs::option")]
     pub idle_time: Option<TimeDelta>,
+    /// Why the group arrived outside the opening hours of the post, if it did
+    pub hours_warning: Option<String>,
 }
 
 #[derive(Serialize)]
 pub struct GroupStats {
+    #[serde(with = "secs::option")]
     pub total_time: Option<TimeDelta>,
+    #[serde(with = "secs::option")]
     pub walking_time: Option<TimeDelta>,
+    #[serde(with = "secs")]
     pub idle_time: TimeDelta,
+    /// Sum of time penalties and bonuses, positive adds time
+    #[serde(with = "secs")]
+    pub time_adjustment: TimeDelta,
+    /// Sum of points bonuses and penalties
+    pub points_adjustment: i32,
+    /// Total time including time adjustments
+    #[serde(with = "secs::option")]
+    pub adjusted_total_time: Option<TimeDelta>,
+    /// Walking time including time adjustments
+    #[serde(with = "secs::option")]
+    pub adjusted_walking_time: Option<TimeDelta>,
     pub post_scans: Vec<PostScanInfo>,
+    /// Walking time from the start to the first post, between posts and to the finish
+    pub legs: Vec<LegSplit>,
+}
+
+/// Walking time between two consecutive points of the hike
+#[derive(Serialize, Clone)]
+pub struct LegSplit {
+    /// Post the leg starts at, empty for the start
+    pub from: Option<Post>,
+    /// Post the leg ends at, empty for the finish
+    pub to: Option<Post>,
+    /// Empty when the departure at the start of the leg was not scanned
+    #[serde(with = "secs::option")]
+    pub walking_time: Option<TimeDelta>,
+}
+
+/// Legs a group walked, following the posts in the order they were visited. The last leg to the
+/// finish is only there once the group finished.
+fn leg_splits(group: &Group, post_scans: &[PostScanInfo]) -> Vec<LegSplit> {
+    if group.start_time.is_none() {
+        return Vec::new();
+    }
+
+    let mut visited: Vec<(&Post, &Scan)> = post_scans
+        .iter()
+        .filter_map(|ps| ps.scan.as_ref().map(|scan| (&ps.post, scan)))
+        .collect();
+    visited.sort_by_key(|(_, scan)| scan.arrival_time);
+
+    let mut legs = Vec::new();
+    let mut from: Option<&Post> = None;
+    let mut left_at = group.start_time;
+    for (post, scan) in visited {
+        legs.push(LegSplit {
+            from: from.cloned(),
+            to: Some(post.clone()),
+            walking_time: left_at.map(|t| scan.arrival_time - t),
+        });
+        from = Some(post);
+        left_at = scan.departure_time;
+    }
+    if let Some(finish) = group.finish_time {
+        legs.push(LegSplit {
+            from: from.cloned(),
+            to: None,
+            walking_time: left_at.map(|t| finish - t),
+        });
+    }
+    legs
+}
+
+/// Walking time of one group on a leg
+#[derive(Serialize, Clone)]
+pub struct LegTime {
+    pub group: Group,
+    #[serde(with = "secs")]
+    pub walking_time: TimeDelta,
+}
+
+/// Walking times of all groups on a leg
+#[derive(Serialize)]
+pub struct LegComparison {
+    pub from: Option<Post>,
+    pub to: Option<Post>,
+    pub fastest: LegTime,
+    #[serde(with = "secs")]
+    pub median: TimeDelta,
+    pub slowest: LegTime,
+    /// Number of groups with a time on this leg
+    pub group_count: usize,
+}
+
+/// Fastest, median and slowest walking time per leg over all groups, legs from the start first
+/// and to the finish last.
+pub fn compare_legs(groups: Vec<(Group, Vec<LegSplit>)>) -> Vec<LegComparison> {
+    let post_id = |post: &Option<Post>| post.as_ref().map(|p| p.id.clone());
+    let mut legs: Vec<(Option<Post>, Option<Post>, Vec<LegTime>)> = Vec::new();
+    for (group, splits) in groups {
+        for split in splits {
+            let Some(walking_time) = split.walking_time else {
+                continue;
+            };
+            let time = LegTime {
+                group: group.clone(),
+                walking_time,
+            };
+            match legs.iter_mut().find(|(from, to, _)| {
+                post_id(from) == post_id(&split.from) && post_id(to) == post_id(&split.to)
+            }) {
+                Some((_, _, times)) => times.push(time),
+                None => legs.push((split.from, split.to, vec![time])),
+            }
+        }
+    }
+
+    legs.sort_by_key(|(from, to, _)| {
+        (
+            from.as_ref().map(|p| p.post_order).unwrap_or(i32::MIN),
+            to.as_ref().map(|p| p.post_order).unwrap_or(i32::MAX),
+        )
+    });
+    legs.into_iter()
+        .map(|(from, to, mut times)| {
+            times.sort_by_key(|t| t.walking_time);
+            let middle = times.len() / 2;
+            let median = if times.len() % 2 == 0 {
+                (times[middle - 1].walking_time + times[middle].walking_time) / 2
+            } else {
+                times[middle].walking_time
+            };
+            LegComparison {
+                from,
+                to,
+                fastest: times[0].clone(),
+                median,
+                slowest: times[times.len() - 1].clone(),
+                group_count: times.len(),
+            }
+        })
+        .collect()
+}
+
+/// Serialize durations as whole seconds, for templates and the JSON API.
+///
+/// Use with `#[serde(with = "secs")]` or `#[serde(with = "secs::option")]`.
+pub mod secs {
+    use chrono::TimeDelta;
+    use serde::Serializer;
+
+    pub fn serialize<S: Serializer>(delta: &TimeDelta, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(delta.num_seconds())
+    }
+
+    pub mod option {
+        use chrono::TimeDelta;
+        use serde::Serializer;
+
+        pub fn serialize<S: Serializer>(
+            delta: &Option<TimeDelta>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match delta {
+                Some(delta) => super::serialize(delta, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+    }
+}
+
+/// Adjustment with a readable description of its amount, for templates
+#[derive(Serialize, Clone)]
+pub struct AdjustmentInfo {
+    pub adjustment: Adjustment,
+    pub label: String,
+    pub amount: String,
+    /// Who gave the adjustment, admins are not told apart
+    pub author: String,
+}
+
+impl AdjustmentInfo {
+    pub fn new(adjustment: Adjustment) -> Self {
+        let amount = adjustment.amount;
+        let (label, amount) = if adjustment.kind == ADJUSTMENT_TIME {
+            let label = if amount >= 0 {
+                "Straftijd"
+            } else {
+                "Tijdbonus"
+            };
+            let sign = if amount >= 0 { '+' } else { '-' };
+            let (minutes, seconds) = (amount.unsigned_abs() / 60, amount.unsigned_abs() % 60);
+            let amount = match seconds {
+                0 => format!("{sign}{minutes} min"),
+                _ => format!("{sign}{minutes} min {seconds} s"),
+            };
+            (label, amount)
+        } else {
+            let label = if amount >= 0 {
+                "Bonuspunten"
+            } else {
+                "Puntenaftrek"
+            };
+            (label, format!("{amount:+}"))
+        };
+        let author = match adjustment.author.as_str() {
+            author if is_admin_actor(author) => "Admin".to_string(),
+            author => author.to_string(),
+        };
+        AdjustmentInfo {
+            adjustment,
+            label: label.to_string(),
+            amount,
+            author,
+        }
+    }
 }
 
 pub fn now_naive() -> NaiveDateTime {
     Utc::now().naive_utc()
 }
 
-pub fn calculate_group_stats(group: &Group, scans: &[Scan], posts: Vec<Post>) -> GroupStats {
+pub fn calculate_group_stats(
+    group: &Group,
+    scans: &[Scan],
+    posts: Vec<Post>,
+    adjustments: &[Adjustment],
+) -> GroupStats {
     let post_scans: Vec<PostScanInfo> = posts
         .into_iter()
         .map(|post| {
@@ -30,10 +248,14 @@ pub fn calculate_group_stats(group: &Group, scans: &[Scan], posts: Vec<Post>) ->
             let idle_time = scan
                 .as_ref()
                 .map(|s| s.departure_time.unwrap_or_else(now_naive) - s.arrival_time);
+            let hours_warning = scan
+                .as_ref()
+                .and_then(|s| post.hours_warning(s.arrival_time));
             PostScanInfo {
                 post,
                 scan,
                 idle_time,
+                hours_warning,
             }
         })
         .collect();
@@ -46,18 +268,38 @@ pub fn calculate_group_stats(group: &Group, scans: &[Scan], posts: Vec<Post>) ->
 
     let walking_time = total_time.map(|t| t - idle_time);
 
+    let time_adjustment = TimeDelta::seconds(
+        adjustments
+            .iter()
+            .filter(|a| a.kind == ADJUSTMENT_TIME)
+            .map(|a| a.amount as i64)
+            .sum(),
+    );
+    let points_adjustment = adjustments
+        .iter()
+        .filter(|a| a.kind == ADJUSTMENT_POINTS)
+        .map(|a| a.amount)
+        .sum();
+
     GroupStats {
         total_time,
         walking_time,
         idle_time,
+        time_adjustment,
+        points_adjustment,
+        adjusted_total_time: total_time.map(|t| t + time_adjustment),
+        adjusted_walking_time: walking_time.map(|t| t + time_adjustment),
+        legs: leg_splits(group, &post_scans),
         post_scans,
     }
 }
 
 pub fn format_duration(delta: TimeDelta) -> String {
-    let total_secs = delta.num_seconds();
+    // Negative after subtracting bonuses
+    let sign = if delta < TimeDelta::zero() { "-" } else { "" };
+    let total_secs = delta.num_seconds().abs();
     let hours = total_secs / 3600;
     let minutes = (total_secs % 3600) / 60;
     let seconds = total_secs % 60;
-    format!("{hours:02}:{minutes:02}:{seconds:02}")
+    format!("{sign}{hours:02}:{minutes:02}:{seconds:02}")
 }
@@ -0,0 +1,303 @@
+use chrono::{DateTime, NaiveDateTime};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+
+use crate::actions::{self, ActionError, Actor, ScanAction};
+use crate::models::ClientAction;
+
+/// Scan action queued on a device while it was offline.
+#[derive(Debug, Deserialize)]
+pub struct QueuedAction {
+    /// Unique ID generated by the client, replaying the same ID has no further effect
+    pub client_id: String,
+    pub group_id: String,
+    /// Action as submitted by the scan page form, e.g. `ARRIVE_<post_id>`
+    pub action: String,
+    /// Device time of the action in milliseconds since the Unix epoch
+    pub at: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStatus {
+    Applied,
+    /// Contradicts what was recorded in the meantime, e.g. by another device
+    Conflict,
+    /// Not allowed or not valid, retrying will not help
+    Rejected,
+}
+
+impl SyncStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SyncStatus::Applied => "applied",
+            SyncStatus::Conflict => "conflict",
+            SyncStatus::Rejected => "rejected",
+        }
+    }
+
+    fn parse(status: &str) -> Self {
+        match status {
+            "applied" => SyncStatus::Applied,
+            "conflict" => SyncStatus::Conflict,
+            _ => SyncStatus::Rejected,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncResult {
+    pub client_id: String,
+    pub group_id: String,
+    pub action: String,
+    pub status: SyncStatus,
+    pub message: Option<String>,
+    /// Whether this client ID was already processed by an earlier sync
+    pub replayed: bool,
+}
+
+impl SyncResult {
+    fn from_client_action(action: ClientAction, replayed: bool) -> Self {
+        SyncResult {
+            client_id: action.client_id,
+            group_id: action.group_id,
+            action: action.action,
+            status: SyncStatus::parse(&action.status),
+            message: action.message,
+            replayed,
+        }
+    }
+}
+
+/// Replay a queued action once, keeping its device time. A client ID that was already used for
+/// another action, group or actor is a conflict.
+///
+/// Device clocks ahead of the server are clamped to `now`, scans cannot happen in the future.
+pub fn apply(
+    conn: &mut SqliteConnection,
+    actor: &Actor,
+    queued: QueuedAction,
+    now: NaiveDateTime,
+) -> QueryResult<SyncResult> {
+    conn.transaction(|conn| {
+        if let Some(existing) = ClientAction::get_by_client_id(conn, &queued.client_id)? {
+            if !existing.matches(&queued.group_id, &queued.action, &actor.audit_name()) {
+                return Ok(SyncResult {
+                    client_id: queued.client_id,
+                    group_id: queued.group_id,
+                    action: queued.action,
+                    status: SyncStatus::Conflict,
+                    message: Some("Deze scan-ID is al voor een andere actie gebruikt".to_string()),
+                    replayed: false,
+                });
+            }
+            return Ok(SyncResult::from_client_action(existing, true));
+        }
+
+        let at = DateTime::from_timestamp_millis(queued.at).map(|at| at.naive_utc().min(now));
+        let (status, message) = match (at, ScanAction::parse(&queued.action)) {
+            (None, _) => (SyncStatus::Rejected, Some("Ongeldige tijd".to_string())),
+            (_, None) => (SyncStatus::Rejected, Some("Onbekende actie".to_string())),
+            (Some(at), Some(action)) => {
+                match actions::replay(conn, actor, &queued.group_id, &action, at) {
+                    Ok(_) => (SyncStatus::Applied, None),
+                    Err(ActionError::Conflict(message)) => (SyncStatus::Conflict, Some(message)),
+                    Err(ActionError::OutOfOrder(message)) => (SyncStatus::Rejected, Some(message)),
+                    Err(ActionError::GroupNotFound) => {
+                        (SyncStatus::Rejected, Some("Onbekende groep".to_string()))
+                    }
+                    Err(ActionError::PostNotFound) => {
+                        (SyncStatus::Rejected, Some("Onbekende post".to_string()))
+                    }
+                    Err(ActionError::EventClosed) => (
+                        SyncStatus::Rejected,
+                        Some("Deze hike is gearchiveerd".to_string()),
+                    ),
+                    Err(ActionError::Forbidden) => (
+                        SyncStatus::Rejected,
+                        Some("Geen toegang tot deze actie".to_string()),
+                    ),
+                    Err(ActionError::Database(err)) => return Err(err),
+                }
+            }
+        };
+
+        let client_action = ClientAction {
+            client_id: queued.client_id,
+            group_id: queued.group_id,
+            action: queued.action,
+            status: status.as_str().to_string(),
+            message,
+            performed_at: at.unwrap_or(now),
+            received_at: now,
+            actor: actor.audit_name(),
+        };
+        ClientAction::insert(conn, &client_action)?;
+        Ok(SyncResult::from_client_action(client_action, false))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeDelta};
+
+    use super::*;
+    use crate::db::{test_conn, test_group, test_post};
+    use crate::models::{Group, Scan};
+
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 5, 1)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap()
+    }
+
+    fn admin() -> Actor {
+        Actor {
+            is_admin: true,
+            post_id: None,
+            admin_session: Some("sessie".to_string()),
+        }
+    }
+
+    fn queued(client_id: &str, group_id: &str, action: &str, at: NaiveDateTime) -> QueuedAction {
+        QueuedAction {
+            client_id: client_id.to_string(),
+            group_id: group_id.to_string(),
+            action: action.to_string(),
+            at: at.and_utc().timestamp_millis(),
+        }
+    }
+
+    #[test]
+    fn keeps_device_time_and_clamps_future_times() {
+        let mut conn = test_conn();
+        let group = test_group(&mut conn, "g1");
+        let first = test_post(&mut conn, "P1", 1);
+        let second = test_post(&mut conn, "P2", 2);
+        let earlier = now() - TimeDelta::minutes(5);
+
+        let arrive = format!("ARRIVE_{}", first.id);
+        let result = apply(
+            &mut conn,
+            &admin(),
+            queued("a", "g1", &arrive, earlier),
+            now(),
+        );
+        assert_eq!(result.unwrap().status, SyncStatus::Applied);
+        let arrive = format!("ARRIVE_{}", second.id);
+        let later = now() + TimeDelta::hours(1);
+        apply(
+            &mut conn,
+            &admin(),
+            queued("b", "g1", &arrive, later),
+            now(),
+        )
+        .unwrap();
+
+        let scans = Scan::get_by_group(&mut conn, &group.id).unwrap();
+        let arrival = |post_id: &str| scans.iter().find(|s| s.post_id == post_id).unwrap();
+        assert_eq!(arrival(&first.id).arrival_time, earlier);
+        assert_eq!(arrival(&second.id).arrival_time, now());
+    }
+
+    #[test]
+    fn replaying_a_client_id_has_no_further_effect() {
+        let mut conn = test_conn();
+        test_group(&mut conn, "g1");
+        let action = "__START_TIMER__";
+        let first = apply(&mut conn, &admin(), queued("a", "g1", action, now()), now()).unwrap();
+        assert!(!first.replayed);
+
+        let later = now() + TimeDelta::minutes(1);
+        let again = apply(&mut conn, &admin(), queued("a", "g1", action, later), later).unwrap();
+        assert!(again.replayed);
+        assert_eq!(again.status, SyncStatus::Applied);
+        let group = Group::get_by_id(&mut conn, "g1").unwrap().unwrap();
+        assert_eq!(group.start_time, Some(now()));
+    }
+
+    #[test]
+    fn client_id_reused_for_another_action_is_a_conflict() {
+        let mut conn = test_conn();
+        test_group(&mut conn, "g1");
+        test_group(&mut conn, "g2");
+        apply(
+            &mut conn,
+            &admin(),
+            queued("a", "g1", "__START_TIMER__", now()),
+            now(),
+        )
+        .unwrap();
+
+        for reused in [
+            queued("a", "g1", "__STOP_TIMER__", now()),
+            queued("a", "g2", "__START_TIMER__", now()),
+        ] {
+            let result = apply(&mut conn, &admin(), reused, now()).unwrap();
+            assert_eq!(result.status, SyncStatus::Conflict);
+            assert!(!result.replayed);
+        }
+    }
+
+    #[test]
+    fn actions_overtaken_by_another_device_are_conflicts() {
+        let mut conn = test_conn();
+        test_group(&mut conn, "g1");
+        let action = "__START_TIMER__";
+        apply(&mut conn, &admin(), queued("a", "g1", action, now()), now()).unwrap();
+
+        let result = apply(&mut conn, &admin(), queued("b", "g1", action, now()), now()).unwrap();
+        assert_eq!(result.status, SyncStatus::Conflict);
+        assert_eq!(
+            result.message.as_deref(),
+            Some("Groep is al gestart om 10:00:00")
+        );
+    }
+
+    #[test]
+    fn invalid_and_forbidden_actions_are_rejected_and_kept() {
+        let mut conn = test_conn();
+        test_group(&mut conn, "g1");
+        let post = test_post(&mut conn, "P1", 1);
+        let post_holder = Actor {
+            is_admin: false,
+            post_id: Some("andere-post".to_string()),
+            admin_session: None,
+        };
+        let arrive = format!("ARRIVE_{}", post.id);
+        let invalid_time = QueuedAction {
+            at: i64::MAX,
+            ..queued("d", "g1", &arrive, now())
+        };
+
+        let cases = [
+            (
+                admin(),
+                queued("a", "g1", "DANCE", now()),
+                "Onbekende actie",
+            ),
+            (
+                admin(),
+                queued("b", "onbekend", &arrive, now()),
+                "Onbekende groep",
+            ),
+            (
+                post_holder,
+                queued("c", "g1", &arrive, now()),
+                "Geen toegang tot deze actie",
+            ),
+            (admin(), invalid_time, "Ongeldige tijd"),
+        ];
+        for (actor, queued, message) in cases {
+            let client_id = queued.client_id.clone();
+            let result = apply(&mut conn, &actor, queued, now()).unwrap();
+            assert_eq!(result.status, SyncStatus::Rejected);
+            assert_eq!(result.message.as_deref(), Some(message));
+            // Kept, so a retry gets the same answer without performing anything
+            let kept = ClientAction::get_by_client_id(&mut conn, &client_id).unwrap();
+            assert_eq!(kept.unwrap().status, "rejected");
+        }
+    }
+}
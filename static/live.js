@@ -0,0 +1,92 @@
+// Live updates: on every change pushed by the server, fetch this page again and swap in the
+// rows of the affected group, or the whole section when rows were added, removed or reordered.
+// Pages for visitors without a login set `data-poll` on the script tag to refresh every that
+// many seconds instead, the event stream needs a login.
+(function() {
+    var script = document.currentScript;
+    var sections = document.querySelectorAll('[data-live]');
+    if (!sections.length) return;
+
+    var pending = null;
+    var busy = false;
+
+    function groupIds(section) {
+        return Array.prototype.map.call(section.querySelectorAll('[data-group-id]'), function(row) {
+            return row.getAttribute('data-group-id');
+        }).join(',');
+    }
+
+    function highlight(el) {
+        el.classList.add('live-updated');
+        setTimeout(function() { el.classList.remove('live-updated'); }, 2000);
+    }
+
+    function apply(doc, groupId) {
+        document.querySelectorAll('[data-live]').forEach(function(section) {
+            var fresh = doc.querySelector('[data-live="' + section.getAttribute('data-live') + '"]');
+            if (!fresh || fresh.innerHTML === section.innerHTML) return;
+
+            if (groupId && groupIds(section) === groupIds(fresh)) {
+                var selector = '[data-group-id="' + CSS.escape(groupId) + '"]';
+                var oldRows = section.querySelectorAll(selector);
+                var newRows = fresh.querySelectorAll(selector);
+                oldRows.forEach(function(row, i) {
+                    var row2 = document.importNode(newRows[i], true);
+                    row.replaceWith(row2);
+                    highlight(row2);
+                });
+                // Other parts, such as counts in headings, may have changed too
+                if (fresh.innerHTML === section.innerHTML) return;
+            }
+
+            var replacement = document.importNode(fresh, true);
+            section.replaceWith(replacement);
+            if (groupId) {
+                replacement.querySelectorAll('[data-group-id="' + CSS.escape(groupId) + '"]').forEach(highlight);
+            }
+        });
+    }
+
+    // Changes arriving while a refresh is running are combined into one follow-up refresh
+    function refresh(groupId) {
+        if (busy) {
+            pending = pending === null || pending === groupId ? groupId : '';
+            return;
+        }
+        busy = true;
+        fetch(window.location.href, { credentials: 'same-origin' })
+            .then(function(res) { return res.ok ? res.text() : Promise.reject(res.status); })
+            .then(function(html) {
+                apply(new DOMParser().parseFromString(html, 'text/html'), groupId);
+            })
+            .catch(function() {})
+            .then(function() {
+                busy = false;
+                if (pending !== null) {
+                    var next = pending;
+                    pending = null;
+                    refresh(next);
+                }
+            });
+    }
+
+    var poll = script && parseInt(script.getAttribute('data-poll'), 10);
+    if (poll > 0) {
+        setInterval(function() { refresh(''); }, poll * 1000);
+        return;
+    }
+    if (!window.EventSource) return;
+
+    var source = new EventSource('/live');
+    var connected = false;
+    source.addEventListener('update', function(e) {
+        var event = JSON.parse(e.data);
+        refresh(event.group_id);
+    });
+    source.addEventListener('resync', function() { refresh(''); });
+    source.addEventListener('open', function() {
+        // Catch up on changes missed while disconnected
+        if (connected) refresh('');
+        connected = true;
+    });
+})();
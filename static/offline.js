@@ -0,0 +1,68 @@
+// Registers the service worker for offline scanning, and shows scans that are waiting to be
+// sent or that could not be applied.
+(function() {
+    // The offline scan page is shown for any group, its forms post to the group in the URL
+    var actions = document.getElementById('offline-actions');
+    if (actions) {
+        var match = window.location.pathname.match(/^\/scan\/([^/]+)$/);
+        if (match && match[1] !== 'offline') {
+            document.getElementById('offline-group-id').textContent = decodeURIComponent(match[1]);
+            actions.querySelectorAll('form').forEach(function(form) {
+                form.action = window.location.pathname;
+            });
+            actions.hidden = false;
+        }
+    }
+
+    if (!('serviceWorker' in navigator)) return;
+    navigator.serviceWorker.register('/sw.js');
+
+    var banner = document.getElementById('offline-status');
+
+    function send(message) {
+        navigator.serviceWorker.ready.then(function(registration) {
+            if (registration.active) registration.active.postMessage(message);
+        });
+    }
+
+    function text(tag, content) {
+        var el = document.createElement(tag);
+        el.textContent = content;
+        return el;
+    }
+
+    function render(status) {
+        if (!banner) return;
+        banner.innerHTML = '';
+        if (status.sync_error) {
+            banner.appendChild(text('p', status.sync_error === 401
+                ? 'Bewaarde scans konden niet verstuurd worden: je bent niet meer ingelogd. Log opnieuw in.'
+                : 'Bewaarde scans konden niet verstuurd worden (fout ' + status.sync_error + ').'));
+        }
+        if (status.pending > 0) {
+            banner.appendChild(text('p', status.pending === 1
+                ? '1 scan wacht op verbinding.'
+                : status.pending + ' scans wachten op verbinding.'));
+        }
+        if (status.problems.length > 0) {
+            banner.appendChild(text('p', 'Deze offline scans zijn niet verwerkt:'));
+            var list = document.createElement('ul');
+            status.problems.forEach(function(problem) {
+                var label = 'Groep ' + problem.group_id + ' (' + problem.action + '): ' + (problem.message || problem.status);
+                list.appendChild(text('li', label));
+            });
+            banner.appendChild(list);
+            var dismiss = text('button', 'Gezien');
+            dismiss.type = 'button';
+            dismiss.addEventListener('click', function() { send({ type: 'dismiss' }); });
+            banner.appendChild(dismiss);
+        }
+        banner.hidden = !banner.childNodes.length;
+    }
+
+    navigator.serviceWorker.addEventListener('message', function(event) {
+        if (event.data && event.data.type === 'status') render(event.data);
+    });
+    window.addEventListener('online', function() { send({ type: 'flush' }); });
+    send({ type: 'status' });
+})();
@@ -0,0 +1,239 @@
+// Service worker for scanning without a connection. Scan form posts that fail for lack of a
+// connection are queued in IndexedDB with the device time and a client ID, and replayed through
+// /scan/sync once the connection is back. The server processes every client ID only once.
+var CACHE = 'hike-tracker-v1';
+var PRECACHE = [
+    '/scan/offline',
+    '/static/style.css',
+    '/static/long-press.js',
+    '/static/offline.js',
+    '/static/logo-small.jpg'
+];
+var SYNC_TAG = 'scan-queue';
+
+self.addEventListener('install', function(event) {
+    event.waitUntil(caches.open(CACHE).then(function(cache) {
+        return cache.addAll(PRECACHE);
+    }).then(function() {
+        return self.skipWaiting();
+    }));
+});
+
+self.addEventListener('activate', function(event) {
+    event.waitUntil(caches.keys().then(function(keys) {
+        return Promise.all(keys.filter(function(key) { return key !== CACHE; }).map(function(key) {
+            return caches.delete(key);
+        }));
+    }).then(function() {
+        return self.clients.claim();
+    }));
+});
+
+// ---- IndexedDB ----
+
+function openDb() {
+    return new Promise(function(resolve, reject) {
+        var request = indexedDB.open('hike-tracker', 1);
+        request.onupgradeneeded = function() {
+            request.result.createObjectStore('queue', { keyPath: 'client_id' });
+            request.result.createObjectStore('problems', { keyPath: 'client_id' });
+        };
+        request.onsuccess = function() { resolve(request.result); };
+        request.onerror = function() { reject(request.error); };
+    });
+}
+
+function store(name, mode, fn) {
+    return openDb().then(function(db) {
+        return new Promise(function(resolve, reject) {
+            var tx = db.transaction(name, mode);
+            var request = fn(tx.objectStore(name));
+            tx.oncomplete = function() { resolve(request && request.result); };
+            tx.onerror = function() { reject(tx.error); };
+        });
+    });
+}
+
+function getAll(name) {
+    return store(name, 'readonly', function(s) { return s.getAll(); });
+}
+
+// ---- Queue ----
+
+// HTTP status of the last refused sync, such as 401 after the login expired
+var syncError = null;
+
+function notifyClients() {
+    return Promise.all([getAll('queue'), getAll('problems')]).then(function(data) {
+        return self.clients.matchAll().then(function(clients) {
+            clients.forEach(function(client) {
+                client.postMessage({
+                    type: 'status',
+                    pending: data[0].length,
+                    problems: data[1],
+                    sync_error: syncError
+                });
+            });
+        });
+    });
+}
+
+var flushing = null;
+
+// Replay all queued actions in the order they were performed
+function flush() {
+    if (flushing) return flushing;
+    flushing = getAll('queue').then(function(queue) {
+        if (!queue.length) return;
+        queue.sort(function(a, b) { return a.at - b.at; });
+        return fetch('/scan/sync', {
+            method: 'POST',
+            credentials: 'same-origin',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ actions: queue })
+        }).then(function(res) {
+            if (!res.ok) {
+                var error = new Error('Sync failed with status ' + res.status);
+                error.status = res.status;
+                throw error;
+            }
+            syncError = null;
+            return res.json();
+        }).then(function(data) {
+            return store('queue', 'readwrite', function(s) {
+                data.results.forEach(function(result) { s.delete(result.client_id); });
+            }).then(function() {
+                return store('problems', 'readwrite', function(s) {
+                    data.results.forEach(function(result) {
+                        if (result.status !== 'applied') s.put(result);
+                    });
+                });
+            });
+        }).then(notifyClients);
+    }).catch(function(err) {
+        // Network errors mean still offline, try again later. A refused sync keeps the queue
+        // too, but is shown on the page so the user can log in again.
+        if (err && err.status) {
+            syncError = err.status;
+            return notifyClients();
+        }
+    }).then(function() {
+        flushing = null;
+    });
+    return flushing;
+}
+
+function queuedPage(groupId) {
+    var html = '<!DOCTYPE html><html lang="nl"><head><meta charset="UTF-8">' +
+        '<meta name="viewport" content="width=device-width, initial-scale=1.0">' +
+        '<title>Scan bewaard - Hike Tracker</title><link rel="stylesheet" href="/static/style.css">' +
+        '</head><body><div class="container">' +
+        '<div id="offline-status" class="card offline-status" hidden></div>' +
+        '<div class="card"><h1>Scan bewaard</h1>' +
+        '<p>Er is geen verbinding. De scan is op dit apparaat bewaard met de huidige tijd en wordt ' +
+        'verstuurd zodra er weer verbinding is.</p>' +
+        '<p style="margin-top: 1rem;"><a href="/scan/' + encodeURIComponent(groupId) + '" class="btn">Terug</a></p>' +
+        '</div></div><script src="/static/offline.js"></script></body></html>';
+    return new Response(html, { headers: { 'Content-Type': 'text/html; charset=utf-8' } });
+}
+
+function handleScanPost(request, groupId) {
+    var body = request.clone().formData();
+    var at = Date.now();
+    // Queued actions go first, so a departure never overtakes its arrival. Only a failing
+    // connection queues the post, if the server refused the sync it still gets the post and
+    // answers it like any other.
+    return flush().then(function() {
+        return fetch(request);
+    }).catch(function() {
+        return body.then(function(form) {
+            var entry = {
+                // The idempotency key of the form, so a post that did reach the server is not
+                // recorded twice
+                client_id: String(form.get('request_id') || self.crypto.randomUUID()),
+                group_id: groupId,
+                action: String(form.get('action') || ''),
+                at: at
+            };
+            return store('queue', 'readwrite', function(s) { return s.put(entry); });
+        }).then(function() {
+            if (self.registration.sync) self.registration.sync.register(SYNC_TAG).catch(function() {});
+            notifyClients();
+            return queuedPage(groupId);
+        });
+    });
+}
+
+// ---- Fetch ----
+
+var offlinePageFetched = 0;
+
+// The offline page shows the buttons of whoever is logged in, keep it up to date
+function refreshOfflinePage() {
+    if (Date.now() - offlinePageFetched < 60 * 1000) return;
+    offlinePageFetched = Date.now();
+    caches.open(CACHE).then(function(cache) { return cache.add('/scan/offline'); }).catch(function() {});
+}
+
+function networkFirst(request, fallbackUrl) {
+    return fetch(request).then(function(res) {
+        if (res.ok) {
+            var copy = res.clone();
+            caches.open(CACHE).then(function(cache) { cache.put(request, copy); });
+            refreshOfflinePage();
+            flush();
+        }
+        return res;
+    }).catch(function() {
+        return caches.match(request).then(function(cached) {
+            if (cached) return cached;
+            if (fallbackUrl) return caches.match(fallbackUrl);
+            return Response.error();
+        });
+    });
+}
+
+self.addEventListener('fetch', function(event) {
+    var request = event.request;
+    var url = new URL(request.url);
+    if (url.origin !== self.location.origin) return;
+
+    var scan = url.pathname.match(/^\/scan\/([^/]+)$/);
+    if (request.method === 'POST' && scan && scan[1] !== 'sync') {
+        event.respondWith(handleScanPost(request, decodeURIComponent(scan[1])));
+        return;
+    }
+    if (request.method !== 'GET') return;
+
+    if (url.pathname.indexOf('/static/') === 0) {
+        // Serve from the cache, and update the cache in the background
+        event.respondWith(caches.open(CACHE).then(function(cache) {
+            return cache.match(request).then(function(cached) {
+                var fetched = fetch(request).then(function(res) {
+                    if (res.ok) cache.put(request, res.clone());
+                    return res;
+                });
+                if (!cached) return fetched;
+                fetched.catch(function() {});
+                return cached;
+            });
+        }));
+    } else if (request.mode === 'navigate') {
+        event.respondWith(networkFirst(request, scan ? '/scan/offline' : null));
+    }
+});
+
+self.addEventListener('sync', function(event) {
+    if (event.tag === SYNC_TAG) event.waitUntil(flush());
+});
+
+self.addEventListener('message', function(event) {
+    var type = event.data && event.data.type;
+    if (type === 'flush') {
+        event.waitUntil(flush().then(notifyClients));
+    } else if (type === 'status') {
+        event.waitUntil(notifyClients());
+    } else if (type === 'dismiss') {
+        event.waitUntil(store('problems', 'readwrite', function(s) { return s.clear(); }).then(notifyClients));
+    }
+});